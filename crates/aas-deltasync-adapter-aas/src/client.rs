@@ -41,6 +41,7 @@ impl Default for AasClientConfig {
 }
 
 /// HTTP client for AAS Part 2 API operations.
+#[derive(Clone)]
 pub struct AasClient {
     client: Client,
     config: AasClientConfig,
@@ -157,6 +158,43 @@ impl AasClient {
             .map_err(|e| ClientError::Parse(e.to_string()))
     }
 
+    /// Get a submodel in normal serialization.
+    ///
+    /// Unlike `$value`, the normal serialization carries the `modelType`
+    /// of every element.
+    ///
+    /// # Errors
+    ///
+    /// Returns error on network or API errors.
+    pub async fn get_submodel(&self, submodel_id: &str) -> Result<Value, ClientError> {
+        let encoded_id = encode_id_base64url(submodel_id);
+        let url = format!("{}/submodels/{}", self.config.base_url, encoded_id);
+
+        tracing::debug!(submodel_id, url, "GET submodel");
+
+        let mut request = self.client.get(&url);
+        if let Some(auth) = self.auth_header() {
+            request = request.header("Authorization", auth);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ClientError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ClientError::Parse(e.to_string()))
+    }
+
     /// Get the value of a specific submodel element.
     ///
     /// # Errors
@@ -246,6 +284,211 @@ impl AasClient {
         Ok(())
     }
 
+    /// Create a submodel element.
+    ///
+    /// The element is added at the top level of the submodel if
+    /// `parent_path` is `None`, otherwise to the collection or list at
+    /// `parent_path`. `element` must be in normal serialization, including
+    /// its `modelType`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error on network or API errors.
+    pub async fn post_submodel_element(
+        &self,
+        submodel_id: &str,
+        parent_path: Option<&str>,
+        element: &Value,
+    ) -> Result<(), ClientError> {
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+        let url = match parent_path {
+            Some(parent_path) => format!(
+                "{}/submodels/{}/submodel-elements/{}",
+                self.config.base_url,
+                encoded_sm_id,
+                encode_idshort_path(parent_path)
+            ),
+            None => format!(
+                "{}/submodels/{}/submodel-elements",
+                self.config.base_url, encoded_sm_id
+            ),
+        };
+
+        tracing::debug!(submodel_id, parent_path, url, "POST element");
+
+        let mut request = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(element);
+
+        if let Some(auth) = self.auth_header() {
+            request = request.header("Authorization", auth);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ClientError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Delete a submodel element and everything below it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error on network or API errors.
+    pub async fn delete_submodel_element(
+        &self,
+        submodel_id: &str,
+        id_short_path: &str,
+    ) -> Result<(), ClientError> {
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+        let encoded_path = encode_idshort_path(id_short_path);
+        let url = format!(
+            "{}/submodels/{}/submodel-elements/{}",
+            self.config.base_url, encoded_sm_id, encoded_path
+        );
+
+        tracing::debug!(submodel_id, id_short_path, url, "DELETE element");
+
+        let mut request = self.client.delete(&url);
+        if let Some(auth) = self.auth_header() {
+            request = request.header("Authorization", auth);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ClientError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Download the attachment of a `File` element.
+    ///
+    /// Returns the content and its content type, if the server sent one.
+    ///
+    /// # Errors
+    ///
+    /// Returns error on network or API errors.
+    pub async fn get_attachment(
+        &self,
+        submodel_id: &str,
+        id_short_path: &str,
+    ) -> Result<(Vec<u8>, Option<String>), ClientError> {
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+        let encoded_path = encode_idshort_path(id_short_path);
+        let url = format!(
+            "{}/submodels/{}/submodel-elements/{}/attachment",
+            self.config.base_url, encoded_sm_id, encoded_path
+        );
+
+        tracing::debug!(submodel_id, id_short_path, url, "GET attachment");
+
+        let mut request = self.client.get(&url);
+        if let Some(auth) = self.auth_header() {
+            request = request.header("Authorization", auth);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ClientError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        Ok((bytes.to_vec(), content_type))
+    }
+
+    /// Upload the attachment of a `File` element.
+    ///
+    /// The content is sent as `multipart/form-data` with a `file` part
+    /// named `file_name` and a `fileName` part, per AAS Part 2.
+    ///
+    /// # Errors
+    ///
+    /// Returns error on network or API errors.
+    pub async fn put_attachment(
+        &self,
+        submodel_id: &str,
+        id_short_path: &str,
+        file_name: &str,
+        content_type: &str,
+        content: &[u8],
+    ) -> Result<(), ClientError> {
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+        let encoded_path = encode_idshort_path(id_short_path);
+        let url = format!(
+            "{}/submodels/{}/submodel-elements/{}/attachment",
+            self.config.base_url, encoded_sm_id, encoded_path
+        );
+
+        tracing::debug!(submodel_id, id_short_path, url, "PUT attachment");
+
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        let boundary = format!("aas-deltasync-{nonce:x}");
+        let body = multipart_body(&boundary, file_name, content_type, content);
+        let mut request = self
+            .client
+            .put(&url)
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body);
+
+        if let Some(auth) = self.auth_header() {
+            request = request.header("Authorization", auth);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ClientError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        Ok(())
+    }
+
     /// Get all submodel descriptors from a submodel repository.
     ///
     /// # Errors
@@ -289,6 +532,19 @@ impl AasClient {
     }
 }
 
+/// Encode an attachment upload as a `multipart/form-data` body.
+fn multipart_body(boundary: &str, file_name: &str, content_type: &str, content: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"fileName\"\r\n\r\n{file_name}\r\n\
+         --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+         Content-Type: {content_type}\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
 /// Errors that can occur with the AAS client.
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum ClientError {
@@ -311,6 +567,14 @@ pub enum ClientError {
     Parse(String),
 }
 
+impl ClientError {
+    /// Whether the server reported that the addressed resource does not exist.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::ApiError { status: 404, .. })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,4 +627,16 @@ mod tests {
         let err_msg = format!("{}", result.err().unwrap());
         assert!(err_msg.contains("client init error"));
     }
+
+    #[test]
+    fn attachment_uploads_are_multipart() {
+        let body = multipart_body("b0", "manual.pdf", "application/pdf", b"%PDF");
+        let body = String::from_utf8(body).unwrap();
+
+        assert!(body.starts_with(
+            "--b0\r\nContent-Disposition: form-data; name=\"fileName\"\r\n\r\nmanual.pdf\r\n"
+        ));
+        assert!(body.contains("name=\"file\"; filename=\"manual.pdf\"\r\nContent-Type: application/pdf\r\n\r\n%PDF\r\n"));
+        assert!(body.ends_with("--b0--\r\n"));
+    }
 }
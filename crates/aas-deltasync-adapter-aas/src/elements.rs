@@ -0,0 +1,605 @@
+//! Construction of submodel elements from `$value` payloads.
+//!
+//! Replicated documents only carry element values, so creating an element on
+//! an AAS server requires picking a `modelType` from the shape of the value:
+//!
+//! - JSON scalars become a `Property` with a matching `valueType`
+//! - Arrays of single-entry `{language: text}` objects become a
+//!   `MultiLanguageProperty`
+//! - Other arrays become a `SubmodelElementList`
+//! - Objects become a `SubmodelElementCollection`
+//!
+//! When the element type is known, [`typed_element_from_value`] builds it
+//! explicitly instead.
+//!
+//! In the other direction, [`flatten_value`] maps a `$value` payload to the
+//! idShortPath keys documents are replicated under, and [`element_types`]
+//! reads the element types of a submodel in normal serialization.
+
+use aas_deltasync_core::{ElementType, ElementTypes, MergeStrategy};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Build a submodel element in normal serialization from its `$value`.
+///
+/// `id_short` is `None` for elements of a `SubmodelElementList`, which
+/// carry no idShort.
+#[must_use]
+pub fn element_from_value(id_short: Option<&str>, value: &Value) -> Value {
+    let mut element = match value {
+        Value::Array(items) if is_multi_language(items) => multi_language_property(items),
+        Value::Array(items) => submodel_element_list(items),
+        Value::Object(children) => json!({
+            "modelType": "SubmodelElementCollection",
+            "value": children
+                .iter()
+                .map(|(id_short, child)| element_from_value(Some(id_short), child))
+                .collect::<Vec<_>>(),
+        }),
+        scalar => property(scalar),
+    };
+
+    if let (Some(id_short), Some(fields)) = (id_short, element.as_object_mut()) {
+        fields.insert("idShort".to_string(), Value::String(id_short.to_string()));
+    }
+    element
+}
+
+/// Build a submodel element of a known type from its `$value`.
+///
+/// The `$value` of a `Range` reads like a collection and that of a `File`
+/// or `Blob` like a collection of two properties, so these are built from
+/// their type; every other type falls back to [`element_from_value`].
+#[must_use]
+pub fn typed_element_from_value(
+    element_type: ElementType,
+    id_short: Option<&str>,
+    value: &Value,
+) -> Value {
+    let mut element = match (element_type, value) {
+        (ElementType::Range, Value::Object(bounds)) => {
+            let mut fields = Map::new();
+            fields.insert("modelType".to_string(), json!("Range"));
+            for bound in ["min", "max"] {
+                if let Some(bound_value) = bounds.get(bound) {
+                    let bound_property = property(bound_value);
+                    if let Some(value_type) = bound_property.get("valueType") {
+                        fields.insert("valueType".to_string(), value_type.clone());
+                    }
+                    if let Some(text) = bound_property.get("value") {
+                        fields.insert(bound.to_string(), text.clone());
+                    }
+                }
+            }
+            Value::Object(fields)
+        }
+        (ElementType::File | ElementType::Blob, Value::Object(content)) => json!({
+            "modelType": if element_type == ElementType::File { "File" } else { "Blob" },
+            "contentType": content.get("contentType").cloned().unwrap_or(Value::Null),
+            "value": content.get("value").cloned().unwrap_or(Value::Null),
+        }),
+        (ElementType::MultiLanguageProperty, Value::Array(items)) => multi_language_property(items),
+        _ => return element_from_value(id_short, value),
+    };
+
+    if let (Some(id_short), Some(fields)) = (id_short, element.as_object_mut()) {
+        fields.insert("idShort".to_string(), Value::String(id_short.to_string()));
+    }
+    element
+}
+
+/// Flatten a `$value` payload into leaf values keyed by idShortPath.
+///
+/// Object members are joined with `.` and array entries addressed as
+/// `[index]`, so `{"Motor": {"Phases": [230]}}` yields `Motor.Phases[0]`.
+/// Elements listed in `types` follow their merge strategy instead:
+/// per-field elements yield one entry per field and content-addressed
+/// ones a single entry for their whole value.
+#[must_use]
+pub fn flatten_value(value: &Value, types: &ElementTypes) -> BTreeMap<String, Value> {
+    let mut leaves = BTreeMap::new();
+    flatten_into(value, "", types, &mut leaves);
+    leaves
+}
+
+fn flatten_into(
+    value: &Value,
+    path: &str,
+    types: &ElementTypes,
+    leaves: &mut BTreeMap<String, Value>,
+) {
+    match types.get(path).map(|ty| ty.merge_strategy()) {
+        Some(MergeStrategy::PerFieldLww) => {
+            leaves.extend(types.split(path, value));
+            return;
+        }
+        Some(MergeStrategy::ContentAddressed) => {
+            leaves.insert(path.to_string(), value.clone());
+            return;
+        }
+        _ => {}
+    }
+
+    match value {
+        Value::Object(members) => {
+            for (key, member) in members {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten_into(member, &child_path, types, leaves);
+            }
+        }
+        Value::Array(entries) => {
+            for (index, entry) in entries.iter().enumerate() {
+                flatten_into(entry, &format!("{path}[{index}]"), types, leaves);
+            }
+        }
+        _ => {
+            if !path.is_empty() {
+                leaves.insert(path.to_string(), value.clone());
+            }
+        }
+    }
+}
+
+/// Rebuild the `$value` of the element at `root` from flattened leaves.
+///
+/// The inverse of [`flatten_value`]: leaves under `root` are nested back
+/// into objects and arrays, and per-field elements are joined. Returns
+/// `None` if no leaf lies under `root`.
+#[must_use]
+pub fn unflatten_value(
+    root: &str,
+    leaves: &BTreeMap<String, Value>,
+    types: &ElementTypes,
+) -> Option<Value> {
+    let mut tree: Option<Node> = None;
+    for (path, value) in leaves.range(root.to_string()..) {
+        let Some(rest) = path.strip_prefix(root) else {
+            break;
+        };
+        if !(root.is_empty() || rest.is_empty() || rest.starts_with(['.', '['])) {
+            continue;
+        }
+        let mut node = tree.get_or_insert_with(Node::default);
+        for segment in path_segments(rest) {
+            node = node.child(segment);
+        }
+        node.value = Some(value.clone());
+    }
+    tree.map(|node| node.into_value(root, types))
+}
+
+/// A partially rebuilt element.
+#[derive(Default)]
+struct Node {
+    value: Option<Value>,
+    members: BTreeMap<String, Node>,
+    entries: BTreeMap<usize, Node>,
+}
+
+#[derive(Clone, Copy)]
+enum Segment<'a> {
+    Member(&'a str),
+    Entry(usize),
+}
+
+impl Node {
+    fn child(&mut self, segment: Segment<'_>) -> &mut Node {
+        match segment {
+            Segment::Member(key) => self.members.entry(key.to_string()).or_default(),
+            Segment::Entry(index) => self.entries.entry(index).or_default(),
+        }
+    }
+
+    fn into_value(self, path: &str, types: &ElementTypes) -> Value {
+        if !self.entries.is_empty() {
+            return Value::Array(
+                self.entries
+                    .into_iter()
+                    .map(|(index, entry)| entry.into_value(&format!("{path}[{index}]"), types))
+                    .collect(),
+            );
+        }
+        if self.members.is_empty() {
+            return self.value.unwrap_or(Value::Null);
+        }
+        if types.get(path).map(|ty| ty.merge_strategy()) == Some(MergeStrategy::PerFieldLww) {
+            let fields: Vec<(&str, &Value)> = self
+                .members
+                .iter()
+                .filter_map(|(field, node)| Some((field.as_str(), node.value.as_ref()?)))
+                .collect();
+            return types.join(path, fields);
+        }
+        Value::Object(
+            self.members
+                .into_iter()
+                .map(|(key, member)| {
+                    let child_path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    let value = member.into_value(&child_path, types);
+                    (key, value)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Split a relative idShortPath such as `.Motor[0].Speed` into segments.
+fn path_segments(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(entry) = rest.strip_prefix('[') {
+            let close = entry.find(']').unwrap_or(entry.len());
+            if let Ok(index) = entry[..close].parse() {
+                segments.push(Segment::Entry(index));
+            }
+            rest = entry.get(close + 1..).unwrap_or_default();
+        } else {
+            let member = rest.strip_prefix('.').unwrap_or(rest);
+            let end = member.find(['.', '[']).unwrap_or(member.len());
+            segments.push(Segment::Member(&member[..end]));
+            rest = &member[end..];
+        }
+    }
+    segments
+}
+
+/// Read the element types and `semanticId`s of a submodel in normal
+/// serialization.
+///
+/// Paths use the same syntax as [`flatten_value`]: collection children
+/// are joined with `.` and list entries addressed as `[index]`.
+#[must_use]
+pub fn element_types(submodel: &Value) -> ElementTypes {
+    let mut types = ElementTypes::new();
+    if let Some(elements) = submodel.get("submodelElements").and_then(Value::as_array) {
+        collect_types(elements, None, false, &mut types);
+    }
+    types
+}
+
+fn collect_types(
+    elements: &[Value],
+    parent: Option<&str>,
+    in_list: bool,
+    types: &mut ElementTypes,
+) {
+    for (index, element) in elements.iter().enumerate() {
+        let id_short = element.get("idShort").and_then(Value::as_str);
+        let path = match (parent, id_short) {
+            (Some(parent), _) if in_list => format!("{parent}[{index}]"),
+            (Some(parent), Some(id_short)) => format!("{parent}.{id_short}"),
+            (None, Some(id_short)) => id_short.to_string(),
+            (_, None) => continue,
+        };
+
+        let Some(element_type) = element
+            .get("modelType")
+            .and_then(Value::as_str)
+            .and_then(ElementType::from_model_type)
+        else {
+            continue;
+        };
+
+        if let Some(Value::Array(children)) = element.get("value") {
+            match element_type {
+                ElementType::SubmodelElementCollection => {
+                    collect_types(children, Some(&path), false, types);
+                }
+                ElementType::SubmodelElementList => {
+                    collect_types(children, Some(&path), true, types);
+                }
+                _ => {}
+            }
+        }
+        if let Some(semantic_id) = semantic_id(element) {
+            types.insert_semantic_id(path.clone(), semantic_id);
+        }
+        types.insert(path, element_type);
+    }
+}
+
+/// The `semanticId` of an element: the value of the last key of its
+/// reference.
+fn semantic_id(element: &Value) -> Option<&str> {
+    element
+        .get("semanticId")?
+        .get("keys")?
+        .as_array()?
+        .last()?
+        .get("value")?
+        .as_str()
+}
+
+/// Split an idShortPath into its parent path and last segment.
+///
+/// The last segment is either an idShort or a list index such as `[2]`.
+///
+/// # Examples
+///
+/// ```
+/// use aas_deltasync_adapter_aas::split_idshort_path;
+///
+/// assert_eq!(split_idshort_path("Temperature"), (None, "Temperature"));
+/// assert_eq!(split_idshort_path("Motor.Speed"), (Some("Motor"), "Speed"));
+/// assert_eq!(split_idshort_path("Phases[2]"), (Some("Phases"), "[2]"));
+/// ```
+#[must_use]
+pub fn split_idshort_path(path: &str) -> (Option<&str>, &str) {
+    let split = if path.ends_with(']') {
+        path.rfind('[')
+    } else {
+        path.rfind('.').map(|dot| dot + 1)
+    };
+
+    match split {
+        Some(0) | None => (None, path),
+        Some(at) => (Some(path[..at].trim_end_matches('.')), &path[at..]),
+    }
+}
+
+/// Build a `Property` from a scalar value.
+fn property(value: &Value) -> Value {
+    let (value_type, text) = match value {
+        Value::Bool(flag) => ("xs:boolean", Some(flag.to_string())),
+        Value::Number(number) if number.is_f64() => ("xs:double", Some(number.to_string())),
+        Value::Number(number) => ("xs:long", Some(number.to_string())),
+        Value::String(text) => ("xs:string", Some(text.clone())),
+        _ => ("xs:string", None),
+    };
+
+    let mut fields = Map::new();
+    fields.insert("modelType".to_string(), json!("Property"));
+    fields.insert("valueType".to_string(), json!(value_type));
+    if let Some(text) = text {
+        fields.insert("value".to_string(), Value::String(text));
+    }
+    Value::Object(fields)
+}
+
+/// Whether an array is the `$value` of a `MultiLanguageProperty`.
+fn is_multi_language(items: &[Value]) -> bool {
+    !items.is_empty()
+        && items.iter().all(|item| {
+            item.as_object()
+                .is_some_and(|entry| entry.len() == 1 && entry.values().all(Value::is_string))
+        })
+}
+
+/// Build a `MultiLanguageProperty` from `[{language: text}, ...]`.
+fn multi_language_property(items: &[Value]) -> Value {
+    let texts: Vec<Value> = items
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|entry| {
+            entry
+                .iter()
+                .map(|(language, text)| json!({ "language": language, "text": text }))
+        })
+        .collect();
+
+    json!({
+        "modelType": "MultiLanguageProperty",
+        "value": texts,
+    })
+}
+
+/// Build a `SubmodelElementList` whose type is taken from its first entry.
+fn submodel_element_list(items: &[Value]) -> Value {
+    let elements: Vec<Value> = items
+        .iter()
+        .map(|item| element_from_value(None, item))
+        .collect();
+
+    let mut fields = Map::new();
+    fields.insert("modelType".to_string(), json!("SubmodelElementList"));
+    if let Some(first) = elements.first() {
+        if let Some(model_type) = first.get("modelType") {
+            fields.insert("typeValueListElement".to_string(), model_type.clone());
+        }
+        if let Some(value_type) = first.get("valueType") {
+            fields.insert("valueTypeListElement".to_string(), value_type.clone());
+        }
+    }
+    fields.insert("value".to_string(), Value::Array(elements));
+    Value::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalars_become_typed_properties() {
+        assert_eq!(
+            element_from_value(Some("Temperature"), &json!(25.5)),
+            json!({
+                "modelType": "Property",
+                "idShort": "Temperature",
+                "valueType": "xs:double",
+                "value": "25.5",
+            })
+        );
+        assert_eq!(
+            element_from_value(Some("Count"), &json!(3))["valueType"],
+            json!("xs:long")
+        );
+        assert_eq!(
+            element_from_value(Some("Running"), &json!(true))["value"],
+            json!("true")
+        );
+        assert_eq!(
+            element_from_value(Some("Status"), &json!("Idle"))["valueType"],
+            json!("xs:string")
+        );
+    }
+
+    #[test]
+    fn objects_become_collections() {
+        let element = element_from_value(Some("Motor"), &json!({"Speed": 1200}));
+
+        assert_eq!(element["modelType"], json!("SubmodelElementCollection"));
+        assert_eq!(element["value"][0]["idShort"], json!("Speed"));
+        assert_eq!(element["value"][0]["modelType"], json!("Property"));
+    }
+
+    #[test]
+    fn arrays_become_lists_or_multi_language_properties() {
+        let name = element_from_value(Some("Name"), &json!([{"en": "Pump"}, {"de": "Pumpe"}]));
+        assert_eq!(name["modelType"], json!("MultiLanguageProperty"));
+        assert_eq!(name["value"][1], json!({"language": "de", "text": "Pumpe"}));
+
+        let phases = element_from_value(Some("Phases"), &json!([230.0, 231.5]));
+        assert_eq!(phases["modelType"], json!("SubmodelElementList"));
+        assert_eq!(phases["typeValueListElement"], json!("Property"));
+        assert_eq!(phases["valueTypeListElement"], json!("xs:double"));
+        assert!(phases["value"][0].get("idShort").is_none());
+    }
+
+    #[test]
+    fn flatten_value_paths() {
+        let leaves = flatten_value(
+            &json!({
+                "Temperature": 25.5,
+                "Motor": {"Speed": 1200, "Phases": [230.1, 229.8]}
+            }),
+            &ElementTypes::new(),
+        );
+
+        let paths: Vec<_> = leaves.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            [
+                "Motor.Phases[0]",
+                "Motor.Phases[1]",
+                "Motor.Speed",
+                "Temperature"
+            ]
+        );
+        assert_eq!(leaves["Motor.Speed"], json!(1200));
+    }
+
+    #[test]
+    fn unflatten_value_inverts_flatten() {
+        let types = element_types(&json!({
+            "modelType": "Submodel",
+            "submodelElements": [
+                {"modelType": "SubmodelElementList", "idShort": "Labels", "value": [
+                    {"modelType": "MultiLanguageProperty", "value": []}
+                ]}
+            ]
+        }));
+        let value = json!({
+            "Labels": [[{"en": "Pump"}]],
+            "Motor": {"Speed": 1200, "Phases": [230.1, 229.8]}
+        });
+        let leaves = flatten_value(&value, &types);
+
+        assert_eq!(
+            unflatten_value("Motor.Phases", &leaves, &types),
+            Some(json!([230.1, 229.8]))
+        );
+        assert_eq!(
+            unflatten_value("Labels", &leaves, &types),
+            Some(json!([[{"en": "Pump"}]]))
+        );
+        assert_eq!(unflatten_value("", &leaves, &types), Some(value));
+        assert_eq!(unflatten_value("Motor.Torque", &leaves, &types), None);
+    }
+
+    #[test]
+    fn typed_flatten_follows_merge_strategies() {
+        let types = element_types(&json!({
+            "modelType": "Submodel",
+            "submodelElements": [
+                {"modelType": "Range", "idShort": "Limits", "valueType": "xs:int", "min": "0", "max": "100"},
+                {"modelType": "SubmodelElementCollection", "idShort": "Nameplate", "value": [
+                    {"modelType": "MultiLanguageProperty", "idShort": "Name", "value": [],
+                     "semanticId": {"type": "ExternalReference", "keys": [
+                         {"type": "GlobalReference", "value": "0173-1#02-AAO677#002"}
+                     ]}},
+                    {"modelType": "File", "idShort": "Manual", "contentType": "application/pdf"}
+                ]}
+            ]
+        }));
+        assert_eq!(
+            types.get("Nameplate.Name"),
+            Some(ElementType::MultiLanguageProperty)
+        );
+        assert_eq!(
+            types.semantic_id("Nameplate.Name"),
+            Some("0173-1#02-AAO677#002")
+        );
+        assert_eq!(types.semantic_id("Limits"), None);
+
+        let leaves = flatten_value(
+            &json!({
+                "Limits": {"min": 0, "max": 100},
+                "Nameplate": {
+                    "Name": [{"en": "Pump"}, {"de": "Pumpe"}],
+                    "Manual": {"contentType": "application/pdf", "value": "/manual.pdf"}
+                }
+            }),
+            &types,
+        );
+
+        let paths: Vec<_> = leaves.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            [
+                "Limits.max",
+                "Limits.min",
+                "Nameplate.Manual",
+                "Nameplate.Name.de",
+                "Nameplate.Name.en"
+            ]
+        );
+    }
+
+    #[test]
+    fn typed_elements_are_built_from_their_type() {
+        let range = typed_element_from_value(
+            ElementType::Range,
+            Some("Limits"),
+            &json!({"min": 0, "max": 100}),
+        );
+        assert_eq!(
+            range,
+            json!({
+                "modelType": "Range",
+                "idShort": "Limits",
+                "valueType": "xs:long",
+                "min": "0",
+                "max": "100",
+            })
+        );
+
+        let manual = typed_element_from_value(
+            ElementType::File,
+            Some("Manual"),
+            &json!({"contentType": "application/pdf", "value": "/manual.pdf"}),
+        );
+        assert_eq!(manual["modelType"], json!("File"));
+        assert_eq!(manual["contentType"], json!("application/pdf"));
+    }
+
+    #[test]
+    fn split_nested_paths() {
+        assert_eq!(
+            split_idshort_path("Motor.Phases[2]"),
+            (Some("Motor.Phases"), "[2]")
+        );
+        assert_eq!(
+            split_idshort_path("Phases[2].Voltage"),
+            (Some("Phases[2]"), "Voltage")
+        );
+    }
+}
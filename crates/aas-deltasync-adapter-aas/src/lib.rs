@@ -14,9 +14,14 @@
 #![warn(clippy::all)]
 
 pub mod client;
+pub mod elements;
 pub mod encoding;
 
-pub use client::{AasClient, AasClientConfig};
+pub use client::{AasClient, AasClientConfig, ClientError};
+pub use elements::{
+    element_from_value, element_types, flatten_value, split_idshort_path, typed_element_from_value,
+    unflatten_value,
+};
 pub use encoding::{
     decode_id_base64url, decode_idshort_path, encode_id_base64url, encode_idshort_path,
 };
@@ -1,6 +1,7 @@
 //! `BaSyx` MQTT event types.
 
 use aas_deltasync_adapter_aas::decode_id_base64url;
+use aas_deltasync_core::ElementType;
 use serde::{Deserialize, Serialize};
 
 /// Type of `BaSyx` event.
@@ -31,6 +32,16 @@ impl EventType {
     }
 }
 
+/// What a `BaSyx` event is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventScope {
+    /// A whole submodel was created, replaced or deleted
+    Submodel,
+    /// One or more submodel elements changed
+    Element,
+}
+
 /// A parsed `BaSyx` MQTT event.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BasyxEvent {
@@ -40,8 +51,12 @@ pub struct BasyxEvent {
     pub submodel_id: String,
     /// Type of event
     pub event_type: EventType,
-    /// Element-level details (if applicable)
-    pub element: Option<ElementEvent>,
+    /// Whether the event concerns the whole submodel or its elements
+    pub scope: EventScope,
+    /// Changed elements: one for element-level events, every patched
+    /// element for `patched` events, and every leaf element of the new
+    /// submodel for submodel-level `created` and `updated` events
+    pub elements: Vec<ElementEvent>,
     /// Raw event payload
     pub payload: serde_json::Value,
 }
@@ -51,8 +66,11 @@ pub struct BasyxEvent {
 pub struct ElementEvent {
     /// idShortPath of the affected element
     pub id_short_path: String,
-    /// New value (if present in event)
+    /// New value (if present in event), in `$value` form for ranges,
+    /// files and blobs
     pub value: Option<serde_json::Value>,
+    /// Element type, if the payload carries a `modelType`
+    pub element_type: Option<ElementType>,
 }
 
 impl BasyxEvent {
@@ -62,6 +80,10 @@ impl BasyxEvent {
     ///
     /// `sm-repository/{repoId}/submodels/{submodelIdBase64}/submodelElements/{idShortPath}/{eventType}`
     ///
+    /// or, for submodel-level events,
+    ///
+    /// `sm-repository/{repoId}/submodels/{submodelIdBase64}/{eventType}`
+    ///
     /// # Errors
     ///
     /// Returns error if the topic format is invalid.
@@ -89,13 +111,7 @@ impl BasyxEvent {
         let submodel_id = decode_id_base64url(submodel_id_encoded)
             .map_err(|e| EventParseError::DecodeError(e.to_string()))?;
 
-        // Parse payload
-        let payload: serde_json::Value = if payload.is_empty() {
-            serde_json::Value::Null
-        } else {
-            serde_json::from_slice(payload)
-                .map_err(|e| EventParseError::PayloadParse(e.to_string()))?
-        };
+        let payload = parse_payload(payload)?;
 
         // Determine event type and element path
         let event_type_str = parts.last().unwrap_or(&"");
@@ -103,46 +119,270 @@ impl BasyxEvent {
             .ok_or_else(|| EventParseError::UnknownEventType((*event_type_str).to_string()))?;
 
         // Check if this is an element-level event
-        let element =
-            if let Some(elements_idx) = parts.iter().position(|&p| p == "submodelElements") {
-                if elements_idx + 1 < parts.len() - 1 {
-                    // There's a path between submodelElements and event type
-                    let path_parts = &parts[elements_idx + 1..parts.len() - 1];
-                    let id_short_path = path_parts.join("/");
-
-                    // Extract value from payload if present
-                    let value = payload.get("value").cloned().or_else(|| {
-                        // Some events have the value directly
-                        if payload.is_object() && payload.get("modelType").is_some() {
-                            payload.get("value").cloned()
-                        } else if !payload.is_null() && !payload.is_object() {
-                            Some(payload.clone())
-                        } else {
-                            None
-                        }
-                    });
-
-                    Some(ElementEvent {
-                        id_short_path,
-                        value,
-                    })
-                } else {
-                    None
-                }
+        let elements_idx = parts.iter().position(|&p| p == "submodelElements");
+        let scope = if elements_idx.is_some() {
+            EventScope::Element
+        } else {
+            EventScope::Submodel
+        };
+
+        let elements = if let Some(elements_idx) = elements_idx {
+            if event_type == EventType::Patched {
+                patched_elements(&payload)
+            } else if elements_idx + 1 < parts.len() - 1 {
+                // There's a path between submodelElements and event type
+                let path_parts = &parts[elements_idx + 1..parts.len() - 1];
+                let id_short_path = path_parts.join("/");
+
+                // Extract value from payload if present
+                let value = element_value(&payload).or_else(|| {
+                    // Some events have the value directly
+                    if !payload.is_null() && !payload.is_object() {
+                        Some(payload.clone())
+                    } else {
+                        None
+                    }
+                });
+
+                vec![ElementEvent {
+                    id_short_path,
+                    value,
+                    element_type: element_type(&payload),
+                }]
             } else {
-                None
-            };
+                Vec::new()
+            }
+        } else {
+            submodel_elements(event_type, &payload)
+        };
 
         Ok(Self {
             repo_id,
             submodel_id,
             event_type,
-            element,
+            scope,
+            elements,
             payload,
         })
     }
 }
 
+/// A parsed `BaSyx` AAS repository (shell) event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellEvent {
+    /// Repository ID
+    pub repo_id: String,
+    /// AAS identifier (decoded from base64url)
+    pub aas_id: String,
+    /// Type of event
+    pub event_type: EventType,
+    /// Submodels the shell references, if the payload lists them
+    pub submodel_ids: Vec<String>,
+    /// Raw event payload
+    pub payload: serde_json::Value,
+}
+
+impl ShellEvent {
+    /// Parse a shell event from an MQTT topic and payload.
+    ///
+    /// # Topic Format
+    ///
+    /// `aas-repository/{repoId}/shells/{aasIdBase64}/{eventType}`
+    ///
+    /// or `aas-repository/{repoId}/shells/{eventType}`, in which case the
+    /// AAS identifier is taken from the shell in the payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the topic format is invalid.
+    pub fn parse(topic: &str, payload: &[u8]) -> Result<Self, EventParseError> {
+        let parts: Vec<&str> = topic.split('/').collect();
+
+        // Minimum: aas-repository/repo/shells/{eventType}
+        if parts.len() < 4 || parts[0] != "aas-repository" || parts[2] != "shells" {
+            return Err(EventParseError::InvalidTopic(topic.to_string()));
+        }
+
+        let repo_id = parts[1].to_string();
+        let payload = parse_payload(payload)?;
+
+        let event_type_str = parts.last().unwrap_or(&"");
+        let event_type = EventType::from_topic_suffix(event_type_str)
+            .ok_or_else(|| EventParseError::UnknownEventType((*event_type_str).to_string()))?;
+
+        let aas_id = if parts.len() > 4 {
+            decode_id_base64url(parts[3])
+                .map_err(|e| EventParseError::DecodeError(e.to_string()))?
+        } else {
+            payload
+                .get("id")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| EventParseError::InvalidTopic(topic.to_string()))?
+        };
+
+        let submodel_ids = payload
+            .get("submodels")
+            .and_then(serde_json::Value::as_array)
+            .map(|references| references.iter().filter_map(submodel_reference).collect())
+            .unwrap_or_default();
+
+        Ok(Self {
+            repo_id,
+            aas_id,
+            event_type,
+            submodel_ids,
+            payload,
+        })
+    }
+}
+
+/// An event from either kind of `BaSyx` repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepositoryEvent {
+    /// Event from a submodel repository (`sm-repository/...`)
+    Submodel(BasyxEvent),
+    /// Event from an AAS repository (`aas-repository/...`)
+    Shell(ShellEvent),
+}
+
+impl RepositoryEvent {
+    /// Parse an event, dispatching on the topic's repository kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the topic format is invalid.
+    pub fn parse(topic: &str, payload: &[u8]) -> Result<Self, EventParseError> {
+        match topic.split('/').next() {
+            Some("sm-repository") => BasyxEvent::parse(topic, payload).map(Self::Submodel),
+            Some("aas-repository") => ShellEvent::parse(topic, payload).map(Self::Shell),
+            _ => Err(EventParseError::InvalidTopic(topic.to_string())),
+        }
+    }
+}
+
+/// Parse an event payload, treating an empty one as `null`.
+fn parse_payload(payload: &[u8]) -> Result<serde_json::Value, EventParseError> {
+    if payload.is_empty() {
+        Ok(serde_json::Value::Null)
+    } else {
+        serde_json::from_slice(payload).map_err(|e| EventParseError::PayloadParse(e.to_string()))
+    }
+}
+
+/// Submodel identifier of a model reference, if it points at a submodel.
+fn submodel_reference(reference: &serde_json::Value) -> Option<String> {
+    reference
+        .get("keys")?
+        .as_array()?
+        .iter()
+        .find(|key| key.get("type").and_then(serde_json::Value::as_str) == Some("Submodel"))?
+        .get("value")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Collect the leaf elements of a created or replaced submodel.
+///
+/// The payload is the submodel in normal serialization. Deleted submodels
+/// have no elements.
+fn submodel_elements(event_type: EventType, payload: &serde_json::Value) -> Vec<ElementEvent> {
+    if event_type == EventType::Deleted {
+        return Vec::new();
+    }
+
+    payload
+        .get("submodelElements")
+        .and_then(serde_json::Value::as_array)
+        .map(|items| flatten_elements(items))
+        .unwrap_or_default()
+}
+
+/// Collect the elements changed by a `patched` event.
+///
+/// The payload holds the patched submodel elements in normal
+/// serialization, either as an array or as a single element.
+fn patched_elements(payload: &serde_json::Value) -> Vec<ElementEvent> {
+    match payload {
+        serde_json::Value::Array(items) => flatten_elements(items),
+        serde_json::Value::Object(_) => flatten_elements(std::slice::from_ref(payload)),
+        _ => Vec::new(),
+    }
+}
+
+/// Flatten submodel elements in normal serialization into leaf events.
+///
+/// Collections and lists are descended into, so every leaf element is
+/// reported with its full idShortPath: children of a collection as
+/// `{parent}/{idShort}`, list entries as `{parent}[{index}]`.
+#[must_use]
+pub fn flatten_elements(items: &[serde_json::Value]) -> Vec<ElementEvent> {
+    let mut out = Vec::new();
+    flatten_into(items, None, false, &mut out);
+    out
+}
+
+fn flatten_into(
+    items: &[serde_json::Value],
+    parent: Option<&str>,
+    in_list: bool,
+    out: &mut Vec<ElementEvent>,
+) {
+    for (index, item) in items.iter().enumerate() {
+        let id_short = item.get("idShort").and_then(serde_json::Value::as_str);
+        let id_short_path = match (parent, id_short) {
+            (Some(parent), _) if in_list => format!("{parent}[{index}]"),
+            (Some(parent), Some(id_short)) => format!("{parent}/{id_short}"),
+            (None, Some(id_short)) => id_short.to_string(),
+            (_, None) => continue,
+        };
+
+        let element_type = element_type(item);
+        match (element_type, item.get("value")) {
+            (
+                Some(ElementType::SubmodelElementCollection),
+                Some(serde_json::Value::Array(children)),
+            ) => {
+                flatten_into(children, Some(&id_short_path), false, out);
+            }
+            (Some(ElementType::SubmodelElementList), Some(serde_json::Value::Array(children))) => {
+                flatten_into(children, Some(&id_short_path), true, out);
+            }
+            _ => out.push(ElementEvent {
+                id_short_path,
+                value: element_value(item),
+                element_type,
+            }),
+        }
+    }
+}
+
+/// Type of an element in normal serialization.
+fn element_type(element: &serde_json::Value) -> Option<ElementType> {
+    element
+        .get("modelType")
+        .and_then(serde_json::Value::as_str)
+        .and_then(ElementType::from_model_type)
+}
+
+/// Value of an element in normal serialization.
+///
+/// Ranges, files and blobs keep their value outside `value`, so these are
+/// given in `$value` form.
+fn element_value(element: &serde_json::Value) -> Option<serde_json::Value> {
+    let fields: &[&str] = match element_type(element) {
+        Some(ElementType::Range) => &["min", "max"],
+        Some(ElementType::File | ElementType::Blob) => &["contentType", "value"],
+        _ => return element.get("value").cloned(),
+    };
+
+    let value: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .filter_map(|field| Some(((*field).to_string(), element.get(*field)?.clone())))
+        .collect();
+    Some(serde_json::Value::Object(value))
+}
+
 /// Errors that can occur parsing `BaSyx` events.
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum EventParseError {
@@ -181,8 +421,9 @@ mod tests {
         assert_eq!(event.repo_id, "repo1");
         assert_eq!(event.submodel_id, submodel_id);
         assert_eq!(event.event_type, EventType::Updated);
+        assert_eq!(event.scope, EventScope::Element);
 
-        let element = event.element.unwrap();
+        let element = &event.elements[0];
         assert_eq!(element.id_short_path, "Temperature");
         assert_eq!(element.value, Some(serde_json::json!(25.5)));
     }
@@ -199,7 +440,7 @@ mod tests {
         let event = BasyxEvent::parse(&topic, b"").unwrap();
 
         assert_eq!(event.event_type, EventType::Deleted);
-        let element = event.element.unwrap();
+        let element = &event.elements[0];
         assert_eq!(element.id_short_path, "OldProperty");
         assert!(element.value.is_none());
     }
@@ -215,7 +456,130 @@ mod tests {
 
         let event = BasyxEvent::parse(&topic, b"{}").unwrap();
 
-        let element = event.element.unwrap();
+        let element = &event.elements[0];
         assert_eq!(element.id_short_path, "Collection/SubProperty");
     }
+
+    #[test]
+    fn parse_patched_event_reports_every_element() {
+        let submodel_id = "urn:example:sm:data";
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+
+        let topic =
+            format!("sm-repository/repo1/submodels/{encoded_sm_id}/submodelElements/patched");
+
+        let payload = br#"[
+            {"modelType": "Property", "idShort": "Temperature", "valueType": "xs:double", "value": "25.5"},
+            {"modelType": "SubmodelElementCollection", "idShort": "Motor", "value": [
+                {"modelType": "Property", "idShort": "Speed", "valueType": "xs:int", "value": "1200"},
+                {"modelType": "SubmodelElementList", "idShort": "Phases", "value": [
+                    {"modelType": "Property", "valueType": "xs:double", "value": "230.1"},
+                    {"modelType": "Property", "valueType": "xs:double", "value": "229.8"}
+                ]}
+            ]}
+        ]"#;
+
+        let event = BasyxEvent::parse(&topic, payload).unwrap();
+
+        assert_eq!(event.event_type, EventType::Patched);
+        let paths: Vec<_> = event
+            .elements
+            .iter()
+            .map(|element| element.id_short_path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "Temperature",
+                "Motor/Speed",
+                "Motor/Phases[0]",
+                "Motor/Phases[1]"
+            ]
+        );
+        assert_eq!(event.elements[3].value, Some(serde_json::json!("229.8")));
+        assert_eq!(event.elements[3].element_type, Some(ElementType::Property));
+    }
+
+    #[test]
+    fn parse_per_field_element_values() {
+        let submodel_id = "urn:example:sm:data";
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+
+        let topic = format!(
+            "sm-repository/repo1/submodels/{encoded_sm_id}/submodelElements/Limits/updated"
+        );
+        let payload =
+            br#"{"modelType": "Range", "idShort": "Limits", "valueType": "xs:int", "min": "0", "max": "100"}"#;
+
+        let event = BasyxEvent::parse(&topic, payload).unwrap();
+
+        let element = &event.elements[0];
+        assert_eq!(element.element_type, Some(ElementType::Range));
+        assert_eq!(
+            element.value,
+            Some(serde_json::json!({"min": "0", "max": "100"}))
+        );
+    }
+
+    #[test]
+    fn parse_submodel_level_events() {
+        let submodel_id = "urn:example:sm:data";
+        let encoded_sm_id = encode_id_base64url(submodel_id);
+
+        let payload = br#"{
+            "modelType": "Submodel",
+            "id": "urn:example:sm:data",
+            "submodelElements": [
+                {"modelType": "Property", "idShort": "Temperature", "value": "25.5"},
+                {"modelType": "SubmodelElementCollection", "idShort": "Motor", "value": [
+                    {"modelType": "Property", "idShort": "Speed", "value": "1200"}
+                ]}
+            ]
+        }"#;
+
+        let topic = format!("sm-repository/repo1/submodels/{encoded_sm_id}/updated");
+        let event = BasyxEvent::parse(&topic, payload).unwrap();
+
+        assert_eq!(event.scope, EventScope::Submodel);
+        assert_eq!(event.event_type, EventType::Updated);
+        assert_eq!(event.elements.len(), 2);
+        assert_eq!(event.elements[1].id_short_path, "Motor/Speed");
+
+        let topic = format!("sm-repository/repo1/submodels/{encoded_sm_id}/deleted");
+        let event = BasyxEvent::parse(&topic, payload).unwrap();
+
+        assert_eq!(event.scope, EventScope::Submodel);
+        assert!(event.elements.is_empty());
+    }
+
+    #[test]
+    fn parse_shell_events() {
+        let aas_id = "urn:example:aas:1";
+        let encoded_aas_id = encode_id_base64url(aas_id);
+
+        let payload = br#"{
+            "modelType": "AssetAdministrationShell",
+            "id": "urn:example:aas:1",
+            "submodels": [
+                {"type": "ModelReference", "keys": [{"type": "Submodel", "value": "urn:example:sm:data"}]}
+            ]
+        }"#;
+
+        let topic = format!("aas-repository/aas-repo/shells/{encoded_aas_id}/updated");
+        let RepositoryEvent::Shell(event) = RepositoryEvent::parse(&topic, payload).unwrap() else {
+            panic!("expected a shell event");
+        };
+
+        assert_eq!(event.repo_id, "aas-repo");
+        assert_eq!(event.aas_id, aas_id);
+        assert_eq!(event.event_type, EventType::Updated);
+        assert_eq!(event.submodel_ids, ["urn:example:sm:data"]);
+
+        // Without an ID in the topic it comes from the payload
+        let event = ShellEvent::parse("aas-repository/aas-repo/shells/created", payload).unwrap();
+        assert_eq!(event.aas_id, aas_id);
+        assert_eq!(event.event_type, EventType::Created);
+
+        assert!(RepositoryEvent::parse("cd-repository/repo/created", b"").is_err());
+    }
 }
@@ -1,6 +1,7 @@
 //! # `BaSyx` Adapter
 //!
 //! MQTT event ingestion from Eclipse `BaSyx` AAS and Submodel Repositories.
+//! Any number of repositories of either kind can be subscribed to.
 //!
 //! ## `BaSyx` MQTT Topics
 //!
@@ -8,6 +9,10 @@
 //! - `sm-repository/{repoId}/submodels/{submodelIdBase64}/submodelElements/{idShortPath}/updated`
 //! - `.../created`, `.../deleted`
 //! - `.../submodelElements/patched`
+//! - `sm-repository/{repoId}/submodels/{submodelIdBase64}/created` (and `updated`, `deleted`)
+//!   for whole submodels
+//! - `aas-repository/{repoId}/shells/{aasIdBase64}/created` (and `updated`, `deleted`)
+//!   for shells
 //!
 //! The adapter subscribes to these topics and converts events to CRDT deltas.
 
@@ -17,5 +22,7 @@
 pub mod events;
 pub mod subscriber;
 
-pub use events::{BasyxEvent, ElementEvent, EventType};
+pub use events::{
+    flatten_elements, BasyxEvent, ElementEvent, EventScope, EventType, RepositoryEvent, ShellEvent,
+};
 pub use subscriber::{BasyxSubscriber, BasyxSubscriberConfig};
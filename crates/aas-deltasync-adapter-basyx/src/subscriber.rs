@@ -1,6 +1,6 @@
 //! `BaSyx` MQTT subscriber for event ingestion.
 
-use crate::events::{BasyxEvent, EventParseError};
+use crate::events::{EventParseError, RepositoryEvent};
 use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS, Transport};
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -17,8 +17,10 @@ pub struct BasyxSubscriberConfig {
     pub mqtt_ca_path: Option<PathBuf>,
     /// Client ID for MQTT connection
     pub client_id: String,
-    /// Repository ID to subscribe to
-    pub repo_id: String,
+    /// Submodel repository IDs to subscribe to
+    pub repo_ids: Vec<String>,
+    /// AAS repository IDs to subscribe to for shell events
+    pub aas_repo_ids: Vec<String>,
     /// Keep-alive interval
     pub keep_alive: Duration,
 }
@@ -29,7 +31,8 @@ impl Default for BasyxSubscriberConfig {
             mqtt_broker: "tcp://localhost:1883".to_string(),
             mqtt_ca_path: None,
             client_id: "aas-deltasync-basyx".to_string(),
-            repo_id: "sm-repo".to_string(),
+            repo_ids: vec!["sm-repo".to_string()],
+            aas_repo_ids: Vec::new(),
             keep_alive: Duration::from_secs(30),
         }
     }
@@ -69,21 +72,33 @@ impl BasyxSubscriber {
         })
     }
 
-    /// Subscribe to submodel repository events.
+    /// Subscribe to events of every configured repository.
     ///
     /// # Errors
     ///
     /// Returns error if subscription fails.
     pub async fn subscribe(&self) -> Result<(), SubscriberError> {
-        // Subscribe to all submodel element events for the repository
-        let topic = format!("sm-repository/{}/#", self.config.repo_id);
-
-        tracing::info!(topic, "Subscribing to BaSyx events");
-
-        self.client
-            .subscribe(&topic, QoS::AtLeastOnce)
-            .await
-            .map_err(|e| SubscriberError::Subscribe(e.to_string()))?;
+        // Subscribe to all events of each repository
+        let topics = self
+            .config
+            .repo_ids
+            .iter()
+            .map(|repo_id| format!("sm-repository/{repo_id}/#"))
+            .chain(
+                self.config
+                    .aas_repo_ids
+                    .iter()
+                    .map(|repo_id| format!("aas-repository/{repo_id}/#")),
+            );
+
+        for topic in topics {
+            tracing::info!(topic, "Subscribing to BaSyx events");
+
+            self.client
+                .subscribe(&topic, QoS::AtLeastOnce)
+                .await
+                .map_err(|e| SubscriberError::Subscribe(e.to_string()))?;
+        }
 
         Ok(())
     }
@@ -91,7 +106,7 @@ impl BasyxSubscriber {
     /// Start receiving events.
     ///
     /// Returns a channel receiver for parsed events.
-    pub fn start(mut self) -> mpsc::Receiver<Result<BasyxEvent, EventParseError>> {
+    pub fn start(mut self) -> mpsc::Receiver<Result<RepositoryEvent, EventParseError>> {
         let (tx, rx) = mpsc::channel(100);
 
         tokio::spawn(async move {
@@ -104,26 +119,32 @@ impl BasyxSubscriber {
 
                         tracing::debug!(topic, payload_len, "Received MQTT message");
 
-                        let event = BasyxEvent::parse(&topic, &payload);
+                        let event = RepositoryEvent::parse(&topic, &payload);
                         match &event {
-                            Ok(parsed) => {
-                                let (id_short_path, has_value) =
-                                    parsed.element.as_ref().map_or((None, false), |element| {
-                                        (
-                                            Some(element.id_short_path.as_str()),
-                                            element.value.is_some(),
-                                        )
-                                    });
+                            Ok(RepositoryEvent::Submodel(parsed)) => {
+                                let id_short_paths: Vec<&str> = parsed
+                                    .elements
+                                    .iter()
+                                    .map(|element| element.id_short_path.as_str())
+                                    .collect();
 
                                 tracing::debug!(
                                     repo_id = %parsed.repo_id,
                                     submodel_id = %parsed.submodel_id,
                                     event_type = ?parsed.event_type,
-                                    id_short_path = ?id_short_path,
-                                    has_value,
+                                    scope = ?parsed.scope,
+                                    id_short_paths = ?id_short_paths,
                                     "Parsed BaSyx event"
                                 );
                             }
+                            Ok(RepositoryEvent::Shell(parsed)) => {
+                                tracing::debug!(
+                                    repo_id = %parsed.repo_id,
+                                    aas_id = %parsed.aas_id,
+                                    event_type = ?parsed.event_type,
+                                    "Parsed BaSyx shell event"
+                                );
+                            }
                             Err(err) => {
                                 tracing::warn!(
                                     error = %err,
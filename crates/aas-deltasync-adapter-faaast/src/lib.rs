@@ -21,4 +21,4 @@
 
 pub mod poller;
 
-pub use poller::{FaaastPoller, FaaastPollerConfig};
+pub use poller::{compute_diff, diff_leaves, FaaastPoller, FaaastPollerConfig};
@@ -1,9 +1,9 @@
 //! FA³ST polling-based change detection.
 
-use aas_deltasync_adapter_aas::{AasClient, AasClientConfig};
-use aas_deltasync_core::{Delta, Hlc};
+use aas_deltasync_adapter_aas::{element_types, flatten_value, AasClient, AasClientConfig};
+use aas_deltasync_core::{Delta, ElementTypes, Hlc};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -44,6 +44,8 @@ pub struct FaaastPoller {
     config: FaaastPollerConfig,
     /// Last known state per submodel
     snapshots: HashMap<String, Value>,
+    /// Element types per submodel, read before its first poll
+    types: HashMap<String, ElementTypes>,
 }
 
 impl FaaastPoller {
@@ -74,6 +76,7 @@ impl FaaastPoller {
             client,
             config,
             snapshots: HashMap::new(),
+            types: HashMap::new(),
         })
     }
 
@@ -120,6 +123,17 @@ impl FaaastPoller {
         submodel_id: &str,
         clock: &mut Hlc,
     ) -> Result<Option<Delta<String, Value>>, PollerError> {
+        if !self.types.contains_key(submodel_id) {
+            let submodel = self
+                .client
+                .get_submodel(submodel_id)
+                .await
+                .map_err(|e| PollerError::Fetch(e.to_string()))?;
+            self.types
+                .insert(submodel_id.to_string(), element_types(&submodel));
+        }
+        let types = &self.types[submodel_id];
+
         let current = self
             .client
             .get_submodel_value(submodel_id)
@@ -130,7 +144,7 @@ impl FaaastPoller {
 
         let delta = if let Some(prev) = previous {
             // Compute diff
-            let delta = compute_diff(prev, &current, clock);
+            let delta = compute_diff(prev, &current, types, clock);
             if delta.is_empty() {
                 None
             } else {
@@ -138,7 +152,7 @@ impl FaaastPoller {
             }
         } else {
             // First poll - generate delta for entire state
-            let delta = value_to_delta(&current, "", clock);
+            let delta = value_to_delta(&current, types, clock);
             if delta.is_empty() {
                 None
             } else {
@@ -153,113 +167,56 @@ impl FaaastPoller {
     }
 }
 
-/// Compute a diff between two JSON values.
-fn compute_diff(old: &Value, new: &Value, clock: &mut Hlc) -> Delta<String, Value> {
-    let mut delta = Delta::new();
-    diff_values(old, new, "", &mut delta, clock);
-    delta
-}
-
-/// Recursively diff two JSON values.
-fn diff_values(
+/// Compute a diff between two `$value` payloads.
+///
+/// Both sides are flattened to replicated idShortPath entries first, split
+/// according to the element `types`; see [`diff_leaves`].
+#[must_use]
+pub fn compute_diff(
     old: &Value,
     new: &Value,
-    path: &str,
-    delta: &mut Delta<String, Value>,
+    types: &ElementTypes,
     clock: &mut Hlc,
-) {
-    match (old, new) {
-        (Value::Object(old_obj), Value::Object(new_obj)) => {
-            // Check for removed keys
-            for key in old_obj.keys() {
-                if !new_obj.contains_key(key) {
-                    let child_path = if path.is_empty() {
-                        key.clone()
-                    } else {
-                        format!("{path}.{key}")
-                    };
-                    delta.add_remove(child_path, clock.tick());
-                }
-            }
+) -> Delta<String, Value> {
+    diff_leaves(
+        &flatten_value(old, types),
+        &flatten_value(new, types),
+        clock,
+    )
+}
 
-            // Check for added/modified keys
-            for (key, new_val) in new_obj {
-                let child_path = if path.is_empty() {
-                    key.clone()
-                } else {
-                    format!("{path}.{key}")
-                };
-
-                if let Some(old_val) = old_obj.get(key) {
-                    // Recurse
-                    diff_values(old_val, new_val, &child_path, delta, clock);
-                } else {
-                    // New key
-                    delta.add_insert(child_path, new_val.clone(), clock.tick());
-                }
-            }
-        }
-        (Value::Array(old_arr), Value::Array(new_arr)) => {
-            // For arrays, we do element-wise comparison by index
-            // Note: This is simplified; production would use stable IDs
-            let max_len = old_arr.len().max(new_arr.len());
-            for i in 0..max_len {
-                let child_path = format!("{path}[{i}]");
-                match (old_arr.get(i), new_arr.get(i)) {
-                    (Some(old_val), Some(new_val)) => {
-                        diff_values(old_val, new_val, &child_path, delta, clock);
-                    }
-                    (Some(_), None) => {
-                        delta.add_remove(child_path, clock.tick());
-                    }
-                    (None, Some(new_val)) => {
-                        delta.add_insert(child_path, new_val.clone(), clock.tick());
-                    }
-                    (None, None) => {}
-                }
-            }
+/// Compute the delta turning one set of idShortPath leaves into another.
+///
+/// Leaves missing from `new` become removes, and leaves that are new or
+/// changed become inserts, each stamped with a fresh tick of `clock`.
+/// List entries are compared by index.
+#[must_use]
+pub fn diff_leaves(
+    old: &BTreeMap<String, Value>,
+    new: &BTreeMap<String, Value>,
+    clock: &mut Hlc,
+) -> Delta<String, Value> {
+    let mut delta = Delta::new();
+    for path in old.keys() {
+        if !new.contains_key(path) {
+            delta.add_remove(path.clone(), clock.tick());
         }
-        _ => {
-            // Scalar comparison
-            if old != new {
-                delta.add_insert(path.to_string(), new.clone(), clock.tick());
-            }
+    }
+    for (path, value) in new {
+        if old.get(path) != Some(value) {
+            delta.add_insert(path.clone(), value.clone(), clock.tick());
         }
     }
+    delta
 }
 
 /// Convert a JSON value to a delta (for initial snapshot).
-fn value_to_delta(value: &Value, path: &str, clock: &mut Hlc) -> Delta<String, Value> {
+fn value_to_delta(value: &Value, types: &ElementTypes, clock: &mut Hlc) -> Delta<String, Value> {
     let mut delta = Delta::new();
-    flatten_value(value, path, &mut delta, clock);
-    delta
-}
-
-/// Flatten a JSON value into delta inserts.
-fn flatten_value(value: &Value, path: &str, delta: &mut Delta<String, Value>, clock: &mut Hlc) {
-    match value {
-        Value::Object(obj) => {
-            for (key, val) in obj {
-                let child_path = if path.is_empty() {
-                    key.clone()
-                } else {
-                    format!("{path}.{key}")
-                };
-                flatten_value(val, &child_path, delta, clock);
-            }
-        }
-        Value::Array(arr) => {
-            for (i, val) in arr.iter().enumerate() {
-                let child_path = format!("{path}[{i}]");
-                flatten_value(val, &child_path, delta, clock);
-            }
-        }
-        _ => {
-            if !path.is_empty() {
-                delta.add_insert(path.to_string(), value.clone(), clock.tick());
-            }
-        }
+    for (path, leaf) in flatten_value(value, types) {
+        delta.add_insert(path, leaf, clock.tick());
     }
+    delta
 }
 
 /// Errors that can occur with the FA³ST poller.
@@ -299,7 +256,7 @@ mod tests {
         let old = serde_json::json!({"temperature": 25});
         let new = serde_json::json!({"temperature": 30});
 
-        let delta = compute_diff(&old, &new, &mut clock);
+        let delta = compute_diff(&old, &new, &ElementTypes::new(), &mut clock);
 
         assert_eq!(delta.inserts.len(), 1);
         assert_eq!(delta.inserts[0].0, "temperature");
@@ -313,7 +270,7 @@ mod tests {
         let old = serde_json::json!({"a": 1, "b": 2});
         let new = serde_json::json!({"a": 1});
 
-        let delta = compute_diff(&old, &new, &mut clock);
+        let delta = compute_diff(&old, &new, &ElementTypes::new(), &mut clock);
 
         assert_eq!(delta.removes.len(), 1);
         assert_eq!(delta.removes[0].0, "b");
@@ -326,9 +283,24 @@ mod tests {
         let old = serde_json::json!({"a": 1});
         let new = serde_json::json!({"a": 1, "b": 2});
 
-        let delta = compute_diff(&old, &new, &mut clock);
+        let delta = compute_diff(&old, &new, &ElementTypes::new(), &mut clock);
 
         assert_eq!(delta.inserts.len(), 1);
         assert_eq!(delta.inserts[0].0, "b");
     }
+
+    #[test]
+    fn diff_composite_values_by_leaf() {
+        let mut clock = Hlc::new(Uuid::new_v4());
+
+        let old = serde_json::json!({"Motor": {"Speed": 1200}, "Phases": [230, 231]});
+        let new = serde_json::json!({"Motor": {"Speed": 1200, "Torque": 5}, "Phases": [230]});
+
+        let delta = compute_diff(&old, &new, &ElementTypes::new(), &mut clock);
+
+        assert_eq!(delta.inserts.len(), 1);
+        assert_eq!(delta.inserts[0].0, "Motor.Torque");
+        assert_eq!(delta.removes.len(), 1);
+        assert_eq!(delta.removes[0].0, "Phases[1]");
+    }
 }
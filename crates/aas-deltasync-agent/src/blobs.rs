@@ -0,0 +1,214 @@
+//! Content-addressed storage for attachment content.
+//!
+//! `File` and `Blob` elements replicate only a [`BlobRef`]; the content is
+//! kept here, one file per SHA-256 hash, and travels between agents as
+//! [`BlobChunk`]s when a peer asks for it.
+//!
+//! [`BlobRef`]: aas_deltasync_core::BlobRef
+
+use aas_deltasync_core::content_hash;
+use aas_deltasync_proto::BlobChunk;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Largest number of chunks accepted for one blob (512 MiB of content).
+const MAX_BLOB_CHUNKS: u32 = 64 * 1024;
+
+/// On-disk content store keyed by SHA-256 hash.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    /// Create a store in `dir`.
+    ///
+    /// Nothing touches the disk until content is first stored, which
+    /// creates the directory, so agents replicating no attachments never
+    /// need one.
+    #[must_use]
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Store content, returning its hash.
+    ///
+    /// Content is written to a temporary file and renamed into place, so a
+    /// crash never leaves a partial blob under its hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the directory cannot be created or the content
+    /// cannot be written.
+    pub fn put(&self, content: &[u8]) -> io::Result<String> {
+        let hash = content_hash(content);
+        let path = self.dir.join(&hash);
+        if !path.exists() {
+            fs::create_dir_all(&self.dir)?;
+            let partial = self.dir.join(format!("{hash}.partial"));
+            fs::write(&partial, content)?;
+            fs::rename(&partial, &path)?;
+        }
+        Ok(hash)
+    }
+
+    /// Read the content stored under `hash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the content exists but cannot be read.
+    pub fn get(&self, hash: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(path) = self.path(hash) else {
+            return Ok(None);
+        };
+        match fs::read(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check if content is stored under `hash`.
+    #[must_use]
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path(hash).is_some_and(|path| path.exists())
+    }
+
+    /// File holding `hash`, if it is a well-formed SHA-256 hash.
+    fn path(&self, hash: &str) -> Option<PathBuf> {
+        let well_formed = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+        well_formed.then(|| self.dir.join(hash))
+    }
+}
+
+/// A blob being fetched from peers.
+#[derive(Debug)]
+struct Fetch {
+    requested: Instant,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+/// Blobs requested from peers and the chunks received so far.
+///
+/// Several peers may answer the same request; chunks of blobs not being
+/// fetched, or already complete, are ignored.
+#[derive(Debug)]
+pub struct BlobFetches {
+    pending: HashMap<String, Fetch>,
+    retry: Duration,
+}
+
+impl BlobFetches {
+    /// Create an empty tracker that repeats unanswered requests after
+    /// `retry`.
+    #[must_use]
+    pub fn new(retry: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            retry,
+        }
+    }
+
+    /// Record a request for `hash`, returning whether it should be sent.
+    ///
+    /// A request is due if none was sent within the retry window.
+    pub fn request(&mut self, hash: &str, now: Instant) -> bool {
+        match self.pending.get_mut(hash) {
+            Some(fetch) if now.duration_since(fetch.requested) < self.retry => false,
+            Some(fetch) => {
+                fetch.requested = now;
+                true
+            }
+            None => {
+                let fetch = Fetch {
+                    requested: now,
+                    chunks: Vec::new(),
+                };
+                self.pending.insert(hash.to_string(), fetch);
+                true
+            }
+        }
+    }
+
+    /// Add a received chunk.
+    ///
+    /// Returns the content once every chunk has arrived. Content that does
+    /// not match its hash is discarded and must be requested again.
+    pub fn receive(&mut self, chunk: &BlobChunk) -> Option<Vec<u8>> {
+        let fetch = self.pending.get_mut(&chunk.content_hash)?;
+        if chunk.total == 0 || chunk.total > MAX_BLOB_CHUNKS || chunk.index >= chunk.total {
+            return None;
+        }
+
+        let total = chunk.total as usize;
+        if fetch.chunks.len() != total {
+            // First chunk, or a responder that split differently
+            fetch.chunks = vec![None; total];
+        }
+        fetch.chunks[chunk.index as usize] = Some(chunk.data.clone());
+        if fetch.chunks.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let fetch = self.pending.remove(&chunk.content_hash)?;
+        let content: Vec<u8> = fetch.chunks.into_iter().flatten().flatten().collect();
+        (content_hash(&content) == chunk.content_hash).then_some(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_keys_content_by_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(&dir.path().join("blobs"));
+
+        // The directory is created with the first content
+        assert_eq!(store.get(&content_hash(b"%PDF-1.7")).unwrap(), None);
+        assert!(!dir.path().join("blobs").exists());
+
+        let hash = store.put(b"%PDF-1.7").unwrap();
+        assert_eq!(hash, content_hash(b"%PDF-1.7"));
+        assert!(store.contains(&hash));
+        assert_eq!(store.get(&hash).unwrap().as_deref(), Some(&b"%PDF-1.7"[..]));
+        assert_eq!(store.put(b"%PDF-1.7").unwrap(), hash);
+
+        assert!(!store.contains(&content_hash(b"other")));
+        assert_eq!(store.get("../deltasync.db").unwrap(), None);
+    }
+
+    #[test]
+    fn fetches_assemble_chunks_from_peers() {
+        let content = vec![42u8; aas_deltasync_proto::BLOB_CHUNK_SIZE + 10];
+        let hash = content_hash(&content);
+        let chunks = BlobChunk::split(&hash, &content);
+        let mut fetches = BlobFetches::new(Duration::from_secs(30));
+        let now = Instant::now();
+
+        // Unrequested content is ignored
+        assert_eq!(fetches.receive(&chunks[0]), None);
+
+        assert!(fetches.request(&hash, now));
+        assert!(!fetches.request(&hash, now + Duration::from_secs(1)));
+        assert_eq!(fetches.receive(&chunks[1]), None);
+        assert_eq!(fetches.receive(&chunks[1]), None);
+        assert_eq!(fetches.receive(&chunks[0]), Some(content));
+
+        // A second responder's chunks arrive after completion
+        assert_eq!(fetches.receive(&chunks[0]), None);
+
+        // Corrupt content is dropped and requested again
+        let mut corrupt = BlobChunk::split(&hash, b"corrupt");
+        corrupt[0].total = 1;
+        assert!(fetches.request(&hash, now));
+        assert_eq!(fetches.receive(&corrupt[0]), None);
+        assert!(fetches.request(&hash, now));
+    }
+}
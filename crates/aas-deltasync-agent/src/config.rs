@@ -1,5 +1,6 @@
 //! Agent configuration.
 
+use aas_deltasync_core::{DocId, PathPattern, RemoveSemantics, View};
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::path::PathBuf;
@@ -39,6 +40,10 @@ pub struct AdapterConfig {
 
     /// MQTT broker URL (for `BaSyx`)
     pub mqtt_broker: Option<String>,
+    /// Submodel repository IDs to ingest events from (for `BaSyx`)
+    pub repo_ids: Vec<String>,
+    /// AAS repository IDs to ingest shell events from (for `BaSyx`)
+    pub aas_repo_ids: Vec<String>,
     /// CA certificate path for MQTT TLS (PEM)
     pub mqtt_ca_path: Option<PathBuf>,
 
@@ -69,6 +74,32 @@ pub struct ReplicationConfig {
 
     /// Enable egress (push back to AAS server)
     pub enable_egress: bool,
+
+    /// Interval between anti-entropy requests per document
+    pub anti_entropy_interval: Duration,
+
+    /// Interval between agent hello announcements per document
+    pub hello_interval: Duration,
+
+    /// Time after the last hello before a peer is considered gone
+    pub peer_ttl: Duration,
+
+    /// How long an egressed write is remembered to drop its adapter echo
+    pub echo_window: Duration,
+    /// Interval between reconciliations of documents against the server
+    pub reconcile_interval: Duration,
+    /// How removals treat concurrent inserts; must match on every agent
+    pub remove_semantics: RemoveSemantics,
+    /// Paths keeping concurrent values as conflicts instead of picking one
+    pub multi_value_paths: Vec<PathPattern>,
+    /// Paths whose concurrent changes add up as counters
+    pub counter_paths: Vec<PathPattern>,
+    /// `semanticId`s of elements whose concurrent changes add up as counters
+    pub counter_semantic_ids: Vec<String>,
+    /// How far ahead of the local clock received timestamps may be, if
+    /// bounded (unbounded by default, as agents that bound it reject
+    /// writes that older agents accept)
+    pub max_clock_offset: Option<Duration>,
 }
 
 /// Persistence configuration.
@@ -80,8 +111,14 @@ pub struct PersistenceConfig {
     /// Database path (for `SQLite`)
     pub db_path: PathBuf,
 
+    /// Directory of the content-addressed blob store
+    pub blob_dir: PathBuf,
+
     /// Compaction interval
     pub compaction_interval: Duration,
+
+    /// How long conflict log entries are kept
+    pub conflict_log_retention: Duration,
 }
 
 /// Subscription configuration.
@@ -92,6 +129,22 @@ pub struct SubscriptionConfig {
 
     /// Submodel identifier
     pub submodel_id: String,
+
+    /// Submodel repository the submodel lives in (any repository if unset)
+    #[serde(default)]
+    pub repo_id: Option<String>,
+
+    /// Serialization view to replicate (defaults to `$value`)
+    #[serde(default)]
+    pub view: View,
+}
+
+impl SubscriptionConfig {
+    /// Document replicated for this subscription.
+    #[must_use]
+    pub fn doc_id(&self) -> DocId {
+        DocId::new(self.aas_id.clone(), self.submodel_id.clone(), self.view)
+    }
 }
 
 impl Default for AgentConfig {
@@ -103,6 +156,8 @@ impl Default for AgentConfig {
                 aas_repo_url: Some("http://localhost:8081".to_string()),
                 sm_repo_url: "http://localhost:8082".to_string(),
                 mqtt_broker: Some("tcp://localhost:1883".to_string()),
+                repo_ids: vec!["sm-repo".to_string()],
+                aas_repo_ids: Vec::new(),
                 mqtt_ca_path: None,
                 bearer_token: None,
                 poll_interval: Duration::from_secs(5),
@@ -115,11 +170,23 @@ impl Default for AgentConfig {
                 mqtt_ca_path: None,
                 tenant: "default".to_string(),
                 enable_egress: false,
+                anti_entropy_interval: Duration::from_secs(60),
+                hello_interval: Duration::from_secs(30),
+                peer_ttl: Duration::from_secs(120),
+                echo_window: Duration::from_secs(30),
+                reconcile_interval: Duration::from_secs(300),
+                remove_semantics: RemoveSemantics::LastWriterWins,
+                multi_value_paths: Vec::new(),
+                counter_paths: Vec::new(),
+                counter_semantic_ids: Vec::new(),
+                max_clock_offset: None,
             },
             persistence: PersistenceConfig {
                 store_type: "sqlite".to_string(),
                 db_path: PathBuf::from("./deltasync.db"),
+                blob_dir: PathBuf::from("./blobs"),
                 compaction_interval: Duration::from_secs(3600),
+                conflict_log_retention: Duration::from_secs(30 * 24 * 3600),
             },
             subscriptions: Vec::new(),
         }
@@ -136,8 +203,28 @@ impl AgentConfig {
     /// - `DELTASYNC_SM_REPO_URL`: Submodel repository URL
     /// - `DELTASYNC_MQTT_BROKER`: MQTT broker URL
     /// - `DELTASYNC_MQTT_CA_PATH`: MQTT CA certificate path (PEM)
+    /// - `DELTASYNC_BASYX_REPO_IDS`: Comma-separated `BaSyx` submodel repository IDs
+    /// - `DELTASYNC_BASYX_AAS_REPO_IDS`: Comma-separated `BaSyx` AAS repository IDs
     /// - `DELTASYNC_TENANT`: Tenant identifier
+    /// - `DELTASYNC_AE_INTERVAL_SECS`: Anti-entropy request interval in seconds
+    /// - `DELTASYNC_HELLO_INTERVAL_SECS`: Agent hello interval in seconds
+    /// - `DELTASYNC_PEER_TTL_SECS`: Seconds without a hello before a peer expires
+    /// - `DELTASYNC_ECHO_WINDOW_SECS`: Seconds an egressed write suppresses its adapter echo
+    /// - `DELTASYNC_RECONCILE_INTERVAL_SECS`: Server drift reconciliation interval in seconds
+    /// - `DELTASYNC_MAX_CLOCK_OFFSET_SECS`: Seconds a received timestamp may be ahead of the
+    ///   local clock before its delta is rejected (unset or 0 accepts any)
+    /// - `DELTASYNC_REMOVE_SEMANTICS`: "lww" (default) or "observed-remove"
+    /// - `DELTASYNC_MULTI_VALUE_PATHS`: Comma-separated path globs whose concurrent writes are
+    ///   held as conflicts
+    /// - `DELTASYNC_COUNTER_PATHS`: Comma-separated path globs of counters whose concurrent
+    ///   changes add up
+    /// - `DELTASYNC_COUNTER_SEMANTIC_IDS`: Comma-separated `semanticId`s of counter elements
     /// - `DELTASYNC_DB_PATH`: `SQLite` database path
+    /// - `DELTASYNC_BLOB_DIR`: Directory storing `File` and `Blob` content by hash, created
+    ///   when content is first stored
+    /// - `DELTASYNC_COMPACTION_INTERVAL_SECS`: Snapshot and compaction interval in seconds
+    /// - `DELTASYNC_CONFLICT_LOG_RETENTION_SECS`: Seconds conflict log entries are kept
+    ///   (default 30 days)
     /// - `DELTASYNC_AAS_CA_PATH`: AAS HTTPS CA certificate path (PEM)
     /// - `DELTASYNC_AAS_CLIENT_CERT`: AAS HTTPS client certificate path (PEM, for mTLS)
     /// - `DELTASYNC_AAS_CLIENT_KEY`: AAS HTTPS client key path (PEM, for mTLS)
@@ -175,14 +262,78 @@ impl AgentConfig {
             config.replication.mqtt_ca_path = Some(ca_path);
         }
 
+        if let Ok(ids) = std::env::var("DELTASYNC_BASYX_REPO_IDS") {
+            config.adapter.repo_ids = split_list(&ids);
+        }
+
+        if let Ok(ids) = std::env::var("DELTASYNC_BASYX_AAS_REPO_IDS") {
+            config.adapter.aas_repo_ids = split_list(&ids);
+        }
+
         if let Ok(tenant) = std::env::var("DELTASYNC_TENANT") {
             config.replication.tenant = tenant;
         }
 
+        if let Ok(secs) = std::env::var("DELTASYNC_AE_INTERVAL_SECS") {
+            let secs: u64 = secs.parse().context("Invalid DELTASYNC_AE_INTERVAL_SECS")?;
+            config.replication.anti_entropy_interval = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_HELLO_INTERVAL_SECS") {
+            let secs: u64 = secs
+                .parse()
+                .context("Invalid DELTASYNC_HELLO_INTERVAL_SECS")?;
+            config.replication.hello_interval = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_PEER_TTL_SECS") {
+            let secs: u64 = secs.parse().context("Invalid DELTASYNC_PEER_TTL_SECS")?;
+            config.replication.peer_ttl = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_ECHO_WINDOW_SECS") {
+            let secs: u64 = secs.parse().context("Invalid DELTASYNC_ECHO_WINDOW_SECS")?;
+            config.replication.echo_window = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_RECONCILE_INTERVAL_SECS") {
+            let secs: u64 = secs
+                .parse()
+                .context("Invalid DELTASYNC_RECONCILE_INTERVAL_SECS")?;
+            config.replication.reconcile_interval = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_MAX_CLOCK_OFFSET_SECS") {
+            let secs: u64 = secs
+                .parse()
+                .context("Invalid DELTASYNC_MAX_CLOCK_OFFSET_SECS")?;
+            config.replication.max_clock_offset = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+
+        merge_semantics_from_env(&mut config.replication)?;
+
         if let Ok(db_path) = std::env::var("DELTASYNC_DB_PATH") {
             config.persistence.db_path = PathBuf::from(db_path);
         }
 
+        if let Ok(blob_dir) = std::env::var("DELTASYNC_BLOB_DIR") {
+            config.persistence.blob_dir = PathBuf::from(blob_dir);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_COMPACTION_INTERVAL_SECS") {
+            let secs: u64 = secs
+                .parse()
+                .context("Invalid DELTASYNC_COMPACTION_INTERVAL_SECS")?;
+            config.persistence.compaction_interval = Duration::from_secs(secs);
+        }
+
+        if let Ok(secs) = std::env::var("DELTASYNC_CONFLICT_LOG_RETENTION_SECS") {
+            let secs: u64 = secs
+                .parse()
+                .context("Invalid DELTASYNC_CONFLICT_LOG_RETENTION_SECS")?;
+            config.persistence.conflict_log_retention = Duration::from_secs(secs);
+        }
+
         if let Ok(token) = std::env::var("DELTASYNC_BEARER_TOKEN") {
             config.adapter.bearer_token = Some(token);
         }
@@ -209,3 +360,44 @@ impl AgentConfig {
         Ok(config)
     }
 }
+
+/// Load how concurrent writes merge, see [`AgentConfig::from_env`].
+fn merge_semantics_from_env(replication: &mut ReplicationConfig) -> Result<()> {
+    if let Ok(semantics) = std::env::var("DELTASYNC_REMOVE_SEMANTICS") {
+        replication.remove_semantics = match semantics.as_str() {
+            "lww" => RemoveSemantics::LastWriterWins,
+            "observed-remove" => RemoveSemantics::ObservedRemove,
+            other => anyhow::bail!("Invalid DELTASYNC_REMOVE_SEMANTICS: {other}"),
+        };
+    }
+
+    if let Ok(patterns) = std::env::var("DELTASYNC_MULTI_VALUE_PATHS") {
+        replication.multi_value_paths = split_list(&patterns)
+            .into_iter()
+            .map(PathPattern::new)
+            .collect();
+    }
+
+    if let Ok(patterns) = std::env::var("DELTASYNC_COUNTER_PATHS") {
+        replication.counter_paths = split_list(&patterns)
+            .into_iter()
+            .map(PathPattern::new)
+            .collect();
+    }
+
+    if let Ok(ids) = std::env::var("DELTASYNC_COUNTER_SEMANTIC_IDS") {
+        replication.counter_semantic_ids = split_list(&ids);
+    }
+
+    Ok(())
+}
+
+/// Split a comma-separated list, dropping empty entries.
+fn split_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
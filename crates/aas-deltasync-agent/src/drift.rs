@@ -0,0 +1,191 @@
+//! Drift detection between documents and the AAS server.
+//!
+//! Missed adapter events, broker restarts or edits made while the agent was
+//! down leave the server and a document disagreeing with nothing left to
+//! report it. Reconciliation periodically reads the server back and sorts
+//! each difference into a local edit, replicated as a delta, or a stale
+//! server value, repaired through egress.
+
+use aas_deltasync_adapter_faaast::diff_leaves;
+use aas_deltasync_core::{same_value, CrdtDocument, Delta, DocId, ListIds};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Differences found between a document and its server state.
+#[derive(Debug)]
+pub struct Drift {
+    /// Server changes the document has not seen, to be replicated
+    pub local_edits: Delta<String, Value>,
+    /// Document state the server lacks, to be written back
+    pub repairs: Delta<String, Value>,
+}
+
+impl Drift {
+    /// Check if the document and the server agree.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.local_edits.is_empty() && self.repairs.is_empty()
+    }
+
+    /// Paths that differ, for reporting.
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        let inserts = self.local_edits.inserts.iter().map(|(path, _, _)| path);
+        let removes = self.local_edits.removes.iter().map(|(path, _)| path);
+        let repairs = self.repairs.inserts.iter().map(|(path, _, _)| path);
+        let deletes = self.repairs.removes.iter().map(|(path, _)| path);
+        inserts.chain(removes).chain(repairs).chain(deletes)
+    }
+}
+
+/// Server state observed at the previous reconciliation of each document.
+///
+/// A server value that changed since the last look was edited on the
+/// server; one that did not is stale and the document wins.
+#[derive(Debug, Default)]
+pub struct DriftTracker {
+    baselines: HashMap<DocId, BTreeMap<String, Value>>,
+}
+
+impl DriftTracker {
+    /// Create a tracker with no observed server state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare a document with the server's flattened `$value`.
+    ///
+    /// Both sides are compared in index form, see
+    /// [`CrdtDocument::index_view`], and so are the resulting deltas. Before a document's first reconciliation there is no baseline, so
+    /// only paths the document has never seen count as server edits.
+    pub fn reconcile(&mut self, doc: &mut CrdtDocument, server: BTreeMap<String, Value>) -> Drift {
+        let baseline = self.baselines.get(&doc.id);
+
+        // BaSyx events store normal-serialization strings, `$value` typed
+        // scalars; equal text is not drift
+        let view = doc.index_view();
+        let materialized: BTreeMap<String, Value> = view
+            .iter()
+            .map(|(path, value)| match server.get(path) {
+                Some(observed) if same_value(value, observed) => (path.clone(), observed.clone()),
+                _ => (path.clone(), value.clone()),
+            })
+            .collect();
+
+        let ids = ListIds::from_state(&doc.state);
+        let diff = diff_leaves(&materialized, &server, &mut doc.clock);
+        let mut drift = Drift {
+            local_edits: Delta::new(),
+            repairs: Delta::new(),
+        };
+
+        for (path, value, timestamp) in diff.inserts {
+            let edited = match baseline {
+                Some(baseline) => baseline
+                    .get(&path)
+                    .map_or(true, |previous| !same_value(previous, &value)),
+                None => !ids
+                    .stable_path(&path)
+                    .is_some_and(|stable| doc.state.has_seen(&stable)),
+            };
+
+            if edited {
+                drift.local_edits.add_insert(path, value, timestamp);
+            } else if let Some(current) = view.get(&path) {
+                drift.repairs.add_insert(path, current.clone(), timestamp);
+            } else {
+                drift.repairs.add_remove(path, timestamp);
+            }
+        }
+
+        for (path, timestamp) in diff.removes {
+            let deleted = baseline.is_some_and(|baseline| baseline.contains_key(&path));
+
+            if deleted {
+                drift.local_edits.add_remove(path, timestamp);
+            } else if let Some(current) = view.get(&path) {
+                drift.repairs.add_insert(path, current.clone(), timestamp);
+            }
+        }
+
+        self.baselines.insert(doc.id.clone(), server);
+        drift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aas_deltasync_core::Hlc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn leaves(entries: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        entries
+            .iter()
+            .map(|(path, value)| ((*path).to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn first_reconciliation_repairs_the_server() {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        let _ = doc.set("Temperature", json!("25.5"));
+        let _ = doc.set("Pressure", json!(1.2));
+        let _ = doc.set("Status", json!("Running"));
+        let _ = doc.set("Removed", json!(1));
+        let _ = doc.remove("Removed");
+
+        let mut tracker = DriftTracker::new();
+        let drift = tracker.reconcile(
+            &mut doc,
+            leaves(&[
+                ("Temperature", json!(25.5)),
+                ("Pressure", json!(1.0)),
+                ("Removed", json!(1)),
+                ("Added", json!(true)),
+            ]),
+        );
+
+        assert_eq!(drift.local_edits.inserts.len(), 1);
+        assert_eq!(drift.local_edits.inserts[0].0, "Added");
+
+        let repaired: Vec<_> = drift
+            .repairs
+            .inserts
+            .iter()
+            .map(|(p, v, _)| (p.as_str(), v))
+            .collect();
+        assert_eq!(
+            repaired,
+            [("Pressure", &json!(1.2)), ("Status", &json!("Running"))]
+        );
+        assert_eq!(drift.repairs.removes.len(), 1);
+        assert_eq!(drift.repairs.removes[0].0, "Removed");
+    }
+
+    #[test]
+    fn server_edits_since_baseline_become_local_edits() {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        let _ = doc.set("Temperature", json!(25.5));
+        let _ = doc.set("Pressure", json!(1.2));
+
+        let mut tracker = DriftTracker::new();
+        let server = leaves(&[("Temperature", json!(25.5)), ("Pressure", json!(1.2))]);
+        assert!(tracker.reconcile(&mut doc, server).is_empty());
+
+        // Edited on the server while events were missed
+        let drift = tracker.reconcile(&mut doc, leaves(&[("Temperature", json!(30.0))]));
+
+        assert!(drift.repairs.is_empty());
+        assert_eq!(drift.local_edits.inserts.len(), 1);
+        assert_eq!(drift.local_edits.inserts[0].1, json!(30.0));
+        assert_eq!(drift.local_edits.removes.len(), 1);
+        assert_eq!(drift.local_edits.removes[0].0, "Pressure");
+
+        let edits = doc.stable_delta(&drift.local_edits);
+        doc.apply_delta(&edits).unwrap();
+        let server = leaves(&[("Temperature", json!(30.0))]);
+        assert!(tracker.reconcile(&mut doc, server).is_empty());
+    }
+}
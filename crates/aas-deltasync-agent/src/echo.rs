@@ -0,0 +1,181 @@
+//! Echo suppression between egress and adapter ingress.
+//!
+//! Every write egress pushes to the AAS server comes back through the
+//! adapter, as a `BaSyx` event or a changed FA³ST poll. Re-ingesting it
+//! would turn each remote write into a fresh local one, so egressed writes
+//! are remembered for a short window and matching adapter changes dropped.
+
+use aas_deltasync_core::{same_value, Delta};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// A write egress made to an element, `None` meaning a deletion.
+#[derive(Debug, Clone)]
+struct EgressedWrite {
+    value: Option<Value>,
+    at: Instant,
+}
+
+/// Recently egressed writes, keyed by submodel and element path.
+#[derive(Debug)]
+pub struct EchoFilter {
+    writes: HashMap<(String, String), VecDeque<EgressedWrite>>,
+    window: Duration,
+}
+
+impl EchoFilter {
+    /// Create an empty filter that remembers writes for `window`.
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            writes: HashMap::new(),
+            window,
+        }
+    }
+
+    /// Remember a value written to an element.
+    pub fn record_write(&mut self, submodel_id: &str, path: &str, value: &Value, now: Instant) {
+        self.record(submodel_id, path, Some(value.clone()), now);
+    }
+
+    /// Remember an element deletion.
+    pub fn record_remove(&mut self, submodel_id: &str, path: &str, now: Instant) {
+        self.record(submodel_id, path, None, now);
+    }
+
+    fn record(&mut self, submodel_id: &str, path: &str, value: Option<Value>, now: Instant) {
+        self.expire(now);
+        self.writes
+            .entry((submodel_id.to_string(), path.to_string()))
+            .or_default()
+            .push_back(EgressedWrite { value, at: now });
+    }
+
+    /// Whether an adapter change is the echo of an egressed write.
+    ///
+    /// A matching write is consumed, so a later identical change made on
+    /// the server itself is ingested again.
+    pub fn is_echo(
+        &mut self,
+        submodel_id: &str,
+        path: &str,
+        value: Option<&Value>,
+        now: Instant,
+    ) -> bool {
+        self.expire(now);
+
+        let key = (submodel_id.to_string(), path.to_string());
+        let Some(pending) = self.writes.get_mut(&key) else {
+            return false;
+        };
+
+        let Some(index) = pending
+            .iter()
+            .position(|write| match (&write.value, value) {
+                (Some(written), Some(observed)) => same_value(written, observed),
+                (None, None) => true,
+                _ => false,
+            })
+        else {
+            return false;
+        };
+
+        pending.remove(index);
+        if pending.is_empty() {
+            self.writes.remove(&key);
+        }
+        true
+    }
+
+    /// Copy a delta without the entries that echo egressed writes.
+    pub fn filter(
+        &mut self,
+        submodel_id: &str,
+        delta: &Delta<String, Value>,
+        now: Instant,
+    ) -> Delta<String, Value> {
+        let mut filtered = Delta::new();
+        for (path, value, timestamp) in &delta.inserts {
+            if !self.is_echo(submodel_id, path, Some(value), now) {
+                filtered.add_insert(path.clone(), value.clone(), *timestamp);
+            }
+        }
+        for (path, timestamp) in &delta.removes {
+            if !self.is_echo(submodel_id, path, None, now) {
+                filtered.add_remove(path.clone(), *timestamp);
+            }
+        }
+        filtered
+    }
+
+    /// Forget writes older than the window.
+    fn expire(&mut self, now: Instant) {
+        let window = self.window;
+        self.writes.retain(|_, pending| {
+            pending.retain(|write| now.saturating_duration_since(write.at) <= window);
+            !pending.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aas_deltasync_core::Timestamp;
+    use uuid::Uuid;
+
+    #[test]
+    fn echoes_are_dropped_once() {
+        let mut echoes = EchoFilter::new(Duration::from_secs(30));
+        let start = Instant::now();
+
+        echoes.record_write("sm1", "Temperature", &serde_json::json!(25.5), start);
+        echoes.record_remove("sm1", "Pressure", start);
+
+        // BaSyx reports property values as strings
+        assert!(echoes.is_echo(
+            "sm1",
+            "Temperature",
+            Some(&serde_json::json!("25.5")),
+            start
+        ));
+        assert!(!echoes.is_echo("sm1", "Temperature", Some(&serde_json::json!(25.5)), start));
+        assert!(!echoes.is_echo("sm2", "Pressure", None, start));
+        assert!(echoes.is_echo("sm1", "Pressure", None, start));
+    }
+
+    #[test]
+    fn filter_keeps_genuine_changes() {
+        let mut echoes = EchoFilter::new(Duration::from_secs(30));
+        let start = Instant::now();
+        let timestamp = Timestamp {
+            physical_ms: 1000,
+            logical: 0,
+            actor_id: Uuid::new_v4(),
+        };
+
+        echoes.record_write("sm1", "Temperature", &serde_json::json!(25.5), start);
+        echoes.record_write("sm1", "Status", &serde_json::json!("Running"), start);
+
+        let mut polled = Delta::new();
+        polled.add_insert(
+            "Temperature".to_string(),
+            serde_json::json!(25.5),
+            timestamp,
+        );
+        polled.add_insert(
+            "Status".to_string(),
+            serde_json::json!("Stopped"),
+            timestamp,
+        );
+
+        let filtered = echoes.filter("sm1", &polled, start);
+        assert_eq!(filtered.inserts.len(), 1);
+        assert_eq!(filtered.inserts[0].0, "Status");
+
+        // Unmatched writes are forgotten after the window
+        let later = start + Duration::from_secs(31);
+        assert!(!echoes.is_echo("sm1", "Status", Some(&serde_json::json!("Running")), later));
+    }
+}
@@ -10,22 +10,47 @@
 //! 3. **Replication**: Publishes deltas to MQTT and handles anti-entropy
 //! 4. **Egress**: Pushes converged state back to AAS server (optional)
 //! 5. **Persistence**: Snapshots and compacts delta log
+//!
+//! `aas-deltasync-agent conflict-log [--doc <doc-id>] [--path <pattern>]
+//! [--since <ms>]` prints the audit trail of overwritten writes from the
+//! database at `DELTASYNC_DB_PATH` as JSON lines instead.
+//! `aas-deltasync-agent conflicts [--doc <doc-id>]` prints the unresolved
+//! conflicts of the subscribed documents as persisted there.
+//! `aas-deltasync-agent evict-peer <peer-id>` forgets a peer that left the
+//! mesh, so it no longer holds back compaction.
 
-use aas_deltasync_core::Hlc;
-use anyhow::Result;
+use aas_deltasync_core::{Hlc, PathPattern};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
+mod blobs;
 mod config;
+mod drift;
+mod echo;
+mod peers;
 mod persistence;
 mod replication;
 mod runtime;
+mod skew;
 
 pub use config::AgentConfig;
 pub use runtime::Agent;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("conflict-log") {
+        return print_conflict_log(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("conflicts") {
+        return print_conflicts(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("evict-peer") {
+        return evict_peer(&args[1..]);
+    }
+
     // Initialize logging
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -54,3 +79,102 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Print the conflict log entries matching the command-line filters.
+fn print_conflict_log(args: &[String]) -> Result<()> {
+    let mut doc_id = None;
+    let mut path = None;
+    let mut since_ms = 0;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("Missing value for {flag}"))?;
+        match flag.as_str() {
+            "--doc" => doc_id = Some(value.as_str()),
+            "--path" => path = Some(PathPattern::new(value.as_str())),
+            "--since" => since_ms = value.parse().context("Invalid --since")?,
+            other => anyhow::bail!("Unknown conflict-log option: {other}"),
+        }
+    }
+
+    let store = open_store()?;
+    let entries = store
+        .query_conflict_log(doc_id, path.as_ref(), since_ms)
+        .context("Failed to query conflict log")?;
+    for entry in entries {
+        println!("{}", serde_json::to_string(&entry)?);
+    }
+
+    Ok(())
+}
+
+/// Print the unresolved conflicts of the subscribed documents.
+///
+/// Documents are rebuilt from the database, so conflicts are those of the
+/// state persisted by the running agent.
+fn print_conflicts(args: &[String]) -> Result<()> {
+    let mut doc_filter = None;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("Missing value for {flag}"))?;
+        match flag.as_str() {
+            "--doc" => doc_filter = Some(value.as_str()),
+            other => anyhow::bail!("Unknown conflicts option: {other}"),
+        }
+    }
+
+    let config = AgentConfig::from_env()?;
+    let store = open_store()?;
+    for sub in &config.subscriptions {
+        let doc_id = sub.doc_id();
+        if doc_filter.is_some_and(|filter| filter != doc_id.to_string()) {
+            continue;
+        }
+        let mut doc = runtime::restore_document(
+            &store,
+            &doc_id,
+            Uuid::nil(),
+            config.replication.remove_semantics,
+        )?;
+        doc.multi_value_paths
+            .clone_from(&config.replication.multi_value_paths);
+        for conflict in doc.conflicts() {
+            let entry = serde_json::json!({
+                "doc_id": doc_id.to_string(),
+                "path": conflict.path,
+                "values": conflict.values,
+            });
+            println!("{entry}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Forget a peer and its progress on every document.
+fn evict_peer(args: &[String]) -> Result<()> {
+    let [peer_id] = args else {
+        anyhow::bail!("Usage: aas-deltasync-agent evict-peer <peer-id>");
+    };
+
+    let store = open_store()?;
+    if store.evict_peer(peer_id).context("Failed to evict peer")? {
+        println!("Evicted peer {peer_id}");
+    } else {
+        println!("Unknown peer {peer_id}");
+    }
+
+    Ok(())
+}
+
+/// Open the database at `DELTASYNC_DB_PATH`, or the default path.
+fn open_store() -> Result<persistence::SqliteStore> {
+    let db_path = std::env::var("DELTASYNC_DB_PATH").map_or_else(
+        |_| AgentConfig::default().persistence.db_path,
+        PathBuf::from,
+    );
+    persistence::SqliteStore::open(&db_path).context("Failed to open SQLite database")
+}
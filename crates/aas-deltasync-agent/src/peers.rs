@@ -0,0 +1,116 @@
+//! Peer membership tracking from agent hellos.
+
+use aas_deltasync_core::DocId;
+use aas_deltasync_proto::AgentHello;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// What is known about a peer from its hellos.
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    /// Advertised capabilities
+    pub capabilities: Vec<String>,
+    /// Agent version
+    pub version: String,
+    /// Documents the peer announced itself for
+    pub documents: HashSet<DocId>,
+    /// When the last hello arrived
+    pub last_seen: Instant,
+}
+
+/// In-memory table of live peers.
+///
+/// Peers are added by their first hello and expire once no hello has
+/// arrived within the TTL.
+#[derive(Debug)]
+pub struct PeerTable {
+    peers: HashMap<Uuid, PeerInfo>,
+    ttl: Duration,
+}
+
+impl PeerTable {
+    /// Create an empty peer table.
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            peers: HashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Record a hello received for a document.
+    ///
+    /// Returns `true` if the peer was not known before.
+    pub fn observe(&mut self, hello: &AgentHello, doc_id: &DocId, now: Instant) -> bool {
+        let mut joined = false;
+        let peer = self.peers.entry(hello.agent_id).or_insert_with(|| {
+            joined = true;
+            PeerInfo {
+                capabilities: Vec::new(),
+                version: String::new(),
+                documents: HashSet::new(),
+                last_seen: now,
+            }
+        });
+
+        peer.capabilities.clone_from(&hello.capabilities);
+        peer.version.clone_from(&hello.version);
+        peer.documents.insert(doc_id.clone());
+        peer.last_seen = now;
+
+        joined
+    }
+
+    /// Drop peers whose last hello is older than the TTL.
+    ///
+    /// Returns the expired peers.
+    pub fn expire(&mut self, now: Instant) -> Vec<(Uuid, PeerInfo)> {
+        let ttl = self.ttl;
+        let stale: Vec<Uuid> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| now.saturating_duration_since(peer.last_seen) > ttl)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+
+        stale
+            .into_iter()
+            .filter_map(|peer_id| Some((peer_id, self.peers.remove(&peer_id)?)))
+            .collect()
+    }
+
+    /// Number of live peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_join_and_expire() {
+        let mut peers = PeerTable::new(Duration::from_secs(30));
+        let start = Instant::now();
+        let hello = AgentHello::new(Uuid::new_v4(), Vec::new());
+
+        let sm1 = DocId::value_view("aas1", "sm1");
+        let sm2 = DocId::value_view("aas1", "sm2");
+
+        assert!(peers.observe(&hello, &sm1, start));
+        assert!(!peers.observe(&hello, &sm2, start + Duration::from_secs(20)));
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers.peers[&hello.agent_id].documents.len(), 2);
+
+        // Still within the TTL of the second hello
+        assert!(peers.expire(start + Duration::from_secs(45)).is_empty());
+
+        let expired = peers.expire(start + Duration::from_secs(60));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, hello.agent_id);
+        assert_eq!(peers.len(), 0);
+    }
+}
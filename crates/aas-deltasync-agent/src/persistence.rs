@@ -1,9 +1,29 @@
 //! `SQLite` persistence layer.
 
-use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
+use aas_deltasync_core::{DocId, LwwRegister, Overwrite, PathPattern, Timestamp, VersionVector};
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult, Row};
+use serde::Serialize;
 use std::path::Path;
+use uuid::Uuid;
+
+/// A dropped write recorded in the conflict log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConflictLogEntry {
+    /// Display form of the document's `DocId`
+    pub doc_id: String,
+    /// Index path of the element
+    pub path: String,
+    /// The write kept
+    pub winner: LwwRegister<serde_json::Value>,
+    /// The write dropped
+    pub loser: LwwRegister<serde_json::Value>,
+    /// When the agent recorded it (Unix seconds)
+    pub recorded_at: u64,
+}
 
 /// `SQLite`-backed persistence store.
+///
+/// Documents are keyed by the display form of their `DocId`.
 pub struct SqliteStore {
     conn: Connection,
 }
@@ -46,7 +66,8 @@ impl SqliteStore {
                 created_at INTEGER NOT NULL
             );
 
-            -- Delta log
+            -- Delta log, indexed by each delta's latest timestamp; `logical`
+            -- is NULL when the delta holds timestamps of several actors
             CREATE TABLE IF NOT EXISTS delta_log (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 doc_id TEXT NOT NULL,
@@ -54,6 +75,7 @@ impl SqliteStore {
                 delta_bytes BLOB NOT NULL,
                 actor_id TEXT NOT NULL,
                 hlc_ts INTEGER NOT NULL,
+                logical INTEGER,
                 created_at INTEGER NOT NULL,
                 UNIQUE(doc_id, delta_id)
             );
@@ -61,7 +83,7 @@ impl SqliteStore {
             CREATE INDEX IF NOT EXISTS idx_delta_log_doc_id ON delta_log(doc_id);
             CREATE INDEX IF NOT EXISTS idx_delta_log_hlc ON delta_log(hlc_ts);
 
-            -- Peer progress tracking
+            -- Peer progress: the CBOR version vector each peer last reported
             CREATE TABLE IF NOT EXISTS peer_progress (
                 peer_id TEXT NOT NULL,
                 doc_id TEXT NOT NULL,
@@ -69,24 +91,67 @@ impl SqliteStore {
                 updated_at INTEGER NOT NULL,
                 PRIMARY KEY (peer_id, doc_id)
             );
+
+            -- Peers announced through agent hellos
+            CREATE TABLE IF NOT EXISTS peers (
+                peer_id TEXT PRIMARY KEY,
+                agent_version TEXT NOT NULL,
+                capabilities TEXT NOT NULL,
+                last_seen INTEGER NOT NULL
+            );
+
+            -- Writes dropped in favour of newer writes, for auditing
+            CREATE TABLE IF NOT EXISTS conflict_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                doc_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                winner_value TEXT NOT NULL,
+                winner_physical_ms INTEGER NOT NULL,
+                winner_logical INTEGER NOT NULL,
+                winner_actor TEXT NOT NULL,
+                loser_value TEXT NOT NULL,
+                loser_physical_ms INTEGER NOT NULL,
+                loser_logical INTEGER NOT NULL,
+                loser_actor TEXT NOT NULL,
+                recorded_at INTEGER NOT NULL,
+                UNIQUE(doc_id, path, winner_physical_ms, winner_logical, winner_actor,
+                       loser_physical_ms, loser_logical, loser_actor)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_conflict_log_doc_id ON conflict_log(doc_id);
             ",
         )?;
 
+        // Logs written before `logical` existed keep NULL there, so their
+        // deltas are always replayed and sent
+        let has_logical = self
+            .conn
+            .prepare("SELECT 1 FROM pragma_table_info('delta_log') WHERE name = 'logical'")?
+            .exists([])?;
+        if !has_logical {
+            self.conn
+                .execute("ALTER TABLE delta_log ADD COLUMN logical INTEGER", [])?;
+        }
+
         Ok(())
     }
 
     /// Save a delta to the log.
     ///
+    /// `latest` is the delta's latest timestamp. If `single_author` is set,
+    /// every timestamp in the delta is from the actor of `latest`, and
+    /// [`Self::get_deltas_beyond`] can skip the delta by that timestamp alone.
+    ///
     /// # Errors
     ///
     /// Returns error if insert fails.
     pub fn save_delta(
         &self,
-        doc_id: &str,
+        doc_id: &DocId,
         delta_id: &[u8],
         delta_bytes: &[u8],
-        actor_id: &str,
-        hlc_ts: u64,
+        latest: Timestamp,
+        single_author: bool,
     ) -> SqliteResult<()> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -94,37 +159,78 @@ impl SqliteStore {
             .as_secs();
 
         let now_i64 = to_i64(now)?;
-        let hlc_ts_i64 = to_i64(hlc_ts)?;
+        let hlc_ts_i64 = to_i64(latest.physical_ms)?;
 
         self.conn.execute(
             r"
-            INSERT OR REPLACE INTO delta_log (doc_id, delta_id, delta_bytes, actor_id, hlc_ts, created_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            INSERT OR REPLACE INTO delta_log (doc_id, delta_id, delta_bytes, actor_id, hlc_ts, logical, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
             ",
-            (doc_id, delta_id, delta_bytes, actor_id, hlc_ts_i64, now_i64),
+            (
+                doc_id.to_string(),
+                delta_id,
+                delta_bytes,
+                latest.actor_id.to_string(),
+                hlc_ts_i64,
+                single_author.then_some(latest.logical),
+                now_i64,
+            ),
         )?;
 
         Ok(())
     }
 
-    /// Get deltas for a document after a given timestamp.
+    /// Get deltas for a document that a version vector may not cover.
+    ///
+    /// Deltas up to the vector's contiguous entry for their actor are
+    /// filtered out in SQL. Deltas within a range the vector holds past a
+    /// gap, and deltas by several actors, are still returned; callers check
+    /// those with [`VersionVector::contains`].
+    ///
+    /// Returns `(delta_id, delta_bytes)` pairs in timestamp order.
     ///
     /// # Errors
     ///
     /// Returns error if query fails.
-    #[allow(dead_code)]
-    pub fn get_deltas_after(&self, doc_id: &str, after_ts: u64) -> SqliteResult<Vec<Vec<u8>>> {
+    pub fn get_deltas_beyond(
+        &self,
+        doc_id: &DocId,
+        version: &VersionVector,
+    ) -> SqliteResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let entries = version
+            .iter()
+            .map(|timestamp| {
+                Ok(serde_json::json!([
+                    timestamp.actor_id.to_string(),
+                    to_i64(timestamp.physical_ms)?,
+                    timestamp.logical,
+                ]))
+            })
+            .collect::<SqliteResult<Vec<_>>>()?;
+
         let mut stmt = self.conn.prepare(
             r"
-            SELECT delta_bytes FROM delta_log
-            WHERE doc_id = ?1 AND hlc_ts > ?2
-            ORDER BY hlc_ts ASC
+            WITH seen(actor_id, hlc_ts, logical) AS (
+                SELECT value ->> 0, value ->> 1, value ->> 2 FROM json_each(?2)
+            )
+            SELECT d.delta_id, d.delta_bytes FROM delta_log d
+            LEFT JOIN seen s ON s.actor_id = d.actor_id
+            WHERE d.doc_id = ?1
+              AND (d.logical IS NULL OR s.actor_id IS NULL
+                   OR (d.hlc_ts, d.logical) > (s.hlc_ts, s.logical))
+            ORDER BY d.hlc_ts ASC, d.logical ASC
             ",
         )?;
 
         let deltas = stmt
-            .query_map((doc_id, to_i64(after_ts)?), |row| row.get(0))?
-            .collect::<SqliteResult<Vec<Vec<u8>>>>()?;
+            .query_map(
+                (
+                    doc_id.to_string(),
+                    serde_json::Value::from(entries).to_string(),
+                ),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<SqliteResult<Vec<(Vec<u8>, Vec<u8>)>>>()?;
 
         Ok(deltas)
     }
@@ -134,10 +240,9 @@ impl SqliteStore {
     /// # Errors
     ///
     /// Returns error if insert fails.
-    #[allow(dead_code)]
     pub fn save_snapshot(
         &self,
-        doc_id: &str,
+        doc_id: &DocId,
         snapshot_bytes: &[u8],
         clock_bytes: &[u8],
     ) -> SqliteResult<()> {
@@ -153,7 +258,7 @@ impl SqliteStore {
             INSERT OR REPLACE INTO doc_snapshots (doc_id, snapshot_bytes, snapshot_clock, created_at)
             VALUES (?1, ?2, ?3, ?4)
             ",
-            (doc_id, snapshot_bytes, clock_bytes, now_i64),
+            (doc_id.to_string(), snapshot_bytes, clock_bytes, now_i64),
         )?;
 
         Ok(())
@@ -164,8 +269,7 @@ impl SqliteStore {
     /// # Errors
     ///
     /// Returns error if query fails.
-    #[allow(dead_code)]
-    pub fn get_snapshot(&self, doc_id: &str) -> SqliteResult<Option<(Vec<u8>, Vec<u8>)>> {
+    pub fn get_snapshot(&self, doc_id: &DocId) -> SqliteResult<Option<(Vec<u8>, Vec<u8>)>> {
         let mut stmt = self.conn.prepare(
             r"
             SELECT snapshot_bytes, snapshot_clock FROM doc_snapshots
@@ -174,7 +278,7 @@ impl SqliteStore {
         )?;
 
         let result = stmt
-            .query_row([doc_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .query_row([doc_id.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))
             .optional()?;
 
         Ok(result)
@@ -185,20 +289,19 @@ impl SqliteStore {
     /// # Errors
     ///
     /// Returns error if delete fails.
-    #[allow(dead_code)]
-    pub fn compact_deltas_before(&self, doc_id: &str, before_ts: u64) -> SqliteResult<usize> {
+    pub fn compact_deltas_before(&self, doc_id: &DocId, before_ts: u64) -> SqliteResult<usize> {
         let deleted = self.conn.execute(
             r"
             DELETE FROM delta_log
             WHERE doc_id = ?1 AND hlc_ts < ?2
             ",
-            (doc_id, to_i64(before_ts)?),
+            (doc_id.to_string(), to_i64(before_ts)?),
         )?;
 
         Ok(deleted)
     }
 
-    /// Update peer progress.
+    /// Record the version vector a peer last reported for a document.
     ///
     /// # Errors
     ///
@@ -206,8 +309,8 @@ impl SqliteStore {
     pub fn update_peer_progress(
         &self,
         peer_id: &str,
-        doc_id: &str,
-        last_delta_id: &[u8],
+        doc_id: &DocId,
+        version: &[u8],
     ) -> SqliteResult<()> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -221,46 +324,524 @@ impl SqliteStore {
             INSERT OR REPLACE INTO peer_progress (peer_id, doc_id, last_ack_delta_id, updated_at)
             VALUES (?1, ?2, ?3, ?4)
             ",
-            (peer_id, doc_id, last_delta_id, now_i64),
+            (peer_id, doc_id.to_string(), version, now_i64),
         )?;
 
         Ok(())
     }
+
+    /// Get the version vector every peer last reported for a document.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if query fails.
+    pub fn get_peer_progress(&self, doc_id: &DocId) -> SqliteResult<Vec<(String, Vec<u8>)>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT peer_id, last_ack_delta_id FROM peer_progress
+            WHERE doc_id = ?1 AND last_ack_delta_id IS NOT NULL
+            ",
+        )?;
+
+        let progress = stmt
+            .query_map([doc_id.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqliteResult<Vec<(String, Vec<u8>)>>>()?;
+
+        Ok(progress)
+    }
+
+    /// Record that a peer was seen.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if update fails.
+    pub fn record_peer(
+        &self,
+        peer_id: &str,
+        agent_version: &str,
+        capabilities: &[String],
+    ) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let now_i64 = to_i64(now)?;
+
+        self.conn.execute(
+            r"
+            INSERT OR REPLACE INTO peers (peer_id, agent_version, capabilities, last_seen)
+            VALUES (?1, ?2, ?3, ?4)
+            ",
+            (peer_id, agent_version, capabilities.join(","), now_i64),
+        )?;
+
+        Ok(())
+    }
+
+    /// Remove peers not seen since `cutoff` (Unix seconds).
+    ///
+    /// Their progress is kept: compaction must keep waiting for a peer that
+    /// is merely offline. Use [`Self::evict_peer`] for peers that left.
+    ///
+    /// Returns the IDs of the removed peers.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if delete fails.
+    pub fn expire_peers(&self, cutoff: u64) -> SqliteResult<Vec<String>> {
+        let cutoff = to_i64(cutoff)?;
+
+        let mut stmt = self.conn.prepare(
+            r"
+            DELETE FROM peers
+            WHERE last_seen < ?1
+            RETURNING peer_id
+            ",
+        )?;
+        let expired = stmt
+            .query_map([cutoff], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<String>>>()?;
+
+        Ok(expired)
+    }
+
+    /// Remove a peer and its progress on every document.
+    ///
+    /// The peer no longer holds back compaction. Returns whether anything
+    /// was known about it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if delete fails.
+    pub fn evict_peer(&self, peer_id: &str) -> SqliteResult<bool> {
+        let peers = self
+            .conn
+            .execute("DELETE FROM peers WHERE peer_id = ?1", [peer_id])?;
+        let progress = self
+            .conn
+            .execute("DELETE FROM peer_progress WHERE peer_id = ?1", [peer_id])?;
+
+        Ok(peers + progress > 0)
+    }
+
+    /// Record dropped writes in the conflict log.
+    ///
+    /// A write dropped again, for example when a delta is redelivered, is
+    /// recorded once.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if insert fails.
+    pub fn save_overwrites(
+        &self,
+        doc_id: &DocId,
+        overwrites: &[Overwrite<String, serde_json::Value>],
+    ) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let now_i64 = to_i64(now)?;
+
+        let mut stmt = self.conn.prepare(
+            r"
+            INSERT OR IGNORE INTO conflict_log (
+                doc_id, path,
+                winner_value, winner_physical_ms, winner_logical, winner_actor,
+                loser_value, loser_physical_ms, loser_logical, loser_actor,
+                recorded_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            ",
+        )?;
+        for overwrite in overwrites {
+            let (winner, loser) = (&overwrite.winner, &overwrite.loser);
+            stmt.execute(rusqlite::params![
+                doc_id.to_string(),
+                overwrite.key,
+                to_json(&winner.value)?,
+                to_i64(winner.timestamp.physical_ms)?,
+                winner.timestamp.logical,
+                winner.timestamp.actor_id.to_string(),
+                to_json(&loser.value)?,
+                to_i64(loser.timestamp.physical_ms)?,
+                loser.timestamp.logical,
+                loser.timestamp.actor_id.to_string(),
+                now_i64,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove conflict log entries recorded before `cutoff` (Unix seconds).
+    ///
+    /// Returns the number of removed entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if delete fails.
+    pub fn prune_conflict_log(&self, cutoff: u64) -> SqliteResult<usize> {
+        self.conn.execute(
+            r"
+            DELETE FROM conflict_log
+            WHERE recorded_at < ?1
+            ",
+            [to_i64(cutoff)?],
+        )
+    }
+
+    /// Query the conflict log, ordered by the time of the winning write.
+    ///
+    /// Filters by the display form of a `DocId`, by a pattern the path
+    /// must match, and by the earliest winning write (HLC milliseconds).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if query fails or a row cannot be decoded.
+    pub fn query_conflict_log(
+        &self,
+        doc_id: Option<&str>,
+        path: Option<&PathPattern>,
+        since_ms: u64,
+    ) -> SqliteResult<Vec<ConflictLogEntry>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT doc_id, path,
+                   winner_value, winner_physical_ms, winner_logical, winner_actor,
+                   loser_value, loser_physical_ms, loser_logical, loser_actor,
+                   recorded_at
+            FROM conflict_log
+            WHERE (?1 IS NULL OR doc_id = ?1) AND winner_physical_ms >= ?2
+            ORDER BY winner_physical_ms ASC, winner_logical ASC, id ASC
+            ",
+        )?;
+
+        let entries = stmt
+            .query_map((doc_id, to_i64(since_ms)?), |row| {
+                Ok(ConflictLogEntry {
+                    doc_id: row.get(0)?,
+                    path: row.get(1)?,
+                    winner: register_at(row, 2)?,
+                    loser: register_at(row, 6)?,
+                    recorded_at: row.get(10)?,
+                })
+            })?
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .map_or(true, |entry| path.map_or(true, |p| p.matches(&entry.path)))
+            })
+            .collect::<SqliteResult<Vec<ConflictLogEntry>>>()?;
+
+        Ok(entries)
+    }
 }
 
 fn to_i64(value: u64) -> SqliteResult<i64> {
     i64::try_from(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
 }
 
+fn to_json(value: &serde_json::Value) -> SqliteResult<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Read a register stored as value, physical time, logical counter and
+/// actor columns starting at `index`.
+fn register_at(row: &Row<'_>, index: usize) -> SqliteResult<LwwRegister<serde_json::Value>> {
+    let decode = |index: usize, e: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e)
+    };
+
+    let value: String = row.get(index)?;
+    let value = serde_json::from_str(&value).map_err(|e| decode(index, Box::new(e)))?;
+    let actor: String = row.get(index + 3)?;
+    let timestamp = Timestamp {
+        physical_ms: row.get(index + 1)?,
+        logical: row.get(index + 2)?,
+        actor_id: Uuid::parse_str(&actor).map_err(|e| decode(index + 3, Box::new(e)))?,
+    };
+    Ok(LwwRegister::new(value, timestamp))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aas_deltasync_core::View;
+
+    fn timestamp(physical_ms: u64, logical: u32, actor_id: Uuid) -> Timestamp {
+        Timestamp {
+            physical_ms,
+            logical,
+            actor_id,
+        }
+    }
 
     #[test]
     fn sqlite_store_basic_operations() {
         let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+        let actor = Uuid::from_bytes([1; 16]);
 
         // Save a delta
         store
-            .save_delta("doc1", b"delta1", b"payload1", "actor1", 1000)
+            .save_delta(
+                &doc1,
+                b"delta1",
+                b"payload1",
+                timestamp(1000, 0, actor),
+                true,
+            )
             .unwrap();
         store
-            .save_delta("doc1", b"delta2", b"payload2", "actor1", 2000)
+            .save_delta(
+                &doc1,
+                b"delta2",
+                b"payload2",
+                timestamp(2000, 0, actor),
+                true,
+            )
             .unwrap();
 
-        // Get deltas after timestamp
-        let deltas = store.get_deltas_after("doc1", 1000).unwrap();
+        // Get deltas a version vector does not cover
+        let mut version = VersionVector::new();
+        version.observe(timestamp(1000, 0, actor));
+        let deltas = store.get_deltas_beyond(&doc1, &version).unwrap();
         assert_eq!(deltas.len(), 1);
-        assert_eq!(deltas[0], b"payload2");
+        assert_eq!(deltas[0], (b"delta2".to_vec(), b"payload2".to_vec()));
 
         // Save and retrieve snapshot
-        store.save_snapshot("doc1", b"snapshot", b"clock").unwrap();
-        let (snap, clock) = store.get_snapshot("doc1").unwrap().unwrap();
+        store.save_snapshot(&doc1, b"snapshot", b"clock").unwrap();
+        let (snap, clock) = store.get_snapshot(&doc1).unwrap().unwrap();
         assert_eq!(snap, b"snapshot");
         assert_eq!(clock, b"clock");
 
         // Compact
-        let deleted = store.compact_deltas_before("doc1", 1500).unwrap();
+        let deleted = store.compact_deltas_before(&doc1, 1500).unwrap();
         assert_eq!(deleted, 1);
     }
+
+    #[test]
+    fn deltas_beyond_skip_what_a_version_vector_covers() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+        let (a, b) = (Uuid::from_bytes([1; 16]), Uuid::from_bytes([2; 16]));
+
+        let rows = [
+            (b"a1", timestamp(1000, 0, a), true),
+            (b"a2", timestamp(1000, 1, a), true),
+            (b"a3", timestamp(2000, 0, a), true),
+            (b"b1", timestamp(1500, 0, b), true),
+            (b"ab", timestamp(900, 0, a), false),
+        ];
+        for (delta_id, latest, single_author) in rows {
+            store
+                .save_delta(&doc1, delta_id, b"payload", latest, single_author)
+                .unwrap();
+        }
+        let ids = |version: &VersionVector| {
+            store
+                .get_deltas_beyond(&doc1, version)
+                .unwrap()
+                .into_iter()
+                .map(|(delta_id, _)| delta_id)
+                .collect::<Vec<_>>()
+        };
+
+        // An empty vector covers nothing
+        assert_eq!(ids(&VersionVector::new()).len(), rows.len());
+
+        // Actor b is unknown, and deltas of several actors are always sent
+        let mut version = VersionVector::new();
+        version.observe(timestamp(1000, 0, a));
+        assert_eq!(
+            ids(&version),
+            [
+                b"ab".to_vec(),
+                b"a2".to_vec(),
+                b"b1".to_vec(),
+                b"a3".to_vec()
+            ]
+        );
+
+        version.observe(timestamp(2000, 0, a));
+        version.observe(timestamp(1500, 0, b));
+        assert_eq!(ids(&version), [b"ab".to_vec()]);
+    }
+
+    #[test]
+    fn delta_logs_without_logical_column_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.db");
+        let doc1 = DocId::value_view("aas1", "sm1");
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TABLE delta_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                doc_id TEXT NOT NULL,
+                delta_id BLOB NOT NULL,
+                delta_bytes BLOB NOT NULL,
+                actor_id TEXT NOT NULL,
+                hlc_ts INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                UNIQUE(doc_id, delta_id)
+            );
+            ",
+        )
+        .unwrap();
+        let actor = Uuid::from_bytes([1; 16]);
+        conn.execute(
+            r"
+            INSERT INTO delta_log (doc_id, delta_id, delta_bytes, actor_id, hlc_ts, created_at)
+            VALUES (?1, X'01', X'02', ?2, 1000, 0)
+            ",
+            (doc1.to_string(), actor.to_string()),
+        )
+        .unwrap();
+        drop(conn);
+
+        // Old rows carry no logical counter, so no vector can skip them
+        let store = SqliteStore::open(&path).unwrap();
+        let mut version = VersionVector::new();
+        version.observe(timestamp(5000, 0, actor));
+        assert_eq!(store.get_deltas_beyond(&doc1, &version).unwrap().len(), 1);
+
+        store
+            .save_delta(&doc1, b"new", b"payload", timestamp(2000, 0, actor), true)
+            .unwrap();
+        assert_eq!(store.get_deltas_beyond(&doc1, &version).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn peer_progress_per_document() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+        // Same submodel, different view
+        let doc2 = DocId::new("aas1", "sm1", View::Metadata);
+
+        store.update_peer_progress("peer1", &doc1, b"ack1").unwrap();
+        store.update_peer_progress("peer2", &doc1, b"ack2").unwrap();
+        store.update_peer_progress("peer1", &doc2, b"ack3").unwrap();
+        store.update_peer_progress("peer1", &doc1, b"ack4").unwrap();
+
+        let mut progress = store.get_peer_progress(&doc1).unwrap();
+        progress.sort();
+        assert_eq!(
+            progress,
+            vec![
+                ("peer1".to_string(), b"ack4".to_vec()),
+                ("peer2".to_string(), b"ack2".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflict_log_records_overwrites_once() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+        let write = |value: i64, physical_ms: u64, actor: u8| {
+            let timestamp = Timestamp {
+                physical_ms,
+                logical: 0,
+                actor_id: Uuid::from_bytes([actor; 16]),
+            };
+            LwwRegister::new(serde_json::json!(value), timestamp)
+        };
+        let overwrite = |path: &str, winner, loser| Overwrite {
+            key: path.to_string(),
+            winner,
+            loser,
+        };
+
+        let setpoint = overwrite("Setpoints.Speed", write(20, 2000, 2), write(10, 1000, 1));
+        let limit = overwrite("Limits.max", write(90, 3000, 1), write(80, 1500, 2));
+        store
+            .save_overwrites(&doc1, &[setpoint.clone(), limit.clone()])
+            .unwrap();
+        // Redelivered deltas drop the same writes again
+        store
+            .save_overwrites(&doc1, std::slice::from_ref(&setpoint))
+            .unwrap();
+
+        let entries = store.query_conflict_log(None, None, 0).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].doc_id, doc1.to_string());
+        assert_eq!(entries[0].path, setpoint.key);
+        assert_eq!(entries[0].winner, setpoint.winner);
+        assert_eq!(entries[0].loser, setpoint.loser);
+        assert_eq!(entries[1].path, limit.key);
+
+        let pattern = PathPattern::new("Setpoints.*");
+        let entries = store
+            .query_conflict_log(Some(&doc1.to_string()), Some(&pattern), 0)
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "Setpoints.Speed");
+        assert_eq!(store.query_conflict_log(None, None, 2500).unwrap().len(), 1);
+        let doc2 = DocId::value_view("aas2", "sm1");
+        assert!(store
+            .query_conflict_log(Some(&doc2.to_string()), None, 0)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn prune_conflict_log_drops_old_entries() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+        let write = |value: i64, actor: u8| {
+            LwwRegister::new(
+                serde_json::json!(value),
+                Timestamp {
+                    physical_ms: 1000,
+                    logical: 0,
+                    actor_id: Uuid::from_bytes([actor; 16]),
+                },
+            )
+        };
+        let overwrite = Overwrite {
+            key: "Setpoints.Speed".to_string(),
+            winner: write(20, 2),
+            loser: write(10, 1),
+        };
+        store.save_overwrites(&doc1, &[overwrite]).unwrap();
+
+        assert_eq!(store.prune_conflict_log(0).unwrap(), 0);
+        assert_eq!(store.query_conflict_log(None, None, 0).unwrap().len(), 1);
+        assert_eq!(store.prune_conflict_log(u64::from(u32::MAX)).unwrap(), 1);
+        assert!(store.query_conflict_log(None, None, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expire_peers_keeps_progress_until_evicted() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc1 = DocId::value_view("aas1", "sm1");
+
+        store
+            .record_peer(
+                "peer1",
+                "0.1.0",
+                &["SubmodelRepositoryServiceSpecification".to_string()],
+            )
+            .unwrap();
+        store.update_peer_progress("peer1", &doc1, b"ack1").unwrap();
+
+        // Nothing is older than the epoch
+        assert!(store.expire_peers(0).unwrap().is_empty());
+
+        // Everything is older than the far future, but progress stays
+        assert_eq!(
+            store.expire_peers(u64::MAX >> 1).unwrap(),
+            vec!["peer1".to_string()]
+        );
+        assert_eq!(store.get_peer_progress(&doc1).unwrap().len(), 1);
+
+        assert!(store.evict_peer("peer1").unwrap());
+        assert!(store.get_peer_progress(&doc1).unwrap().is_empty());
+        assert!(!store.evict_peer("peer1").unwrap());
+    }
 }
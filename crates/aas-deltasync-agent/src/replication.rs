@@ -1,6 +1,9 @@
 //! Replication layer for delta dissemination.
 
-use aas_deltasync_proto::{AntiEntropyRequest, AntiEntropyResponse, DocDelta, TopicScheme};
+use aas_deltasync_proto::{
+    AgentHello, AntiEntropyRequest, AntiEntropyResponse, BlobChunk, BlobRequest, ConflictReport,
+    DocDelta, TopicScheme,
+};
 use rumqttc::{AsyncClient, EventLoop, MqttOptions, QoS, Transport};
 use std::fs;
 use std::path::Path;
@@ -8,6 +11,7 @@ use std::time::Duration;
 use url::Url;
 
 /// Replication manager for delta dissemination.
+#[derive(Clone)]
 pub struct ReplicationManager {
     client: AsyncClient,
     topic_scheme: TopicScheme,
@@ -60,6 +64,31 @@ impl ReplicationManager {
         Ok(())
     }
 
+    /// Publish an agent hello for a document.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if publish fails.
+    pub async fn publish_hello(
+        &self,
+        doc_hash: &str,
+        hello: &AgentHello,
+    ) -> Result<(), ReplicationError> {
+        let topic = self.topic_scheme.hello(doc_hash);
+        let payload = hello
+            .to_cbor()
+            .map_err(|e| ReplicationError::Serialize(e.to_string()))?;
+
+        tracing::debug!(topic, payload_len = payload.len(), "Publishing agent hello");
+
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, false, payload)
+            .await
+            .map_err(|e| ReplicationError::Publish(e.to_string()))?;
+
+        Ok(())
+    }
+
     /// Publish a delta.
     ///
     /// # Errors
@@ -120,7 +149,6 @@ impl ReplicationManager {
     /// # Errors
     ///
     /// Returns error if publish fails.
-    #[allow(dead_code)]
     pub async fn publish_ae_request(
         &self,
         doc_hash: &str,
@@ -145,6 +173,95 @@ impl ReplicationManager {
 
         Ok(())
     }
+
+    /// Publish a blob content request.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if publish fails.
+    pub async fn publish_blob_request(
+        &self,
+        doc_hash: &str,
+        request: &BlobRequest,
+    ) -> Result<(), ReplicationError> {
+        let topic = self.topic_scheme.blob_request(doc_hash);
+        let payload = request
+            .to_cbor()
+            .map_err(|e| ReplicationError::Serialize(e.to_string()))?;
+
+        tracing::debug!(
+            topic,
+            content_hash = %request.content_hash,
+            "Publishing blob request"
+        );
+
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, false, payload)
+            .await
+            .map_err(|e| ReplicationError::Publish(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Publish a chunk of blob content.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if publish fails.
+    pub async fn publish_blob_chunk(
+        &self,
+        doc_hash: &str,
+        chunk: &BlobChunk,
+    ) -> Result<(), ReplicationError> {
+        let topic = self.topic_scheme.blob_chunk(doc_hash);
+        let payload = chunk
+            .to_cbor()
+            .map_err(|e| ReplicationError::Serialize(e.to_string()))?;
+
+        tracing::debug!(
+            topic,
+            content_hash = %chunk.content_hash,
+            index = chunk.index,
+            total = chunk.total,
+            "Publishing blob chunk"
+        );
+
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, false, payload)
+            .await
+            .map_err(|e| ReplicationError::Publish(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Publish a document's conflict report, retained for late subscribers.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if publish fails.
+    pub async fn publish_conflicts(
+        &self,
+        doc_hash: &str,
+        report: &ConflictReport,
+    ) -> Result<(), ReplicationError> {
+        let topic = self.topic_scheme.conflicts(doc_hash);
+        let payload = report
+            .to_cbor()
+            .map_err(|e| ReplicationError::Serialize(e.to_string()))?;
+
+        tracing::debug!(
+            topic,
+            conflicts = report.conflicts.len(),
+            "Publishing conflict report"
+        );
+
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, true, payload)
+            .await
+            .map_err(|e| ReplicationError::Publish(e.to_string()))?;
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
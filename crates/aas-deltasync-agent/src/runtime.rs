@@ -1,43 +1,120 @@
 //! Agent runtime orchestration.
 
+use crate::blobs::{BlobFetches, BlobStore};
 use crate::config::{AgentConfig, SubscriptionConfig};
+use crate::drift::DriftTracker;
+use crate::echo::EchoFilter;
+use crate::peers::PeerTable;
 use crate::persistence::SqliteStore;
 use crate::replication::ReplicationManager;
-use aas_deltasync_adapter_aas::{AasClient, AasClientConfig};
-use aas_deltasync_adapter_basyx::{BasyxEvent, BasyxSubscriber, BasyxSubscriberConfig, EventType};
-use aas_deltasync_core::{Delta, Hlc, OrMap, Timestamp};
-use aas_deltasync_proto::topics::MessageType;
-use aas_deltasync_proto::{AntiEntropyRequest, AntiEntropyResponse, DocDelta, TopicScheme};
+use crate::skew::ClockOffenders;
+use aas_deltasync_adapter_aas::{
+    element_from_value, element_types, flatten_value, split_idshort_path, typed_element_from_value,
+    unflatten_value, AasClient, AasClientConfig, ClientError,
+};
+use aas_deltasync_adapter_basyx::{
+    BasyxEvent, BasyxSubscriber, BasyxSubscriberConfig, EventScope, EventType, RepositoryEvent,
+    ShellEvent,
+};
+use aas_deltasync_adapter_faaast::{diff_leaves, FaaastPoller, FaaastPollerConfig};
+use aas_deltasync_core::{
+    indexed_writes, same_value, BlobRef, ClockError, Conflict, CrdtDocument, Delta, DocId,
+    ElementType, ElementTypes, Hlc, IndexedWrites, ListIds, MergeStrategy, OrMap, PathKey,
+    RemoveSemantics, Timestamp, VersionVector, View,
+};
+use aas_deltasync_proto::topics::{self, MessageType};
+use aas_deltasync_proto::{
+    AgentHello, AntiEntropyRequest, AntiEntropyResponse, BlobChunk, BlobRequest, ConflictReport,
+    DocDelta, TopicScheme,
+};
 use anyhow::{Context, Result};
-use std::collections::HashMap;
-use std::time::Duration;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
-#[derive(Debug)]
-struct DocumentState {
-    state: OrMap<String, serde_json::Value>,
-    clock: Hlc,
-}
-
-impl DocumentState {
-    fn new(actor_id: Uuid) -> Self {
-        Self {
-            state: OrMap::new(),
-            clock: Hlc::new(actor_id),
+/// Capabilities advertised in agent hellos.
+const CAPABILITIES: &[&str] = &["SubmodelRepositoryServiceSpecification"];
+
+/// Rebuild a document from its latest snapshot and the delta log.
+///
+/// Only logged deltas the snapshot's version vector does not cover are
+/// replayed on top of it. Logged state was accepted when it arrived, so
+/// the clock is restored without a maximum offset; callers set it
+/// afterwards.
+pub(crate) fn restore_document(
+    store: &SqliteStore,
+    doc_id: &DocId,
+    actor_id: Uuid,
+    semantics: RemoveSemantics,
+) -> Result<CrdtDocument> {
+    let mut doc = CrdtDocument::with_semantics(doc_id.clone(), Hlc::new(actor_id), semantics);
+    let mut snapshot_version = VersionVector::new();
+
+    if let Some((snapshot_bytes, clock_bytes)) = store
+        .get_snapshot(doc_id)
+        .context("Failed to load document snapshot")?
+    {
+        match ciborium::from_reader::<OrMap<String, serde_json::Value>, _>(
+            snapshot_bytes.as_slice(),
+        ) {
+            Ok(state) => {
+                let version = VersionVector::from_cbor(&clock_bytes).unwrap_or_else(|err| {
+                    tracing::warn!(error = %err, %doc_id, "Failed to decode snapshot clock");
+                    VersionVector::new()
+                });
+                match doc.merge_snapshot(&state, &version) {
+                    Ok(()) => snapshot_version = version,
+                    Err(err) => {
+                        tracing::warn!(error = %err, %doc_id, "Failed to merge snapshot, ignoring");
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, %doc_id, "Failed to decode snapshot, ignoring");
+            }
         }
     }
 
-    fn apply_delta(&mut self, delta: &Delta<String, serde_json::Value>) {
-        for (_, _, timestamp) in &delta.inserts {
-            self.clock.update(*timestamp);
-        }
-        for (_, timestamp) in &delta.removes {
-            self.clock.update(*timestamp);
-        }
+    let delta_rows = store
+        .get_deltas_beyond(doc_id, &snapshot_version)
+        .context("Failed to load delta log")?;
 
-        delta.apply_to(&mut self.state);
+    let mut replayed = 0;
+    for (_, bytes) in &delta_rows {
+        match ciborium::from_reader::<Delta<String, serde_json::Value>, _>(bytes.as_slice()) {
+            Ok(delta) if covers(&snapshot_version, &delta) => {}
+            Ok(delta) => match doc.apply_delta(&delta) {
+                Ok(()) => replayed += 1,
+                Err(err) => {
+                    tracing::warn!(error = %err, %doc_id, "Skipping rejected logged delta");
+                }
+            },
+            Err(err) => {
+                tracing::warn!(error = %err, %doc_id, "Skipping undecodable logged delta");
+            }
+        }
     }
+
+    // Writes dropped by the replay were logged when first applied
+    doc.take_overwrites();
+
+    tracing::info!(
+        %doc_id,
+        entries = doc.len(),
+        replayed,
+        "Restored document state"
+    );
+
+    Ok(doc)
+}
+
+/// Build an anti-entropy request summarizing what we have of a document.
+fn ae_request(doc: &CrdtDocument) -> Result<AntiEntropyRequest> {
+    AntiEntropyRequest::with_version_vector(doc.id.clone(), &doc.version)
+        .context("Failed to encode version vector")
 }
 
 /// The main agent runtime.
@@ -45,6 +122,7 @@ pub struct Agent {
     config: AgentConfig,
     clock: Hlc,
     store: Option<SqliteStore>,
+    blobs: BlobStore,
 }
 
 impl Agent {
@@ -62,11 +140,13 @@ impl Agent {
         } else {
             None
         };
+        let blobs = BlobStore::new(&config.persistence.blob_dir);
 
         Ok(Self {
             config,
             clock,
             store,
+            blobs,
         })
     }
 
@@ -82,30 +162,43 @@ impl Agent {
         let topic_scheme = TopicScheme::new(&self.config.replication.tenant);
         let actor_id = self.clock.actor_id();
 
-        let mut subscriptions = HashMap::<String, SubscriptionConfig>::new();
-        let mut documents = HashMap::<String, DocumentState>::new();
+        let mut subscriptions = HashMap::<DocId, SubscriptionConfig>::new();
+        let mut documents = HashMap::<DocId, CrdtDocument>::new();
 
+        let semantics = self.config.replication.remove_semantics;
         for sub in &self.config.subscriptions {
-            let doc_id = format!("{}:{}", sub.aas_id, sub.submodel_id);
-            subscriptions.insert(doc_id.clone(), sub.clone());
-            documents
-                .entry(doc_id)
-                .or_insert_with(|| DocumentState::new(actor_id));
-        }
+            let doc_id = sub.doc_id();
 
-        let aas_client = if self.config.replication.enable_egress {
-            let config = AasClientConfig {
-                base_url: self.config.adapter.sm_repo_url.clone(),
-                timeout: Duration::from_secs(30),
-                bearer_token: self.config.adapter.bearer_token.clone(),
-                ca_cert_path: self.config.adapter.aas_ca_path.clone(),
-                client_cert_path: self.config.adapter.aas_client_cert_path.clone(),
-                client_key_path: self.config.adapter.aas_client_key_path.clone(),
+            let mut doc = match self.store.as_ref() {
+                Some(store) => restore_document(store, &doc_id, actor_id, semantics)?,
+                None => CrdtDocument::with_semantics(doc_id.clone(), Hlc::new(actor_id), semantics),
             };
-            Some(AasClient::new(config).context("Failed to create AAS client")?)
-        } else {
-            None
-        };
+            doc.multi_value_paths
+                .clone_from(&self.config.replication.multi_value_paths);
+            doc.counter_paths
+                .clone_from(&self.config.replication.counter_paths);
+            doc.counter_semantic_ids
+                .clone_from(&self.config.replication.counter_semantic_ids);
+            doc.clock
+                .set_max_offset(self.config.replication.max_clock_offset);
+            documents.entry(doc_id.clone()).or_insert(doc);
+            subscriptions.insert(doc_id, sub.clone());
+        }
+
+        let server_client = AasClient::new(AasClientConfig {
+            base_url: self.config.adapter.sm_repo_url.clone(),
+            timeout: Duration::from_secs(30),
+            bearer_token: self.config.adapter.bearer_token.clone(),
+            ca_cert_path: self.config.adapter.aas_ca_path.clone(),
+            client_cert_path: self.config.adapter.aas_client_cert_path.clone(),
+            client_key_path: self.config.adapter.aas_client_key_path.clone(),
+        })
+        .context("Failed to create AAS client")?;
+        let aas_client = self
+            .config
+            .replication
+            .enable_egress
+            .then(|| server_client.clone());
 
         // Initialize replication
         let (replication, mut eventloop) = ReplicationManager::new(
@@ -116,22 +209,16 @@ impl Agent {
         )
         .context("Failed to create replication manager")?;
 
-        // Subscribe to document topics
-        for sub in &self.config.subscriptions {
-            let doc_id = format!("{}:{}", sub.aas_id, sub.submodel_id);
-            let doc_hash = hash_doc_id(&doc_id);
-            replication.subscribe(&doc_hash).await?;
-        }
-
         // Initialize BaSyx subscriber if adapter type is basyx
-        let basyx_rx: Option<mpsc::Receiver<Result<BasyxEvent, _>>> =
+        let basyx_rx: Option<mpsc::Receiver<Result<RepositoryEvent, _>>> =
             if self.config.adapter.adapter_type == "basyx" {
                 if let Some(mqtt_broker) = &self.config.adapter.mqtt_broker {
                     let basyx_config = BasyxSubscriberConfig {
                         mqtt_broker: mqtt_broker.clone(),
                         mqtt_ca_path: self.config.adapter.mqtt_ca_path.clone(),
                         client_id: format!("aas-deltasync-basyx-{actor_id}"),
-                        repo_id: "sm-repo".to_string(),
+                        repo_ids: self.config.adapter.repo_ids.clone(),
+                        aas_repo_ids: self.config.adapter.aas_repo_ids.clone(),
                         keep_alive: Duration::from_secs(30),
                     };
 
@@ -142,7 +229,11 @@ impl Agent {
                         .await
                         .context("Failed to subscribe to BaSyx events")?;
 
-                    tracing::info!("BaSyx event ingestion enabled");
+                    tracing::info!(
+                        repo_ids = ?self.config.adapter.repo_ids,
+                        aas_repo_ids = ?self.config.adapter.aas_repo_ids,
+                        "BaSyx event ingestion enabled"
+                    );
                     Some(subscriber.start())
                 } else {
                     tracing::warn!("BaSyx adapter selected but no MQTT broker configured");
@@ -155,6 +246,98 @@ impl Agent {
         // Wrap in Option for the select! loop
         let mut basyx_rx = basyx_rx;
 
+        // Adapters only report changes, so seed `$value` documents from the
+        // server, learning the element types egress needs along the way
+        let mut element_types = HashMap::<DocId, ElementTypes>::new();
+        let value_docs: Vec<DocId> = subscriptions
+            .keys()
+            .filter(|doc_id| doc_id.view == View::Value)
+            .cloned()
+            .collect();
+        let (bootstrap_tx, mut bootstrap_rx) = mpsc::channel(value_docs.len().max(1));
+        tokio::spawn(fetch_server_submodels(
+            server_client.clone(),
+            self.blobs.clone(),
+            value_docs.clone(),
+            bootstrap_tx,
+        ));
+
+        // Initialize FA³ST poller if adapter type is faaast
+        let mut faaast_rx = if self.config.adapter.adapter_type == "faaast" {
+            let poller_config = FaaastPollerConfig {
+                base_url: self.config.adapter.sm_repo_url.clone(),
+                poll_interval: self.config.adapter.poll_interval,
+                ca_cert_path: self.config.adapter.aas_ca_path.clone(),
+                client_cert_path: self.config.adapter.aas_client_cert_path.clone(),
+                client_key_path: self.config.adapter.aas_client_key_path.clone(),
+                bearer_token: self.config.adapter.bearer_token.clone(),
+            };
+
+            let poller =
+                FaaastPoller::new(poller_config).context("Failed to create FA³ST poller")?;
+            // Polled values only feed `$value` documents
+            let mut submodel_ids: Vec<String> = subscriptions
+                .keys()
+                .filter(|doc_id| doc_id.view == View::Value)
+                .map(|doc_id| doc_id.submodel_id.clone())
+                .collect();
+            submodel_ids.sort();
+            submodel_ids.dedup();
+
+            tracing::info!(
+                poll_interval = ?self.config.adapter.poll_interval,
+                "FA³ST polling enabled"
+            );
+            Some(poller.start(submodel_ids, Hlc::new(actor_id)))
+        } else {
+            None
+        };
+
+        // Persistence loop: snapshot documents and compact the delta log
+        let compaction_interval = self.config.persistence.compaction_interval;
+        let mut compaction_timer = tokio::time::interval_at(
+            tokio::time::Instant::now() + compaction_interval,
+            compaction_interval,
+        );
+        compaction_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        // Anti-entropy loop: periodically ask peers for missing deltas
+        let ae_interval = self.config.replication.anti_entropy_interval;
+        let mut ae_timer =
+            tokio::time::interval_at(tokio::time::Instant::now() + ae_interval, ae_interval);
+        ae_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        // Membership loop: announce ourselves and expire silent peers
+        let hello_interval = self.config.replication.hello_interval;
+        let peer_ttl = self.config.replication.peer_ttl;
+        let mut peers = PeerTable::new(peer_ttl);
+        let mut hello_timer =
+            tokio::time::interval_at(tokio::time::Instant::now() + hello_interval, hello_interval);
+        hello_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        // Egressed writes whose adapter echoes must not be re-ingested
+        let mut echoes = EchoFilter::new(self.config.replication.echo_window);
+
+        // Reconciliation loop: read the server back and resolve drift
+        let reconcile_interval = self.config.replication.reconcile_interval;
+        let mut reconcile_timer = tokio::time::interval_at(
+            tokio::time::Instant::now() + reconcile_interval,
+            reconcile_interval,
+        );
+        reconcile_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let (reconcile_tx, mut reconcile_rx) = mpsc::channel(subscriptions.len().max(1));
+        let mut drift = DriftTracker::new();
+
+        // Attachment content referenced by documents but not stored locally
+        let mut fetches = BlobFetches::new(ae_interval);
+
+        // Conflicts last published, and versions last checked, per document
+        let mut reported_conflicts = HashMap::<DocId, Vec<Conflict>>::new();
+        let mut checked_versions = HashMap::<DocId, VersionVector>::new();
+
+        // Peers whose deltas were rejected for clocks running too far ahead
+        let mut offenders = ClockOffenders::new();
+
         tracing::info!("Agent running, press Ctrl+C to stop");
 
         // Main event loop
@@ -181,14 +364,22 @@ impl Agent {
                                         actor_id,
                                         &mut documents,
                                         &subscriptions,
+                                        &element_types,
                                         aas_client.as_ref(),
+                                        &self.blobs,
+                                        &mut echoes,
+                                        &mut offenders,
                                         self.store.as_ref(),
                                     ).await;
+                                    let docs = documents.values().filter(|doc| topics::doc_hash(&doc.id) == doc_hash);
+                                    let requests = blob_requests(docs, &self.blobs, &mut fetches);
+                                    tokio::spawn(publish_blob_requests(replication.clone(), requests));
                                 }
                                 MessageType::AntiEntropyRequest => {
                                     handle_ae_request(
                                         &publish.payload,
                                         &doc_hash,
+                                        semantics,
                                         &replication,
                                         self.store.as_ref(),
                                     ).await;
@@ -196,19 +387,56 @@ impl Agent {
                                 MessageType::AntiEntropyResponse => {
                                     handle_ae_response(
                                         &publish.payload,
-                                        actor_id,
                                         &mut documents,
+                                        &mut offenders,
                                         self.store.as_ref(),
                                     );
+                                    let docs = documents.values().filter(|doc| topics::doc_hash(&doc.id) == doc_hash);
+                                    let requests = blob_requests(docs, &self.blobs, &mut fetches);
+                                    tokio::spawn(publish_blob_requests(replication.clone(), requests));
+                                }
+                                MessageType::BlobRequest => {
+                                    handle_blob_request(&publish.payload, &doc_hash, &self.blobs, &replication);
+                                }
+                                MessageType::BlobChunk => {
+                                    let Some(hash) = handle_blob_chunk(&publish.payload, &self.blobs, &mut fetches) else {
+                                        continue;
+                                    };
+                                    if let Some(client) = aas_client.as_ref() {
+                                        egress_fetched_blob(
+                                            &hash,
+                                            &documents,
+                                            &subscriptions,
+                                            &element_types,
+                                            client,
+                                            &self.blobs,
+                                            &mut echoes,
+                                        ).await;
+                                    }
                                 }
+                                // Reports are for operators; every agent derives its own
+                                MessageType::Conflicts => {}
                                 MessageType::Hello => {
-                                    tracing::debug!(
-                                        doc_hash = %doc_hash,
-                                        "Received agent hello (peer discovery not yet implemented)"
+                                    handle_hello(
+                                        &publish.payload,
+                                        &doc_hash,
+                                        actor_id,
+                                        semantics,
+                                        &subscriptions,
+                                        &mut peers,
+                                        self.store.as_ref(),
                                     );
                                 }
                             }
                         }
+                        Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                            tracing::info!("Connected to replication broker");
+                            // Subscriptions do not survive a clean-session reconnect, and
+                            // anything published while we were away must be caught up
+                            let hellos = hellos(&documents, &subscriptions, actor_id);
+                            let requests = ae_requests(&documents, &subscriptions);
+                            tokio::spawn(rejoin(replication.clone(), hellos, requests));
+                        }
                         Ok(_) => {}
                         Err(e) => {
                             tracing::error!(error = %e, "MQTT error");
@@ -225,29 +453,130 @@ impl Agent {
                     }
                 } => {
                     match event_result {
-                        Ok(basyx_event) => {
+                        Ok(RepositoryEvent::Submodel(basyx_event)) => {
                             handle_basyx_event(
                                 &basyx_event,
-                                actor_id,
                                 &mut documents,
                                 &subscriptions,
+                                &mut element_types,
+                                &server_client,
+                                &self.blobs,
+                                &mut echoes,
                                 &replication,
-                                &topic_scheme,
                                 self.store.as_ref(),
                             ).await;
                         }
+                        Ok(RepositoryEvent::Shell(shell_event)) => {
+                            handle_shell_event(&shell_event, &subscriptions);
+                        }
                         Err(e) => {
                             tracing::warn!(error = %e, "Failed to parse BaSyx event");
                         }
                     }
                 }
 
+                // Seed documents with the server state fetched at startup
+                Some(submodel) = bootstrap_rx.recv() => {
+                    let types = element_types.entry(submodel.doc_id.clone()).or_default();
+                    types.extend(&submodel.types);
+                    if let Some(doc) = documents.get_mut(&submodel.doc_id) {
+                        doc.select_counters(types);
+                        bootstrap_document(doc, &submodel.leaves, &replication, self.store.as_ref()).await;
+                    }
+                }
+
+                // Resolve drift against the server state fetched for reconciliation
+                Some(submodel) = reconcile_rx.recv() => {
+                    reconcile_document(
+                        &submodel,
+                        &mut documents,
+                        &subscriptions,
+                        &mut element_types,
+                        &mut drift,
+                        aas_client.as_ref(),
+                        &self.blobs,
+                        &mut echoes,
+                        &replication,
+                        self.store.as_ref(),
+                    ).await;
+                }
+
+                // Handle FA³ST poll deltas
+                Some((submodel_id, delta)) = async {
+                    match faaast_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    handle_faaast_delta(
+                        &submodel_id,
+                        &delta,
+                        &mut documents,
+                        &subscriptions,
+                        &element_types,
+                        &server_client,
+                        &self.blobs,
+                        &mut echoes,
+                        &replication,
+                        self.store.as_ref(),
+                    ).await;
+                }
+
+                // Periodic hellos and peer expiry
+                _ = hello_timer.tick() => {
+                    let hellos = hellos(&documents, &subscriptions, actor_id);
+                    tokio::spawn(publish_hellos(replication.clone(), hellos));
+                    expire_peers(&mut peers, self.store.as_ref(), peer_ttl);
+                    report_clock_offenders(&mut offenders);
+                }
+
+                // Periodic server reconciliation
+                _ = reconcile_timer.tick() => {
+                    tokio::spawn(fetch_server_submodels(
+                        server_client.clone(),
+                        self.blobs.clone(),
+                        value_docs.clone(),
+                        reconcile_tx.clone(),
+                    ));
+                }
+
+                // Periodic anti-entropy requests
+                _ = ae_timer.tick() => {
+                    let requests = ae_requests(&documents, &subscriptions);
+                    tokio::spawn(publish_ae_requests(replication.clone(), requests));
+                    let requests = blob_requests(documents.values(), &self.blobs, &mut fetches);
+                    tokio::spawn(publish_blob_requests(replication.clone(), requests));
+                }
+
+                // Periodic snapshot and delta-log compaction
+                _ = compaction_timer.tick() => {
+                    if let Some(store) = self.store.as_ref() {
+                        run_persistence_cycle(store, &mut documents, actor_id);
+                        prune_conflict_log(store, self.config.persistence.conflict_log_retention);
+                    }
+                }
+
                 // Handle shutdown
                 _ = tokio::signal::ctrl_c() => {
                     tracing::info!("Shutdown signal received");
+                    if let Some(store) = self.store.as_ref() {
+                        run_persistence_cycle(store, &mut documents, actor_id);
+                    }
                     break;
                 }
             }
+
+            let changed = changed_documents(&documents, &mut checked_versions);
+            if !changed.is_empty() {
+                report_conflicts(
+                    &documents,
+                    &changed,
+                    &mut reported_conflicts,
+                    &replication,
+                    actor_id,
+                );
+                log_overwrites(&mut documents, &changed, self.store.as_ref());
+            }
         }
 
         tracing::info!("Agent stopped");
@@ -255,302 +584,1738 @@ impl Agent {
     }
 }
 
-fn persist_delta(store: Option<&SqliteStore>, delta: &DocDelta, timestamp: Timestamp) {
-    if let Some(store) = store {
-        if let Err(err) = store.save_delta(
-            &delta.doc_id,
-            &delta.delta_id,
-            &delta.delta_payload,
-            &timestamp.actor_id.to_string(),
-            timestamp.physical_ms,
-        ) {
-            tracing::warn!(error = %err, doc_id = %delta.doc_id, "Failed to persist delta");
-        }
+/// Log a delta, indexed by its latest timestamp.
+///
+/// Deltas without timestamps fall back to the envelope's; they hold nothing
+/// to replay, so whether they are skipped does not matter.
+fn persist_delta(
+    store: Option<&SqliteStore>,
+    doc_delta: &DocDelta,
+    delta: &Delta<String, serde_json::Value>,
+) {
+    let Some(store) = store else {
+        return;
+    };
+    let Some(latest) = delta
+        .timestamps()
+        .max()
+        .or_else(|| doc_delta.timestamp().ok())
+    else {
+        return;
+    };
+    let single_author = delta
+        .timestamps()
+        .all(|timestamp| timestamp.actor_id == latest.actor_id);
+    if let Err(err) = store.save_delta(
+        &doc_delta.doc_id,
+        &doc_delta.delta_id,
+        &doc_delta.delta_payload,
+        latest,
+        single_author,
+    ) {
+        tracing::warn!(error = %err, doc_id = %doc_delta.doc_id, "Failed to persist delta");
     }
 }
 
-fn update_peer_progress(store: Option<&SqliteStore>, delta: &DocDelta, timestamp: Timestamp) {
-    if let Some(store) = store {
-        if let Err(err) = store.update_peer_progress(
-            &timestamp.actor_id.to_string(),
-            &delta.doc_id,
-            &delta.delta_id,
-        ) {
-            tracing::warn!(error = %err, doc_id = %delta.doc_id, "Failed to update peer progress");
-        }
-    }
+/// Build anti-entropy requests for every subscribed document.
+fn ae_requests(
+    documents: &HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+) -> Vec<(String, AntiEntropyRequest)> {
+    subscriptions
+        .keys()
+        .filter_map(|doc_id| {
+            let doc = documents.get(doc_id)?;
+            match ae_request(doc) {
+                Ok(request) => Some((topics::doc_hash(doc_id), request)),
+                Err(err) => {
+                    tracing::warn!(error = %err, %doc_id, "Failed to build AE request");
+                    None
+                }
+            }
+        })
+        .collect()
 }
 
-async fn apply_delta_egress(
-    client: &AasClient,
-    sub: &SubscriptionConfig,
-    delta: &Delta<String, serde_json::Value>,
+/// Publish anti-entropy requests.
+///
+/// Runs as its own task so a large batch cannot block the loop that drives
+/// the MQTT event loop.
+async fn publish_ae_requests(
+    replication: ReplicationManager,
+    requests: Vec<(String, AntiEntropyRequest)>,
 ) {
-    for (path, value, _) in &delta.inserts {
-        if let Err(err) = client
-            .patch_submodel_element_value(&sub.submodel_id, path, value)
-            .await
-        {
-            tracing::warn!(
-                error = %err,
-                submodel_id = %sub.submodel_id,
-                path,
-                "Failed to apply delta insert via egress"
-            );
+    for (doc_hash, request) in &requests {
+        if let Err(err) = replication.publish_ae_request(doc_hash, request).await {
+            tracing::warn!(error = %err, doc_id = %request.doc_id, "Failed to publish AE request");
         }
     }
+    tracing::debug!(count = requests.len(), "Sent anti-entropy requests");
+}
 
-    for (path, _) in &delta.removes {
-        tracing::debug!(
-            submodel_id = %sub.submodel_id,
-            path,
-            "Skipping remove in egress (no delete API wired)"
-        );
-    }
+/// Build agent hellos for every subscribed document.
+fn hellos(
+    documents: &HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    actor_id: Uuid,
+) -> Vec<(String, AgentHello)> {
+    let capabilities: Vec<String> = CAPABILITIES.iter().map(ToString::to_string).collect();
+    subscriptions
+        .keys()
+        .filter_map(|doc_id| {
+            let doc = documents.get(doc_id)?;
+            match AgentHello::with_version_vector(actor_id, capabilities.clone(), &doc.version) {
+                Ok(mut hello) => {
+                    hello.remove_semantics = Some(doc.state.semantics());
+                    Some((topics::doc_hash(doc_id), hello))
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, %doc_id, "Failed to build agent hello");
+                    None
+                }
+            }
+        })
+        .collect()
 }
 
-/// Hash a document ID for topic sharding.
-fn hash_doc_id(doc_id: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+/// Publish agent hellos.
+async fn publish_hellos(replication: ReplicationManager, hellos: Vec<(String, AgentHello)>) {
+    for (doc_hash, hello) in &hellos {
+        if let Err(err) = replication.publish_hello(doc_hash, hello).await {
+            tracing::warn!(error = %err, doc_hash, "Failed to publish agent hello");
+        }
+    }
+    tracing::debug!(count = hellos.len(), "Sent agent hellos");
+}
 
-    let mut hasher = DefaultHasher::new();
-    doc_id.hash(&mut hasher);
-    format!("{:016x}", hasher.finish())
+/// Subscribe to document topics, announce ourselves, then catch up
+/// through anti-entropy.
+async fn rejoin(
+    replication: ReplicationManager,
+    hellos: Vec<(String, AgentHello)>,
+    requests: Vec<(String, AntiEntropyRequest)>,
+) {
+    for (doc_hash, _) in &requests {
+        if let Err(err) = replication.subscribe(doc_hash).await {
+            tracing::warn!(error = %err, doc_hash, "Failed to subscribe to document topic");
+        }
+    }
+    publish_hellos(replication.clone(), hellos).await;
+    publish_ae_requests(replication, requests).await;
 }
 
-/// Handle a Delta message from the replication stream.
-async fn handle_delta_message(
+/// Handle an agent hello received on a document topic.
+fn handle_hello(
     payload: &[u8],
     doc_hash: &str,
     actor_id: Uuid,
-    documents: &mut HashMap<String, DocumentState>,
-    subscriptions: &HashMap<String, SubscriptionConfig>,
-    aas_client: Option<&AasClient>,
+    semantics: RemoveSemantics,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    peers: &mut PeerTable,
     store: Option<&SqliteStore>,
 ) {
-    let doc_delta = match DocDelta::from_cbor(payload) {
-        Ok(delta) => delta,
+    let hello = match AgentHello::from_cbor(payload) {
+        Ok(hello) => hello,
         Err(err) => {
-            tracing::warn!(error = %err, "Failed to decode DocDelta");
+            tracing::warn!(error = %err, "Failed to decode AgentHello");
             return;
         }
     };
 
-    let expected_hash = hash_doc_id(&doc_delta.doc_id);
-    if doc_hash != expected_hash {
-        tracing::warn!(
-            doc_id = %doc_delta.doc_id,
-            topic_hash = %doc_hash,
-            expected_hash = %expected_hash,
-            "Doc hash mismatch for delta"
-        );
+    // The broker echoes our own hellos back to us
+    if hello.agent_id == actor_id {
+        return;
     }
 
-    let delta: Delta<String, serde_json::Value> =
-        match ciborium::from_reader(doc_delta.delta_payload.as_slice()) {
-            Ok(delta) => delta,
-            Err(err) => {
-                tracing::warn!(error = %err, "Failed to decode delta payload");
-                return;
-            }
-        };
-
-    let doc_state = documents
-        .entry(doc_delta.doc_id.clone())
-        .or_insert_with(|| DocumentState::new(actor_id));
-    doc_state.apply_delta(&delta);
-
-    if let Ok(timestamp) = doc_delta.timestamp() {
-        persist_delta(store, &doc_delta, timestamp);
-        update_peer_progress(store, &doc_delta, timestamp);
-    }
+    let Some(doc_id) = subscriptions
+        .keys()
+        .find(|doc_id| topics::doc_hash(doc_id) == doc_hash)
+    else {
+        tracing::debug!(doc_hash, "Ignoring hello for unsubscribed document");
+        return;
+    };
 
-    if let Some(aas_client) = aas_client {
-        if let Some(sub) = subscriptions.get(&doc_delta.doc_id) {
-            apply_delta_egress(aas_client, sub, &delta).await;
-        }
+    if let Some(theirs) = hello.remove_semantics.filter(|theirs| *theirs != semantics) {
+        tracing::warn!(
+            peer_id = %hello.agent_id,
+            %doc_id,
+            ours = ?semantics,
+            theirs = ?theirs,
+            "Peer uses other remove semantics, its state is refused"
+        );
     }
 
-    tracing::debug!(
-        doc_id = %doc_delta.doc_id,
-        inserts = delta.inserts.len(),
-        removes = delta.removes.len(),
-        "Applied delta from replication"
-    );
+    observe_peer(&hello, doc_id, peers, store, Instant::now());
 }
 
-/// Handle a `BaSyx` event by converting to delta and publishing.
-async fn handle_basyx_event(
-    event: &BasyxEvent,
-    actor_id: Uuid,
-    documents: &mut HashMap<String, DocumentState>,
-    subscriptions: &HashMap<String, SubscriptionConfig>,
-    replication: &ReplicationManager,
-    _topic_scheme: &TopicScheme,
+/// Track a peer and record the version vector it reported for a document.
+fn observe_peer(
+    hello: &AgentHello,
+    doc_id: &DocId,
+    peers: &mut PeerTable,
     store: Option<&SqliteStore>,
+    now: Instant,
 ) {
-    // Find matching subscription by submodel_id
-    let doc_id = subscriptions
-        .iter()
-        .find(|(_, sub)| sub.submodel_id == event.submodel_id)
-        .map(|(doc_id, _)| doc_id.clone());
-
-    let Some(doc_id) = doc_id else {
-        tracing::debug!(
-            submodel_id = %event.submodel_id,
-            "Ignoring BaSyx event for unsubscribed submodel"
+    if peers.observe(hello, doc_id, now) {
+        tracing::info!(
+            peer_id = %hello.agent_id,
+            version = %hello.version,
+            capabilities = ?hello.capabilities,
+            peers = peers.len(),
+            "Peer joined"
         );
+    }
+
+    let Some(store) = store else {
         return;
     };
 
-    // Get or create document state
-    let doc_state = documents
-        .entry(doc_id.clone())
-        .or_insert_with(|| DocumentState::new(actor_id));
-
-    // Convert BasyxEvent to Delta
-    let delta = basyx_event_to_delta(event, &mut doc_state.clock);
-
-    if delta.is_empty() {
-        return;
+    let peer_id = hello.agent_id.to_string();
+    if let Err(err) = store.record_peer(&peer_id, &hello.version, &hello.capabilities) {
+        tracing::warn!(error = %err, %peer_id, "Failed to record peer");
     }
 
-    // Apply delta locally
-    doc_state.apply_delta(&delta);
-
-    // Serialize delta payload
-    let mut delta_payload = Vec::new();
-    if let Err(err) = ciborium::into_writer(&delta, &mut delta_payload) {
-        tracing::warn!(error = %err, "Failed to serialize delta");
+    if let Err(err) = hello.version_vector() {
+        tracing::warn!(error = %err, %peer_id, "Unreadable hello clock summary");
         return;
     }
 
-    // Create and publish DocDelta
-    let timestamp = doc_state.clock.current();
-    let doc_delta = DocDelta::new(doc_id.clone(), timestamp, delta_payload);
-    let doc_hash = hash_doc_id(&doc_id);
-
-    if let Err(err) = replication.publish_delta(&doc_hash, &doc_delta).await {
-        tracing::warn!(error = %err, "Failed to publish delta from BaSyx event");
+    if let Err(err) = store.update_peer_progress(&peer_id, doc_id, &hello.clock_summary) {
+        tracing::warn!(error = %err, %doc_id, "Failed to update peer progress");
     }
-
-    // Persist delta
-    persist_delta(store, &doc_delta, timestamp);
-
-    tracing::debug!(
-        doc_id = %doc_id,
-        event_type = ?event.event_type,
-        inserts = delta.inserts.len(),
-        removes = delta.removes.len(),
-        "Processed BaSyx event"
-    );
 }
 
-/// Convert a `BaSyx` event to a CRDT delta.
-fn basyx_event_to_delta(event: &BasyxEvent, clock: &mut Hlc) -> Delta<String, serde_json::Value> {
-    let mut delta = Delta::new();
-
-    let Some(element) = &event.element else {
-        return delta;
-    };
+/// Oldest local timestamp a peer has not been shown to have seen.
+///
+/// For every actor we have deltas from, the peer's entry (or the zero
+/// timestamp if it has none) bounds what it has caught up on; the minimum
+/// over all actors is safe to use as its acknowledgement watermark.
+fn hello_ack(own: &VersionVector, peer: &VersionVector) -> Option<Timestamp> {
+    own.iter()
+        .map(|own_ts| {
+            peer.get(&own_ts.actor_id).unwrap_or(Timestamp {
+                physical_ms: 0,
+                logical: 0,
+                actor_id: own_ts.actor_id,
+            })
+        })
+        .min()
+}
 
-    // Convert idShortPath slashes to dots for CRDT key
-    let path = element.id_short_path.replace('/', ".");
+/// Drop peers that stopped announcing themselves.
+///
+/// Their progress is kept, so a peer that is only offline still holds back
+/// compaction and receives every remove once it is back. Peers that left
+/// the mesh for good are evicted explicitly.
+fn expire_peers(peers: &mut PeerTable, store: Option<&SqliteStore>, ttl: Duration) {
+    for (peer_id, peer) in peers.expire(Instant::now()) {
+        tracing::info!(
+            %peer_id,
+            documents = ?peer.documents,
+            peers = peers.len(),
+            "Peer expired"
+        );
+    }
 
-    match event.event_type {
-        EventType::Created | EventType::Updated => {
-            if let Some(value) = &element.value {
-                delta.add_insert(path, value.clone(), clock.tick());
-            }
-        }
-        EventType::Deleted => {
-            delta.add_remove(path, clock.tick());
-        }
-        EventType::Patched => {
-            // For patched events, the payload may contain multiple changes
-            // For now, treat as a single update if value is present
-            if let Some(value) = &element.value {
-                delta.add_insert(path, value.clone(), clock.tick());
+    if let Some(store) = store {
+        let cutoff = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_sub(ttl)
+            .as_secs();
+        match store.expire_peers(cutoff) {
+            Ok(expired) if !expired.is_empty() => {
+                tracing::debug!(?expired, "Removed stale peers");
             }
+            Ok(_) => {}
+            Err(err) => tracing::warn!(error = %err, "Failed to expire peers"),
         }
     }
+}
 
-    delta
+/// Drop conflict log entries older than `retention`.
+fn prune_conflict_log(store: &SqliteStore, retention: Duration) {
+    let cutoff = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .saturating_sub(retention)
+        .as_secs();
+    match store.prune_conflict_log(cutoff) {
+        Ok(0) => {}
+        Ok(pruned) => tracing::debug!(pruned, "Pruned conflict log"),
+        Err(err) => tracing::warn!(error = %err, "Failed to prune conflict log"),
+    }
 }
 
-/// Handle an anti-entropy request by querying persistence and responding.
-async fn handle_ae_request(
-    payload: &[u8],
-    doc_hash: &str,
-    replication: &ReplicationManager,
-    store: Option<&SqliteStore>,
+/// Snapshot every document and compact its delta log.
+fn run_persistence_cycle(
+    store: &SqliteStore,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    actor_id: Uuid,
 ) {
-    let request = match AntiEntropyRequest::from_cbor(payload) {
-        Ok(req) => req,
-        Err(err) => {
-            tracing::warn!(error = %err, "Failed to decode AntiEntropyRequest");
-            return;
+    for (doc_id, doc) in documents {
+        match compact_document(store, doc, actor_id) {
+            Ok(deleted) => {
+                tracing::debug!(%doc_id, deleted, "Snapshotted document");
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, %doc_id, "Failed to snapshot document");
+            }
         }
-    };
+    }
+}
 
-    tracing::debug!(
-        doc_id = %request.doc_id,
-        "Processing anti-entropy request"
-    );
+/// Save a snapshot of a document and drop logged deltas it supersedes.
+///
+/// Only deltas older than both the snapshot and the acknowledgement
+/// watermark of every known peer are dropped, so anti-entropy can still
+/// serve them to lagging peers. Tombstones and removed dots below the
+/// same bound are compacted first: every peer already holds the writes
+/// they hide. Returns the number of deleted deltas.
+fn compact_document(store: &SqliteStore, doc: &mut CrdtDocument, actor_id: Uuid) -> Result<usize> {
+    let snapshot_clock = doc.clock.current();
+    let acked_ms = peer_ack_watermark(store, doc, actor_id)?;
+    if let Some(acked_ms) = acked_ms {
+        doc.state.compact_tombstones(Timestamp {
+            physical_ms: acked_ms.min(snapshot_clock.physical_ms),
+            logical: 0,
+            actor_id: Uuid::nil(),
+        });
+    }
 
-    let Some(store) = store else {
-        tracing::debug!("No persistence store, cannot respond to AE request");
-        return;
+    let doc_id = &doc.id;
+    let mut snapshot_bytes = Vec::new();
+    ciborium::into_writer(&doc.state, &mut snapshot_bytes)
+        .context("Failed to serialize snapshot")?;
+    let version_bytes = doc
+        .version
+        .to_cbor()
+        .context("Failed to serialize snapshot version vector")?;
+    store
+        .save_snapshot(doc_id, &snapshot_bytes, &version_bytes)
+        .context("Failed to save snapshot")?;
+
+    let Some(acked_ms) = acked_ms else {
+        tracing::debug!(%doc_id, "No peer acknowledgements yet, keeping delta log");
+        return Ok(0);
     };
 
-    // Parse the have_summary as a timestamp threshold
-    let after_ts: u64 = if request.have_summary.len() >= 8 {
-        u64::from_be_bytes(request.have_summary[..8].try_into().unwrap_or([0u8; 8]))
-    } else {
-        0
+    let before_ts = acked_ms.min(snapshot_clock.physical_ms);
+    store
+        .compact_deltas_before(doc_id, before_ts)
+        .context("Failed to compact delta log")
+}
+
+/// Lowest physical time acknowledged by every known peer of a document.
+///
+/// Each peer's acknowledgement is derived from the version vector it last
+/// reported, against the document's current version, so deltas of actors
+/// a peer has not heard from are never treated as seen. Returns `None` if
+/// no peer has reported a version vector yet.
+fn peer_ack_watermark(
+    store: &SqliteStore,
+    doc: &CrdtDocument,
+    actor_id: Uuid,
+) -> Result<Option<u64>> {
+    let own_id = actor_id.to_string();
+    let watermark = store
+        .get_peer_progress(&doc.id)
+        .context("Failed to load peer progress")?
+        .into_iter()
+        .filter(|(peer_id, _)| *peer_id != own_id)
+        .map(|(_, version)| {
+            // Unreadable progress must not allow compaction
+            VersionVector::from_cbor(&version)
+                .ok()
+                .and_then(|version| hello_ack(&doc.version, &version))
+                .map_or(0, |ack| ack.physical_ms)
+        })
+        .min();
+
+    Ok(watermark)
+}
+
+/// Write index-addressed changes through to the AAS server.
+///
+/// `view` is the document's [`CrdtDocument::index_view`] after the changes.
+/// Fields of per-field elements cannot be written one by one, so each such
+/// element is reassembled from `view` and written whole, as are lists whose
+/// entries moved. Elements overlapping a `held` path, one of the document's
+/// [`CrdtDocument::conflicts`], are left alone until it is resolved.
+#[allow(clippy::too_many_arguments)]
+async fn apply_delta_egress(
+    client: &AasClient,
+    blobs: &BlobStore,
+    sub: &SubscriptionConfig,
+    writes: &IndexedWrites,
+    view: &BTreeMap<String, serde_json::Value>,
+    types: &ElementTypes,
+    held: &BTreeSet<String>,
+    echoes: &mut EchoFilter,
+) {
+    let delta = &writes.delta;
+    let mut per_field = BTreeSet::new();
+    let is_held = |path: &str| {
+        let path = path.to_string();
+        let overlaps = held.iter().any(|conflict| {
+            *conflict == path || conflict.is_descendant_of(&path) || path.is_descendant_of(conflict)
+        });
+        if overlaps {
+            tracing::debug!(
+                submodel_id = %sub.submodel_id,
+                path,
+                "Unresolved conflict, holding back egress"
+            );
+        }
+        overlaps
+    };
+
+    for (path, value, _) in &delta.inserts {
+        if let Some((owner, _)) = types.field_owner(path) {
+            per_field.insert(owner);
+            continue;
+        }
+        if is_held(path) {
+            continue;
+        }
+
+        echoes.record_write(&sub.submodel_id, path, value, Instant::now());
+        let result = match BlobRef::from_value(value) {
+            Some(blob) => {
+                egress_content(
+                    client,
+                    blobs,
+                    &sub.submodel_id,
+                    path,
+                    &blob,
+                    types.get(path),
+                )
+                .await
+            }
+            None => upsert_element(client, &sub.submodel_id, path, value, types.get(path)).await,
+        };
+        if let Err(err) = result {
+            tracing::warn!(
+                error = %err,
+                submodel_id = %sub.submodel_id,
+                path,
+                "Failed to apply delta insert via egress"
+            );
+        }
+    }
+
+    for (path, _) in &delta.removes {
+        if is_held(path) {
+            continue;
+        }
+        echoes.record_remove(&sub.submodel_id, path, Instant::now());
+        if let Some((owner, _)) = types.field_owner(path) {
+            per_field.insert(owner);
+            continue;
+        }
+
+        match client.delete_submodel_element(&sub.submodel_id, path).await {
+            Ok(()) => {}
+            Err(err) if err.is_not_found() => {
+                tracing::debug!(
+                    submodel_id = %sub.submodel_id,
+                    path,
+                    "Element already absent, nothing to delete"
+                );
+            }
+            Err(err) => {
+                tracing::warn!(
+                    error = %err,
+                    submodel_id = %sub.submodel_id,
+                    path,
+                    "Failed to apply delta remove via egress"
+                );
+            }
+        }
+    }
+
+    for owner in per_field.into_iter().filter(|owner| !is_held(owner)) {
+        egress_per_field_element(client, &sub.submodel_id, owner, view, types, echoes).await;
+    }
+
+    for list in writes.lists.iter().filter(|list| !is_held(list)) {
+        egress_list(client, &sub.submodel_id, list, view, types, echoes).await;
+    }
+}
+
+/// Index paths of a document's unresolved conflicts.
+fn conflicted_paths(doc: &CrdtDocument) -> BTreeSet<String> {
+    doc.conflicts()
+        .into_iter()
+        .map(|conflict| conflict.path)
+        .collect()
+}
+
+/// Documents whose version moved since they were last checked.
+///
+/// Every change to a document's state advances its version, so documents
+/// whose version stood still need no new conflict report or log entries.
+fn changed_documents(
+    documents: &HashMap<DocId, CrdtDocument>,
+    checked: &mut HashMap<DocId, VersionVector>,
+) -> Vec<DocId> {
+    let changed: Vec<DocId> = documents
+        .values()
+        .filter(|doc| checked.get(&doc.id) != Some(&doc.version))
+        .map(|doc| doc.id.clone())
+        .collect();
+    for doc_id in &changed {
+        checked.insert(doc_id.clone(), documents[doc_id].version.clone());
+    }
+    changed
+}
+
+/// Record the writes the changed documents dropped in the conflict log.
+fn log_overwrites(
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    changed: &[DocId],
+    store: Option<&SqliteStore>,
+) {
+    for doc_id in changed {
+        let Some(doc) = documents.get_mut(doc_id) else {
+            continue;
+        };
+        let overwrites = doc.take_overwrites();
+        if overwrites.is_empty() {
+            continue;
+        }
+        for overwrite in &overwrites {
+            tracing::debug!(
+                doc_id = %doc.id,
+                path = %overwrite.key,
+                winner = ?overwrite.winner.timestamp,
+                loser = ?overwrite.loser.timestamp,
+                "Write overwritten"
+            );
+        }
+        if let Some(store) = store {
+            if let Err(err) = store.save_overwrites(&doc.id, &overwrites) {
+                tracing::warn!(error = %err, doc_id = %doc.id, "Failed to record overwritten writes");
+            }
+        }
+    }
+}
+
+/// Count a delta rejected for a timestamp too far ahead.
+///
+/// Only an actor's first rejection is logged as a warning; the rest show
+/// up in [`report_clock_offenders`].
+fn record_rejection(offenders: &mut ClockOffenders, doc_id: &DocId, err: &ClockError) {
+    let offender = offenders.record(err);
+    if offender.rejected == 1 {
+        tracing::warn!(error = %err, %doc_id, "Rejected delta from a clock too far ahead");
+    } else {
+        tracing::debug!(error = %err, %doc_id, "Rejected delta from a clock too far ahead");
+    }
+}
+
+/// Log the peers whose deltas were rejected since the last report.
+fn report_clock_offenders(offenders: &mut ClockOffenders) {
+    for (actor_id, offender) in offenders.report() {
+        tracing::warn!(
+            %actor_id,
+            rejected = offender.rejected,
+            ahead_ms = offender.ahead_ms,
+            "Peer clock ahead of the maximum offset, its deltas are rejected"
+        );
+    }
+}
+
+/// Publish the conflicts of every changed document whose set changed
+/// since the last report.
+fn report_conflicts(
+    documents: &HashMap<DocId, CrdtDocument>,
+    changed: &[DocId],
+    reported: &mut HashMap<DocId, Vec<Conflict>>,
+    replication: &ReplicationManager,
+    actor_id: Uuid,
+) {
+    for doc in changed.iter().filter_map(|doc_id| documents.get(doc_id)) {
+        if doc.multi_value_paths.is_empty() {
+            continue;
+        }
+        let conflicts = doc.conflicts();
+        let previous = reported.get(&doc.id).map_or(&[][..], Vec::as_slice);
+        if conflicts == previous {
+            continue;
+        }
+
+        for conflict in conflicts
+            .iter()
+            .filter(|c| !previous.iter().any(|p| p.path == c.path))
+        {
+            tracing::warn!(
+                doc_id = %doc.id,
+                path = %conflict.path,
+                values = conflict.values.len(),
+                "Concurrent writes conflict, egress held until resolved"
+            );
+        }
+        for conflict in previous
+            .iter()
+            .filter(|p| !conflicts.iter().any(|c| c.path == p.path))
+        {
+            tracing::info!(doc_id = %doc.id, path = %conflict.path, "Conflict resolved");
+        }
+
+        reported.insert(doc.id.clone(), conflicts.clone());
+        let report = ConflictReport::new(doc.id.clone(), actor_id, conflicts);
+        let replication = replication.clone();
+        tokio::spawn(async move {
+            let doc_hash = topics::doc_hash(&report.doc_id);
+            if let Err(err) = replication.publish_conflicts(&doc_hash, &report).await {
+                tracing::warn!(error = %err, doc_id = %report.doc_id, "Failed to publish conflict report");
+            }
+        });
+    }
+}
+
+/// Write the content behind a [`BlobRef`] to the AAS server.
+///
+/// `File` content is uploaded to the element's attachment endpoint; `Blob`
+/// elements have none, so their content is written inline as base64.
+/// Content not yet fetched from peers is skipped and written once it
+/// arrives.
+async fn egress_content(
+    client: &AasClient,
+    blobs: &BlobStore,
+    submodel_id: &str,
+    path: &str,
+    blob: &BlobRef,
+    element_type: Option<ElementType>,
+) -> Result<(), ClientError> {
+    let content = match blobs.get(&blob.content_hash) {
+        Ok(Some(content)) => content,
+        Ok(None) => {
+            tracing::debug!(
+                submodel_id,
+                path,
+                content_hash = %blob.content_hash,
+                "Content not fetched yet, deferring egress"
+            );
+            return Ok(());
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, content_hash = %blob.content_hash, "Failed to read blob");
+            return Ok(());
+        }
+    };
+
+    if element_type != Some(ElementType::File) {
+        let value = serde_json::json!({
+            "contentType": blob.content_type,
+            "value": BASE64.encode(&content),
+        });
+        return upsert_element(client, submodel_id, path, &value, Some(ElementType::Blob)).await;
+    }
+
+    let file_name = split_idshort_path(path).1.trim_matches(['[', ']']);
+    let upload = client
+        .put_attachment(submodel_id, path, file_name, &blob.content_type, &content)
+        .await;
+    match upload {
+        Err(err) if err.is_not_found() => {
+            let value = serde_json::json!({"contentType": blob.content_type, "value": ""});
+            create_element(client, submodel_id, path, &value, element_type).await?;
+            client
+                .put_attachment(submodel_id, path, file_name, &blob.content_type, &content)
+                .await
+        }
+        result => result,
+    }
+}
+
+/// Write content that arrived from a peer wherever a document references it.
+async fn egress_fetched_blob(
+    hash: &str,
+    documents: &HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    element_types: &HashMap<DocId, ElementTypes>,
+    client: &AasClient,
+    blobs: &BlobStore,
+    echoes: &mut EchoFilter,
+) {
+    for (doc_id, sub) in subscriptions
+        .iter()
+        .filter(|(_, sub)| sub.view == View::Value)
+    {
+        let Some(doc) = documents.get(doc_id) else {
+            continue;
+        };
+        let types = element_types.get(doc_id).cloned().unwrap_or_default();
+        let held = conflicted_paths(doc);
+        for (path, value) in doc.index_view() {
+            if held.contains(&path) {
+                continue;
+            }
+            let Some(blob) = BlobRef::from_value(&value).filter(|blob| blob.content_hash == hash)
+            else {
+                continue;
+            };
+            echoes.record_write(&sub.submodel_id, &path, &value, Instant::now());
+            let result = egress_content(
+                client,
+                blobs,
+                &sub.submodel_id,
+                &path,
+                &blob,
+                types.get(&path),
+            )
+            .await;
+            if let Err(err) = result {
+                tracing::warn!(
+                    error = %err,
+                    submodel_id = %sub.submodel_id,
+                    path,
+                    "Failed to write fetched content via egress"
+                );
+            }
+        }
+    }
+}
+
+/// Move the content of `File` and `Blob` values into the blob store.
+///
+/// Each such value is replaced by its [`BlobRef`]. `Blob` content is inline
+/// in the value, `File` content is downloaded from the element's
+/// attachment. Values whose content cannot be read are kept inline.
+async fn store_contents<'a>(
+    client: &AasClient,
+    blobs: &BlobStore,
+    submodel_id: &str,
+    types: &ElementTypes,
+    values: impl Iterator<Item = (&'a String, &'a mut serde_json::Value)>,
+) {
+    for (path, value) in values {
+        let element_type = types.get(path);
+        if !matches!(element_type, Some(ElementType::Blob | ElementType::File))
+            || BlobRef::from_value(value).is_some()
+        {
+            continue;
+        }
+
+        let content = if element_type == Some(ElementType::Blob) {
+            value
+                .get("value")
+                .and_then(serde_json::Value::as_str)
+                .and_then(|encoded| BASE64.decode(encoded).ok())
+        } else {
+            match client.get_attachment(submodel_id, path).await {
+                Ok((content, _)) => Some(content),
+                Err(err) => {
+                    tracing::debug!(
+                        error = %err,
+                        submodel_id,
+                        path,
+                        "No attachment, replicating File value inline"
+                    );
+                    None
+                }
+            }
+        };
+        let Some(content) = content else {
+            continue;
+        };
+
+        let content_type = value
+            .get("contentType")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default();
+        match blobs.put(&content) {
+            Ok(content_hash) => {
+                let blob = BlobRef {
+                    content_type: content_type.to_string(),
+                    content_hash,
+                };
+                *value = blob.to_value();
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, submodel_id, path, "Failed to store content");
+            }
+        }
+    }
+}
+
+/// Requests for content the given documents reference but the blob store
+/// lacks.
+fn blob_requests<'a>(
+    documents: impl Iterator<Item = &'a CrdtDocument>,
+    blobs: &BlobStore,
+    fetches: &mut BlobFetches,
+) -> Vec<(String, BlobRequest)> {
+    let now = Instant::now();
+    let mut requests = Vec::new();
+    for doc in documents {
+        for (_, value) in doc.state.iter() {
+            let Some(blob) = BlobRef::from_value(value) else {
+                continue;
+            };
+            if !blobs.contains(&blob.content_hash) && fetches.request(&blob.content_hash, now) {
+                let request = BlobRequest::new(doc.id.clone(), blob.content_hash);
+                requests.push((topics::doc_hash(&doc.id), request));
+            }
+        }
+    }
+    requests
+}
+
+async fn publish_blob_requests(
+    replication: ReplicationManager,
+    requests: Vec<(String, BlobRequest)>,
+) {
+    for (doc_hash, request) in requests {
+        if let Err(err) = replication.publish_blob_request(&doc_hash, &request).await {
+            tracing::warn!(error = %err, doc_id = %request.doc_id, "Failed to publish blob request");
+        }
+    }
+}
+
+/// Answer a blob request with the content's chunks, if it is stored here.
+///
+/// Every agent holding the content answers; the requester ignores chunks
+/// once its copy is complete.
+fn handle_blob_request(
+    payload: &[u8],
+    doc_hash: &str,
+    blobs: &BlobStore,
+    replication: &ReplicationManager,
+) {
+    let request = match BlobRequest::from_cbor(payload) {
+        Ok(request) => request,
+        Err(err) => {
+            tracing::warn!(error = %err, "Failed to decode BlobRequest");
+            return;
+        }
+    };
+
+    let content = match blobs.get(&request.content_hash) {
+        Ok(Some(content)) => content,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(error = %err, content_hash = %request.content_hash, "Failed to read blob");
+            return;
+        }
+    };
+
+    let chunks = BlobChunk::split(&request.content_hash, &content);
+    let replication = replication.clone();
+    let doc_hash = doc_hash.to_string();
+    tokio::spawn(async move {
+        for chunk in chunks {
+            if let Err(err) = replication.publish_blob_chunk(&doc_hash, &chunk).await {
+                tracing::warn!(error = %err, content_hash = %chunk.content_hash, "Failed to publish blob chunk");
+                return;
+            }
+        }
+    });
+}
+
+/// Collect a chunk of requested content, storing the content once complete.
+///
+/// Returns the hash of content that was just completed.
+fn handle_blob_chunk(
+    payload: &[u8],
+    blobs: &BlobStore,
+    fetches: &mut BlobFetches,
+) -> Option<String> {
+    let chunk = match BlobChunk::from_cbor(payload) {
+        Ok(chunk) => chunk,
+        Err(err) => {
+            tracing::warn!(error = %err, "Failed to decode BlobChunk");
+            return None;
+        }
+    };
+
+    let content = fetches.receive(&chunk)?;
+    match blobs.put(&content) {
+        Ok(hash) => {
+            tracing::debug!(content_hash = %hash, len = content.len(), "Fetched blob from peers");
+            Some(hash)
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, content_hash = %chunk.content_hash, "Failed to store blob");
+            None
+        }
+    }
+}
+
+/// Write a list whole from its entries in `view`.
+///
+/// Lists are only addressable by index, so inserting, removing or
+/// reordering entries rewrites the list.
+async fn egress_list(
+    client: &AasClient,
+    submodel_id: &str,
+    list: &str,
+    view: &BTreeMap<String, serde_json::Value>,
+    types: &ElementTypes,
+    echoes: &mut EchoFilter,
+) {
+    let entries = format!("{list}[");
+    for (path, value) in view
+        .range(entries.clone()..)
+        .take_while(|(path, _)| path.starts_with(&entries))
+    {
+        echoes.record_write(submodel_id, path, value, Instant::now());
+    }
+
+    let value = unflatten_value(list, view, types).unwrap_or_else(|| serde_json::json!([]));
+    if let Err(err) = upsert_element(client, submodel_id, list, &value, types.get(list)).await {
+        tracing::warn!(
+            error = %err,
+            submodel_id,
+            path = list,
+            "Failed to write list via egress"
+        );
+    }
+}
+
+/// Write a per-field element whole from its fields in `view`.
+///
+/// An element with no fields left is deleted.
+async fn egress_per_field_element(
+    client: &AasClient,
+    submodel_id: &str,
+    owner: &str,
+    view: &BTreeMap<String, serde_json::Value>,
+    types: &ElementTypes,
+    echoes: &mut EchoFilter,
+) {
+    let fields = element_fields(view, types, owner);
+
+    let result = if fields.is_empty() {
+        match client.delete_submodel_element(submodel_id, owner).await {
+            Err(err) if err.is_not_found() => Ok(()),
+            result => result,
+        }
+    } else {
+        for (field, value) in &fields {
+            echoes.record_write(
+                submodel_id,
+                &format!("{owner}.{field}"),
+                value,
+                Instant::now(),
+            );
+        }
+        let value = types.join(owner, fields);
+        upsert_element(client, submodel_id, owner, &value, types.get(owner)).await
+    };
+
+    if let Err(err) = result {
+        tracing::warn!(
+            error = %err,
+            submodel_id,
+            path = owner,
+            "Failed to write per-field element via egress"
+        );
+    }
+}
+
+/// Live fields of the per-field element at `owner`, sorted by name.
+fn element_fields<'a>(
+    view: &'a BTreeMap<String, serde_json::Value>,
+    types: &ElementTypes,
+    owner: &str,
+) -> Vec<(&'a str, &'a serde_json::Value)> {
+    let mut fields: Vec<_> = view
+        .iter()
+        .filter_map(|(path, value)| match types.field_owner(path) {
+            Some((field_owner, field)) if field_owner == owner => Some((field, value)),
+            _ => None,
+        })
+        .collect();
+    fields.sort_by_key(|(field, _)| *field);
+    fields
+}
+
+/// Write an element value, creating the element if it does not exist yet.
+async fn upsert_element(
+    client: &AasClient,
+    submodel_id: &str,
+    path: &str,
+    value: &serde_json::Value,
+    element_type: Option<ElementType>,
+) -> Result<(), ClientError> {
+    match client
+        .patch_submodel_element_value(submodel_id, path, value)
+        .await
+    {
+        Err(err) if err.is_not_found() => {
+            create_element(client, submodel_id, path, value, element_type).await
+        }
+        result => result,
+    }
+}
+
+/// Create an element, along with any missing ancestor collections.
+///
+/// Ancestors are created as empty `SubmodelElementCollection`s. List
+/// entries are never created implicitly, since the list's element type is
+/// unknown.
+async fn create_element(
+    client: &AasClient,
+    submodel_id: &str,
+    path: &str,
+    value: &serde_json::Value,
+    element_type: Option<ElementType>,
+) -> Result<(), ClientError> {
+    let mut pending = vec![(path, value.clone(), element_type)];
+
+    while let Some((path, value, element_type)) = pending.last().cloned() {
+        let (parent, segment) = split_idshort_path(path);
+        let id_short = (!segment.starts_with('[')).then_some(segment);
+        let element = match element_type {
+            Some(element_type) => typed_element_from_value(element_type, id_short, &value),
+            None => element_from_value(id_short, &value),
+        };
+
+        match client
+            .post_submodel_element(submodel_id, parent, &element)
+            .await
+        {
+            Ok(()) => {
+                tracing::debug!(submodel_id, path, "Created element via egress");
+                pending.pop();
+            }
+            Err(err) if err.is_not_found() => match parent {
+                Some(parent) if !split_idshort_path(parent).1.starts_with('[') => {
+                    pending.push((parent, serde_json::json!({}), None));
+                }
+                _ => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a Delta message from the replication stream.
+#[allow(clippy::too_many_arguments)]
+async fn handle_delta_message(
+    payload: &[u8],
+    doc_hash: &str,
+    actor_id: Uuid,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    element_types: &HashMap<DocId, ElementTypes>,
+    aas_client: Option<&AasClient>,
+    blobs: &BlobStore,
+    echoes: &mut EchoFilter,
+    offenders: &mut ClockOffenders,
+    store: Option<&SqliteStore>,
+) {
+    let doc_delta = match DocDelta::from_cbor(payload) {
+        Ok(delta) => delta,
+        Err(err) => {
+            tracing::warn!(error = %err, "Failed to decode DocDelta");
+            return;
+        }
+    };
+
+    let expected_hash = topics::doc_hash(&doc_delta.doc_id);
+    if doc_hash != expected_hash {
+        tracing::warn!(
+            doc_id = %doc_delta.doc_id,
+            topic_hash = %doc_hash,
+            expected_hash = %expected_hash,
+            "Doc hash mismatch for delta"
+        );
+    }
+
+    let delta: Delta<String, serde_json::Value> =
+        match ciborium::from_reader(doc_delta.delta_payload.as_slice()) {
+            Ok(delta) => delta,
+            Err(err) => {
+                tracing::warn!(error = %err, "Failed to decode delta payload");
+                return;
+            }
+        };
+
+    // MQTT 3.1.1 has no no-local option, so the broker hands our own deltas back
+    if is_own_delta(&delta, actor_id) {
+        tracing::trace!(doc_id = %doc_delta.doc_id, "Ignoring our own delta");
+        return;
+    }
+
+    let Some(doc) = documents.get_mut(&doc_delta.doc_id) else {
+        tracing::debug!(doc_id = %doc_delta.doc_id, "Ignoring delta for unsubscribed document");
+        return;
+    };
+    if !accepts_semantics(doc, doc_delta.remove_semantics) {
+        return;
+    }
+    if let Err(err) = doc.apply_delta(&delta) {
+        record_rejection(offenders, &doc_delta.doc_id, &err);
+        return;
+    }
+
+    persist_delta(store, &doc_delta, &delta);
+
+    // Egress writes through the `$value` API, so other views stay replicated only
+    if let Some(aas_client) = aas_client {
+        if let Some(sub) = subscriptions
+            .get(&doc_delta.doc_id)
+            .filter(|sub| sub.view == View::Value)
+        {
+            let types = element_types
+                .get(&doc_delta.doc_id)
+                .cloned()
+                .unwrap_or_default();
+            let writes = indexed_writes(&doc.state, &delta);
+            let view = doc.index_view();
+            let held = conflicted_paths(doc);
+            apply_delta_egress(
+                aas_client, blobs, sub, &writes, &view, &types, &held, echoes,
+            )
+            .await;
+        }
+    }
+
+    tracing::debug!(
+        doc_id = %doc_delta.doc_id,
+        inserts = delta.inserts.len(),
+        removes = delta.removes.len(),
+        "Applied delta from replication"
+    );
+}
+
+/// Check whether every timestamp in a delta was issued by this agent.
+///
+/// Such deltas are echoes of our own publishes. Anti-entropy still accepts
+/// them, so an agent that lost its database can recover its own writes.
+fn is_own_delta(delta: &Delta<String, serde_json::Value>, actor_id: Uuid) -> bool {
+    let mut timestamps = delta.timestamps().peekable();
+    timestamps.peek().is_some() && timestamps.all(|timestamp| timestamp.actor_id == actor_id)
+}
+
+/// Find the subscribed `$value` document for a submodel.
+///
+/// Adapters report element values, so they only feed the value view. If
+/// the adapter knows which repository the submodel lives in, subscriptions
+/// pinned to another repository do not match.
+fn find_subscribed_doc(
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    repo_id: Option<&str>,
+    submodel_id: &str,
+) -> Option<DocId> {
+    subscriptions
+        .iter()
+        .find(|(doc_id, sub)| {
+            doc_id.submodel_id == submodel_id
+                && doc_id.view == View::Value
+                && match (repo_id, sub.repo_id.as_deref()) {
+                    (Some(repo_id), Some(sub_repo_id)) => repo_id == sub_repo_id,
+                    _ => true,
+                }
+        })
+        .map(|(doc_id, _)| doc_id.clone())
+}
+
+/// Apply a locally originated delta, then publish and persist it.
+async fn commit_local_delta(
+    doc: &mut CrdtDocument,
+    delta: &Delta<String, serde_json::Value>,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    // Apply delta locally, recording the inserts it supersedes
+    let mut delta = delta.clone();
+    doc.observe(&mut delta);
+    if let Err(err) = doc.apply_delta(&delta) {
+        tracing::warn!(error = %err, doc_id = %doc.id, "Failed to apply local delta");
+        return;
+    }
+
+    // Serialize delta payload
+    let mut delta_payload = Vec::new();
+    if let Err(err) = ciborium::into_writer(&delta, &mut delta_payload) {
+        tracing::warn!(error = %err, "Failed to serialize delta");
+        return;
+    }
+
+    // Create and publish DocDelta
+    let timestamp = doc.clock.current();
+    let mut doc_delta = DocDelta::new(doc.id.clone(), timestamp, delta_payload);
+    doc_delta.remove_semantics = Some(doc.state.semantics());
+    let doc_hash = topics::doc_hash(&doc.id);
+
+    if let Err(err) = replication.publish_delta(&doc_hash, &doc_delta).await {
+        tracing::warn!(error = %err, doc_id = %doc.id, "Failed to publish local delta");
+    }
+
+    // Persist delta
+    persist_delta(store, &doc_delta, &delta);
+}
+
+/// A document's submodel as read back from the server.
+struct ServerSubmodel {
+    doc_id: DocId,
+    types: ElementTypes,
+    /// Flattened `$value`, with attachment content moved to the blob store
+    leaves: BTreeMap<String, serde_json::Value>,
+}
+
+/// Fetch the element types and `$value` of each document's submodel.
+///
+/// Documents whose fetch fails are skipped until the next attempt; they
+/// still fill in from change events and peers.
+async fn fetch_server_submodels(
+    client: AasClient,
+    blobs: BlobStore,
+    doc_ids: Vec<DocId>,
+    tx: mpsc::Sender<ServerSubmodel>,
+) {
+    for doc_id in doc_ids {
+        let fetched = match client.get_submodel(&doc_id.submodel_id).await {
+            Ok(submodel) => client
+                .get_submodel_value(&doc_id.submodel_id)
+                .await
+                .map(|value| (element_types(&submodel), value)),
+            Err(err) => Err(err),
+        };
+
+        match fetched {
+            Ok((types, value)) => {
+                let mut leaves = flatten_value(&value, &types);
+                let submodel_id = &doc_id.submodel_id;
+                store_contents(&client, &blobs, submodel_id, &types, leaves.iter_mut()).await;
+                let submodel = ServerSubmodel {
+                    doc_id,
+                    types,
+                    leaves,
+                };
+                if tx.send(submodel).await.is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, doc_id = %doc_id, "Failed to fetch submodel");
+            }
+        }
+    }
+}
+
+/// Seed a document with the submodel value read from the server.
+///
+/// Only paths the document has never seen are committed, so state that
+/// already arrived from peers or storage is kept. List entries past the
+/// end of a document's list are new. Server values that disagree with the
+/// document are reported.
+async fn bootstrap_document(
+    doc: &mut CrdtDocument,
+    leaves: &BTreeMap<String, serde_json::Value>,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    let mut target = doc.index_view();
+    let diverging: Vec<&String> = leaves
+        .iter()
+        .filter(|(path, leaf)| {
+            target
+                .get(*path)
+                .is_some_and(|current| !same_value(current, leaf))
+        })
+        .map(|(path, _)| path)
+        .collect();
+
+    if !diverging.is_empty() {
+        tracing::warn!(
+            doc_id = %doc.id,
+            paths = ?diverging,
+            "Server values diverge from replicated state"
+        );
+    }
+
+    let ids = ListIds::from_state(&doc.state);
+    let mut seeded = 0;
+    for (path, leaf) in leaves {
+        let seen = ids
+            .stable_path(path)
+            .is_some_and(|stable| doc.state.has_seen(&stable));
+        if !seen && !target.contains_key(path) {
+            target.insert(path.clone(), leaf.clone());
+            seeded += 1;
+        }
+    }
+
+    let delta = doc.reconcile_indexed(&target);
+    if !delta.is_empty() {
+        commit_local_delta(doc, &delta, replication, store).await;
+    }
+
+    tracing::info!(
+        doc_id = %doc.id,
+        seeded,
+        existing = leaves.len() - seeded,
+        "Bootstrapped document from server"
+    );
+}
+
+/// Reconcile a document with the submodel value read from the server.
+///
+/// Server edits the document missed are committed as local deltas, and
+/// document state the server lacks is written back when egress is
+/// enabled.
+#[allow(clippy::too_many_arguments)]
+async fn reconcile_document(
+    submodel: &ServerSubmodel,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    element_types: &mut HashMap<DocId, ElementTypes>,
+    drift: &mut DriftTracker,
+    aas_client: Option<&AasClient>,
+    blobs: &BlobStore,
+    echoes: &mut EchoFilter,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    let doc_id = &submodel.doc_id;
+    let (Some(doc), Some(sub)) = (documents.get_mut(doc_id), subscriptions.get(doc_id)) else {
+        return;
     };
 
-    // Query persistence for deltas after the requester's timestamp
-    let delta_bytes = match store.get_deltas_after(&request.doc_id, after_ts) {
-        Ok(deltas) => deltas,
+    let types = element_types.entry(doc_id.clone()).or_default();
+    types.extend(&submodel.types);
+    doc.select_counters(types);
+
+    let found = drift.reconcile(doc, submodel.leaves.clone());
+    if found.is_empty() {
+        tracing::debug!(doc_id = %doc_id, "No drift from server");
+        return;
+    }
+
+    tracing::warn!(
+        doc_id = %doc_id,
+        paths = ?found.paths().collect::<Vec<_>>(),
+        local_edits = found.local_edits.inserts.len() + found.local_edits.removes.len(),
+        repairs = found.repairs.inserts.len() + found.repairs.removes.len(),
+        "Drift between server and replicated state"
+    );
+
+    if !found.local_edits.is_empty() {
+        let delta = doc.stable_delta(&found.local_edits);
+        commit_local_delta(doc, &delta, replication, store).await;
+    }
+
+    if found.repairs.is_empty() {
+        return;
+    }
+    if let Some(client) = aas_client {
+        let writes = IndexedWrites {
+            delta: found.repairs,
+            lists: BTreeSet::new(),
+        };
+        let view = doc.index_view();
+        let held = conflicted_paths(doc);
+        apply_delta_egress(client, blobs, sub, &writes, &view, types, &held, echoes).await;
+    } else {
+        tracing::warn!(doc_id = %doc_id, "Egress disabled, server drift left unrepaired");
+    }
+}
+
+/// Handle a `BaSyx` shell event for the subscribed documents of its AAS.
+///
+/// Shells only reference submodels, so nothing is replicated; the event is
+/// reported, and a shell that stops referencing a subscribed submodel is
+/// flagged, since its documents keep replicating regardless.
+fn handle_shell_event(event: &ShellEvent, subscriptions: &HashMap<DocId, SubscriptionConfig>) {
+    let affected: Vec<&DocId> = subscriptions
+        .keys()
+        .filter(|doc_id| doc_id.aas_id == event.aas_id)
+        .collect();
+
+    if affected.is_empty() {
+        tracing::debug!(aas_id = %event.aas_id, "Ignoring shell event for unsubscribed AAS");
+        return;
+    }
+
+    let detached: Vec<&DocId> = match event.event_type {
+        EventType::Deleted => affected.clone(),
+        EventType::Created | EventType::Updated if !event.payload.is_null() => affected
+            .iter()
+            .copied()
+            .filter(|doc_id| !event.submodel_ids.contains(&doc_id.submodel_id))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    if detached.is_empty() {
+        tracing::info!(
+            repo_id = %event.repo_id,
+            aas_id = %event.aas_id,
+            event_type = ?event.event_type,
+            "Shell of subscribed documents changed"
+        );
+    } else {
+        tracing::warn!(
+            repo_id = %event.repo_id,
+            aas_id = %event.aas_id,
+            event_type = ?event.event_type,
+            documents = ?detached,
+            "Shell no longer references subscribed submodels"
+        );
+    }
+}
+
+/// Handle a `BaSyx` event by converting to delta and publishing.
+#[allow(clippy::too_many_arguments)]
+async fn handle_basyx_event(
+    event: &BasyxEvent,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    element_types: &mut HashMap<DocId, ElementTypes>,
+    client: &AasClient,
+    blobs: &BlobStore,
+    echoes: &mut EchoFilter,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    // Find matching subscription by repository and submodel_id
+    let Some(doc_id) = find_subscribed_doc(subscriptions, Some(&event.repo_id), &event.submodel_id)
+    else {
+        tracing::debug!(
+            repo_id = %event.repo_id,
+            submodel_id = %event.submodel_id,
+            "Ignoring BaSyx event for unsubscribed submodel"
+        );
+        return;
+    };
+
+    let Some(doc) = documents.get_mut(&doc_id) else {
+        return;
+    };
+
+    // Learn element types from the event before splitting its values
+    let types = element_types.entry(doc_id.clone()).or_default();
+    for element in &event.elements {
+        if let Some(element_type) = element.element_type {
+            types.insert(element.id_short_path.replace('/', "."), element_type);
+        }
+    }
+
+    // Convert BasyxEvent to Delta, dropping echoes of our own egress
+    let mut delta = match (event.scope, event.event_type) {
+        (EventScope::Submodel, EventType::Created | EventType::Updated | EventType::Deleted) => {
+            submodel_event_to_delta(event, types, doc)
+        }
+        _ => basyx_event_to_delta(event, types, doc),
+    };
+    let values = delta
+        .inserts
+        .iter_mut()
+        .map(|(path, value, _)| (&*path, value));
+    store_contents(client, blobs, &event.submodel_id, types, values).await;
+    let delta = echoes.filter(&event.submodel_id, &delta, Instant::now());
+
+    if delta.is_empty() {
+        return;
+    }
+
+    let delta = doc.stable_delta(&delta);
+    commit_local_delta(doc, &delta, replication, store).await;
+
+    tracing::debug!(
+        doc_id = %doc_id,
+        event_type = ?event.event_type,
+        inserts = delta.inserts.len(),
+        removes = delta.removes.len(),
+        "Processed BaSyx event"
+    );
+}
+
+/// Handle a delta detected by the FA³ST poller.
+///
+/// Changes egress itself made are dropped first. The poller addresses
+/// list entries by index and stamps deltas with its own clock, which never
+/// sees remote updates, so the delta is rebuilt against the document with
+/// [`CrdtDocument::stable_delta`], taking fresh ticks of the document
+/// clock. Otherwise a local edit could lose to an older remote write.
+#[allow(clippy::too_many_arguments)]
+async fn handle_faaast_delta(
+    submodel_id: &str,
+    polled: &Delta<String, serde_json::Value>,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    subscriptions: &HashMap<DocId, SubscriptionConfig>,
+    element_types: &HashMap<DocId, ElementTypes>,
+    client: &AasClient,
+    blobs: &BlobStore,
+    echoes: &mut EchoFilter,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    let Some(doc_id) = find_subscribed_doc(subscriptions, None, submodel_id) else {
+        tracing::debug!(
+            submodel_id,
+            "Ignoring FA³ST delta for unsubscribed submodel"
+        );
+        return;
+    };
+
+    let mut polled = polled.clone();
+    if let Some(types) = element_types.get(&doc_id) {
+        let values = polled
+            .inserts
+            .iter_mut()
+            .map(|(path, value, _)| (&*path, value));
+        store_contents(client, blobs, submodel_id, types, values).await;
+    }
+
+    let Some(doc) = documents.get_mut(&doc_id) else {
+        return;
+    };
+    let polled = echoes.filter(submodel_id, &polled, Instant::now());
+    let delta = doc.stable_delta(&polled);
+
+    if delta.is_empty() {
+        return;
+    }
+
+    commit_local_delta(doc, &delta, replication, store).await;
+
+    tracing::debug!(
+        doc_id = %doc_id,
+        inserts = delta.inserts.len(),
+        removes = delta.removes.len(),
+        "Processed FA³ST poll delta"
+    );
+}
+
+/// Convert a `BaSyx` event to an index-addressed CRDT delta.
+///
+/// Every changed element becomes one entry, or one per field for
+/// per-field elements, stamped with consecutive ticks of the document
+/// clock. The event carries the whole element, so only fields whose value
+/// changed are written, and fields it no longer has are removed.
+fn basyx_event_to_delta(
+    event: &BasyxEvent,
+    types: &ElementTypes,
+    doc: &mut CrdtDocument,
+) -> Delta<String, serde_json::Value> {
+    let view = doc.index_view();
+    let mut delta = Delta::new();
+
+    for element in &event.elements {
+        // Convert idShortPath slashes to dots for CRDT key
+        let path = element.id_short_path.replace('/', ".");
+        let entries = match (event.event_type, &element.value) {
+            (EventType::Deleted, _) => Vec::new(),
+            (_, Some(value)) => types.split(&path, value),
+            (_, None) => continue,
+        };
+
+        let stale: Vec<String> = element_fields(&view, types, &path)
+            .into_iter()
+            .map(|(field, _)| format!("{path}.{field}"))
+            .filter(|field_path| !entries.iter().any(|(entry, _)| entry == field_path))
+            .collect();
+
+        match event.event_type {
+            EventType::Created | EventType::Updated | EventType::Patched => {
+                let per_field = types
+                    .get(&path)
+                    .is_some_and(|ty| ty.merge_strategy() == MergeStrategy::PerFieldLww);
+                for (path, value) in entries {
+                    let unchanged = view
+                        .get(&path)
+                        .is_some_and(|current| same_value(current, &value));
+                    if !(per_field && unchanged) {
+                        delta.add_insert(path, value, doc.clock.tick());
+                    }
+                }
+            }
+            EventType::Deleted if stale.is_empty() => {
+                delta.add_remove(path, doc.clock.tick());
+            }
+            EventType::Deleted => {}
+        }
+        for field_path in stale {
+            delta.add_remove(field_path, doc.clock.tick());
+        }
+    }
+
+    delta
+}
+
+/// Convert a submodel-level `BaSyx` event to an index-addressed CRDT delta.
+///
+/// A created or replaced submodel is diffed path by path against the
+/// document's [`CrdtDocument::index_view`], so elements missing from the
+/// new submodel are removed. A deleted submodel removes every path of the
+/// document.
+fn submodel_event_to_delta(
+    event: &BasyxEvent,
+    types: &ElementTypes,
+    doc: &mut CrdtDocument,
+) -> Delta<String, serde_json::Value> {
+    let target: BTreeMap<String, serde_json::Value> = event
+        .elements
+        .iter()
+        .filter_map(|element| {
+            let value = element.value.as_ref()?;
+            Some(types.split(&element.id_short_path.replace('/', "."), value))
+        })
+        .flatten()
+        .collect();
+
+    diff_leaves(&doc.index_view(), &target, &mut doc.clock)
+}
+
+/// Handle an anti-entropy request by querying persistence and responding.
+async fn handle_ae_request(
+    payload: &[u8],
+    doc_hash: &str,
+    semantics: RemoveSemantics,
+    replication: &ReplicationManager,
+    store: Option<&SqliteStore>,
+) {
+    let request = match AntiEntropyRequest::from_cbor(payload) {
+        Ok(req) => req,
         Err(err) => {
-            tracing::warn!(error = %err, "Failed to query deltas for AE");
+            tracing::warn!(error = %err, "Failed to decode AntiEntropyRequest");
             return;
         }
     };
 
-    if delta_bytes.is_empty() {
-        tracing::debug!(doc_id = %request.doc_id, "No missing deltas to send");
+    tracing::debug!(
+        doc_id = %request.doc_id,
+        "Processing anti-entropy request"
+    );
+
+    let Some(store) = store else {
+        tracing::debug!("No persistence store, cannot respond to AE request");
         return;
-    }
+    };
 
-    // Convert raw bytes to DocDeltas
-    let mut deltas = Vec::new();
-    for bytes in delta_bytes {
-        if let Ok(doc_delta) = DocDelta::from_cbor(&bytes) {
-            deltas.push(doc_delta);
+    let response = match build_ae_response(&request, semantics, store) {
+        Ok(Some(response)) => response,
+        Ok(None) => {
+            tracing::debug!(doc_id = %request.doc_id, "No missing deltas to send");
+            return;
         }
-    }
-
-    let response = AntiEntropyResponse::with_deltas(request.doc_id.clone(), deltas.clone());
+        Err(err) => {
+            tracing::warn!(error = %err, "Failed to query deltas for AE");
+            return;
+        }
+    };
 
     if let Err(err) = replication.publish_ae_response(doc_hash, &response).await {
         tracing::warn!(error = %err, "Failed to publish AE response");
     } else {
         tracing::info!(
             doc_id = %request.doc_id,
-            deltas_count = deltas.len(),
+            deltas_count = response.deltas.len(),
+            has_snapshot = response.snapshot.is_some(),
             "Sent anti-entropy response"
         );
     }
 }
 
+/// Build the response to an anti-entropy request from persisted state.
+///
+/// Every logged delta holding a timestamp the requester's version vector
+/// does not cover is re-wrapped in a `DocDelta` envelope from its stored
+/// columns. If the requester has not seen everything in our latest snapshot,
+/// compaction may have dropped what it needs, so the snapshot is attached as
+/// well. Returns `None` if there is nothing to send.
+fn build_ae_response(
+    request: &AntiEntropyRequest,
+    semantics: RemoveSemantics,
+    store: &SqliteStore,
+) -> Result<Option<AntiEntropyResponse>> {
+    let have = request.version_vector().unwrap_or_else(|err| {
+        tracing::debug!(error = %err, doc_id = %request.doc_id, "Unreadable AE summary, sending everything");
+        VersionVector::new()
+    });
+
+    let mut deltas = Vec::new();
+    for (delta_id, delta_payload) in store
+        .get_deltas_beyond(&request.doc_id, &have)
+        .context("Failed to query delta log")?
+    {
+        let delta: Delta<String, serde_json::Value> = match ciborium::from_reader(
+            delta_payload.as_slice(),
+        ) {
+            Ok(delta) => delta,
+            Err(err) => {
+                tracing::warn!(error = %err, doc_id = %request.doc_id, "Skipping undecodable logged delta");
+                continue;
+            }
+        };
+
+        if covers(&have, &delta) {
+            continue;
+        }
+
+        deltas.push(DocDelta {
+            doc_id: request.doc_id.clone(),
+            delta_id,
+            delta_payload,
+            signature: None,
+            remove_semantics: Some(semantics),
+        });
+    }
+
+    let snapshot = store
+        .get_snapshot(&request.doc_id)
+        .context("Failed to load snapshot")?
+        .filter(|(_, version_bytes)| {
+            !VersionVector::from_cbor(version_bytes)
+                .is_ok_and(|snapshot_version| have.dominates(&snapshot_version))
+        });
+
+    if deltas.is_empty() && snapshot.is_none() {
+        return Ok(None);
+    }
+
+    let mut response = AntiEntropyResponse::with_deltas(request.doc_id.clone(), deltas);
+    if let Some((snapshot_bytes, version_bytes)) = snapshot {
+        response.snapshot = Some(snapshot_bytes);
+        response.snapshot_version = Some(version_bytes);
+    }
+    Ok(Some(response))
+}
+
+/// Check whether a version vector covers every timestamp of a delta.
+///
+/// The delta log only filters by each actor's contiguous entry, so deltas
+/// within ranges held past a gap are checked here.
+fn covers(version: &VersionVector, delta: &Delta<String, serde_json::Value>) -> bool {
+    delta
+        .timestamps()
+        .all(|timestamp| version.contains(&timestamp))
+}
+
+/// Check that replicated state was made under the document's removal
+/// semantics, as merging state of the other semantics diverges silently.
+///
+/// State from peers that do not announce their semantics is accepted.
+fn accepts_semantics(doc: &CrdtDocument, theirs: Option<RemoveSemantics>) -> bool {
+    let ours = doc.state.semantics();
+    match theirs {
+        Some(theirs) if theirs != ours => {
+            tracing::warn!(
+                doc_id = %doc.id,
+                ours = ?ours,
+                theirs = ?theirs,
+                "Refusing state replicated under other remove semantics"
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
 /// Handle an anti-entropy response by applying received deltas.
 fn handle_ae_response(
     payload: &[u8],
-    actor_id: Uuid,
-    documents: &mut HashMap<String, DocumentState>,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    offenders: &mut ClockOffenders,
     store: Option<&SqliteStore>,
 ) {
     let response = match AntiEntropyResponse::from_cbor(payload) {
@@ -561,6 +2326,16 @@ fn handle_ae_response(
         }
     };
 
+    apply_ae_response(&response, documents, offenders, store);
+}
+
+/// Apply the snapshot and deltas of an anti-entropy response.
+fn apply_ae_response(
+    response: &AntiEntropyResponse,
+    documents: &mut HashMap<DocId, CrdtDocument>,
+    offenders: &mut ClockOffenders,
+    store: Option<&SqliteStore>,
+) {
     tracing::debug!(
         doc_id = %response.doc_id,
         deltas_count = response.deltas.len(),
@@ -568,17 +2343,30 @@ fn handle_ae_response(
         "Processing anti-entropy response"
     );
 
-    let doc_state = documents
-        .entry(response.doc_id.clone())
-        .or_insert_with(|| DocumentState::new(actor_id));
+    let Some(doc) = documents.get_mut(&response.doc_id) else {
+        tracing::debug!(doc_id = %response.doc_id, "Ignoring AE response for unsubscribed document");
+        return;
+    };
 
-    // Apply snapshot if provided (takes precedence)
+    // Merge snapshot if provided; replacing local state would drop our own writes
     if let Some(snapshot_bytes) = &response.snapshot {
         if let Ok(state) =
             ciborium::from_reader::<OrMap<String, serde_json::Value>, _>(snapshot_bytes.as_slice())
         {
-            doc_state.state = state;
-            tracing::info!(doc_id = %response.doc_id, "Applied snapshot from AE response");
+            let version = response
+                .snapshot_version
+                .as_deref()
+                .and_then(|bytes| VersionVector::from_cbor(bytes).ok())
+                .unwrap_or_default();
+            if !accepts_semantics(doc, Some(state.semantics())) {
+                return;
+            }
+            match doc.merge_snapshot(&state, &version) {
+                Ok(()) => {
+                    tracing::info!(doc_id = %response.doc_id, "Merged snapshot from AE response");
+                }
+                Err(err) => record_rejection(offenders, &response.doc_id, &err),
+            }
         }
     }
 
@@ -594,14 +2382,17 @@ fn handle_ae_response(
                 }
             };
 
-        doc_state.apply_delta(&delta);
+        if !accepts_semantics(doc, doc_delta.remove_semantics) {
+            return;
+        }
+        if let Err(err) = doc.apply_delta(&delta) {
+            record_rejection(offenders, &response.doc_id, &err);
+            continue;
+        }
         applied_count += 1;
 
         // Persist the delta
-        if let Ok(timestamp) = doc_delta.timestamp() {
-            persist_delta(store, doc_delta, timestamp);
-            update_peer_progress(store, doc_delta, timestamp);
-        }
+        persist_delta(store, doc_delta, &delta);
     }
 
     tracing::info!(
@@ -610,3 +2401,1140 @@ fn handle_ae_response(
         "Anti-entropy sync complete"
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_timestamp(physical: u64, logical: u32, actor_num: u8) -> Timestamp {
+        Timestamp {
+            physical_ms: physical,
+            logical,
+            actor_id: Uuid::from_bytes([actor_num; 16]),
+        }
+    }
+
+    fn encode<T: serde::Serialize>(value: &T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn test_doc_id() -> DocId {
+        DocId::value_view("aas1", "sm1")
+    }
+
+    fn new_document(actor_id: Uuid) -> CrdtDocument {
+        CrdtDocument::new(test_doc_id(), Hlc::new(actor_id))
+    }
+
+    /// Get a document, creating an empty one on first use.
+    fn document_mut<'a>(
+        documents: &'a mut HashMap<DocId, CrdtDocument>,
+        doc_id: &DocId,
+        actor_id: Uuid,
+    ) -> &'a mut CrdtDocument {
+        documents
+            .entry(doc_id.clone())
+            .or_insert_with(|| CrdtDocument::new(doc_id.clone(), Hlc::new(actor_id)))
+    }
+
+    #[test]
+    fn restore_replays_deltas_over_snapshot() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc_id = &test_doc_id();
+
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 2);
+
+        let mut snapshot: OrMap<String, serde_json::Value> = OrMap::new();
+        snapshot.insert("Temperature".to_string(), serde_json::json!(20), t1);
+        let mut version = VersionVector::new();
+        version.observe(t1);
+        store
+            .save_snapshot(doc_id, &encode(&snapshot), &version.to_cbor().unwrap())
+            .unwrap();
+
+        let mut delta = Delta::new();
+        delta.add_insert("Temperature".to_string(), serde_json::json!(25), t2);
+        delta.add_insert("Status".to_string(), serde_json::json!("Running"), t2);
+        store
+            .save_delta(doc_id, &t2.to_bytes(), &encode(&delta), t2, true)
+            .unwrap();
+
+        // A delta the snapshot covers is not replayed; had it been folded
+        // into the snapshot, replaying it would change nothing
+        let t0 = make_timestamp(500, 0, 1);
+        let mut covered = Delta::new();
+        covered.add_insert("Pressure".to_string(), serde_json::json!(1), t0);
+        store
+            .save_delta(doc_id, &t0.to_bytes(), &encode(&covered), t0, true)
+            .unwrap();
+
+        let doc =
+            restore_document(&store, doc_id, Uuid::new_v4(), RemoveSemantics::default()).unwrap();
+
+        assert_eq!(doc.get("Temperature"), Some(&serde_json::json!(25)));
+        assert_eq!(doc.get("Status"), Some(&serde_json::json!("Running")));
+        assert_eq!(doc.get("Pressure"), None);
+        assert!(doc.clock.current() > t2);
+    }
+
+    #[test]
+    fn compaction_keeps_deltas_not_acknowledged_by_all_peers() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc_id = &test_doc_id();
+        let actor_id = Uuid::from_bytes([9; 16]);
+
+        let mut doc = new_document(actor_id);
+        for physical in [1000, 2000, 3000] {
+            let timestamp = make_timestamp(physical, 0, 1);
+            let mut delta = Delta::new();
+            delta.add_insert("X".to_string(), serde_json::json!(physical), timestamp);
+            doc.apply_delta(&delta).unwrap();
+            store
+                .save_delta(
+                    doc_id,
+                    &timestamp.to_bytes(),
+                    &encode(&delta),
+                    timestamp,
+                    true,
+                )
+                .unwrap();
+        }
+
+        // Without any peer acknowledgement nothing is dropped
+        assert_eq!(compact_document(&store, &mut doc, actor_id).unwrap(), 0);
+
+        // The slowest peer bounds compaction; our own progress is ignored
+        let report = |peer_id: &str, timestamp: Timestamp| {
+            let mut version = VersionVector::new();
+            version.observe(timestamp);
+            store
+                .update_peer_progress(peer_id, doc_id, &version.to_cbor().unwrap())
+                .unwrap();
+        };
+        report("peer-fast", make_timestamp(3000, 0, 1));
+        report("peer-slow", make_timestamp(2000, 0, 1));
+        report(&actor_id.to_string(), make_timestamp(500, 0, 1));
+
+        assert_eq!(compact_document(&store, &mut doc, actor_id).unwrap(), 1);
+        assert_eq!(
+            store
+                .get_deltas_beyond(doc_id, &VersionVector::new())
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // The snapshot plus the remaining log still restores the full state
+        let restored =
+            restore_document(&store, doc_id, actor_id, RemoveSemantics::default()).unwrap();
+        assert_eq!(restored.state, doc.state);
+    }
+
+    #[test]
+    fn compaction_drops_tombstones_every_peer_has_seen() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc_id = &test_doc_id();
+        let actor_id = Uuid::from_bytes([9; 16]);
+        let mut doc = new_document(actor_id);
+        let key = "X".to_string();
+
+        let mut delta = Delta::new();
+        delta.add_insert(
+            key.clone(),
+            serde_json::json!(1),
+            make_timestamp(1000, 0, 1),
+        );
+        delta.add_remove(key.clone(), make_timestamp(2000, 0, 1));
+        doc.apply_delta(&delta).unwrap();
+
+        for (acked, seen) in [(1500, true), (2500, false)] {
+            let mut peer_version = VersionVector::new();
+            peer_version.observe(make_timestamp(acked, 0, 1));
+            store
+                .update_peer_progress("peer", doc_id, &peer_version.to_cbor().unwrap())
+                .unwrap();
+            compact_document(&store, &mut doc, actor_id).unwrap();
+            assert_eq!(doc.state.has_seen(&key), seen);
+        }
+    }
+
+    #[test]
+    fn compaction_waits_for_peers_lagging_on_an_actor() {
+        let store = SqliteStore::in_memory().unwrap();
+        let doc_id = &test_doc_id();
+        let actor_id = Uuid::from_bytes([9; 16]);
+        let mut doc = new_document(actor_id);
+
+        // The peer reported catching up on actor 1 only
+        let mut peer_version = VersionVector::new();
+        peer_version.observe(make_timestamp(3000, 0, 1));
+        store
+            .update_peer_progress("peer", doc_id, &peer_version.to_cbor().unwrap())
+            .unwrap();
+
+        // Deltas of actor 2 it has never seen arrive later
+        for (physical, actor_num) in [(1000, 1), (1500, 2), (3000, 1)] {
+            let timestamp = make_timestamp(physical, 0, actor_num);
+            let mut delta = Delta::new();
+            delta.add_insert(
+                format!("X{actor_num}"),
+                serde_json::json!(physical),
+                timestamp,
+            );
+            doc.apply_delta(&delta).unwrap();
+            store
+                .save_delta(
+                    doc_id,
+                    &timestamp.to_bytes(),
+                    &encode(&delta),
+                    timestamp,
+                    true,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(peer_ack_watermark(&store, &doc, actor_id).unwrap(), Some(0));
+        assert_eq!(compact_document(&store, &mut doc, actor_id).unwrap(), 0);
+
+        // Once the peer reports actor 2, the deltas below both entries go
+        peer_version.observe(make_timestamp(1500, 0, 2));
+        store
+            .update_peer_progress("peer", doc_id, &peer_version.to_cbor().unwrap())
+            .unwrap();
+        assert_eq!(compact_document(&store, &mut doc, actor_id).unwrap(), 1);
+    }
+
+    #[test]
+    fn ae_request_summarizes_version_vector() {
+        let actor_id = Uuid::from_bytes([9; 16]);
+        let mut doc = new_document(actor_id);
+
+        let empty = ae_request(&doc).unwrap();
+        assert!(empty.version_vector().unwrap().is_empty());
+
+        let remote = make_timestamp(2000, 3, 1);
+        let local = make_timestamp(5000, 0, 9);
+        let mut delta = Delta::new();
+        delta.add_insert("A".to_string(), serde_json::json!(1), remote);
+        delta.add_insert("B".to_string(), serde_json::json!(2), local);
+        doc.apply_delta(&delta).unwrap();
+
+        let request = ae_request(&doc).unwrap();
+        let version = request.version_vector().unwrap();
+        assert_eq!(request.doc_id, test_doc_id());
+        assert_eq!(version.get(&remote.actor_id), Some(remote));
+        assert_eq!(version.get(&actor_id), Some(local));
+    }
+
+    /// A minimal in-process agent: a store and its documents.
+    struct TestAgent {
+        actor_id: Uuid,
+        store: SqliteStore,
+        documents: HashMap<DocId, CrdtDocument>,
+    }
+
+    impl TestAgent {
+        fn new(actor_num: u8) -> Self {
+            Self {
+                actor_id: Uuid::from_bytes([actor_num; 16]),
+                store: SqliteStore::in_memory().unwrap(),
+                documents: HashMap::new(),
+            }
+        }
+
+        /// Apply and log a local write, as the ingestion path does.
+        fn write(&mut self, doc_id: &DocId, path: &str, value: serde_json::Value) {
+            let doc = document_mut(&mut self.documents, doc_id, self.actor_id);
+            let delta = doc.set(path, value);
+
+            let timestamp = doc.clock.current();
+            let doc_delta = DocDelta::new(doc_id.clone(), timestamp, encode(&delta));
+            persist_delta(Some(&self.store), &doc_delta, &delta);
+        }
+
+        /// Apply and log a local remove.
+        fn remove(&mut self, doc_id: &DocId, path: &str) {
+            let doc = document_mut(&mut self.documents, doc_id, self.actor_id);
+            let delta = doc.remove(path);
+
+            let timestamp = doc.clock.current();
+            let doc_delta = DocDelta::new(doc_id.clone(), timestamp, encode(&delta));
+            persist_delta(Some(&self.store), &doc_delta, &delta);
+        }
+
+        /// Run one anti-entropy round against a peer.
+        fn sync_from(&mut self, peer: &TestAgent, doc_id: &DocId) -> Option<AntiEntropyResponse> {
+            let request =
+                ae_request(document_mut(&mut self.documents, doc_id, self.actor_id)).unwrap();
+
+            // Round-trip through the wire format
+            let request = AntiEntropyRequest::from_cbor(&request.to_cbor().unwrap()).unwrap();
+            let response =
+                build_ae_response(&request, RemoveSemantics::default(), &peer.store).unwrap()?;
+            let response = AntiEntropyResponse::from_cbor(&response.to_cbor().unwrap()).unwrap();
+
+            apply_ae_response(
+                &response,
+                &mut self.documents,
+                &mut ClockOffenders::new(),
+                Some(&self.store),
+            );
+            Some(response)
+        }
+
+        fn state(&self, doc_id: &DocId) -> &OrMap<String, serde_json::Value> {
+            &self.documents[doc_id].state
+        }
+    }
+
+    #[test]
+    fn anti_entropy_closes_gap_between_agents() {
+        let doc_id = &test_doc_id();
+        let mut agent_a = TestAgent::new(1);
+        let mut agent_b = TestAgent::new(2);
+
+        // B was offline while A wrote
+        agent_a.write(doc_id, "Temperature", serde_json::json!(25.5));
+        agent_a.write(doc_id, "Status", serde_json::json!("Running"));
+        agent_b.write(doc_id, "Pressure", serde_json::json!(1.2));
+
+        let response = agent_b.sync_from(&agent_a, doc_id).unwrap();
+        assert_eq!(response.deltas.len(), 2);
+
+        assert_eq!(
+            agent_b.state(doc_id).get(&"Temperature".to_string()),
+            Some(&serde_json::json!(25.5))
+        );
+        assert_eq!(
+            agent_b.state(doc_id).get(&"Status".to_string()),
+            Some(&serde_json::json!("Running"))
+        );
+
+        // The other direction only needs B's own write
+        let response = agent_a.sync_from(&agent_b, doc_id).unwrap();
+        assert_eq!(response.deltas.len(), 1);
+        assert_eq!(agent_a.state(doc_id), agent_b.state(doc_id));
+
+        // Once converged there is nothing left to send
+        assert!(agent_b.sync_from(&agent_a, doc_id).is_none());
+
+        // Deltas received through AE are logged, so B can now serve them too
+        let mut agent_c = TestAgent::new(3);
+        agent_c.sync_from(&agent_b, doc_id).unwrap();
+        assert_eq!(agent_c.state(doc_id), agent_a.state(doc_id));
+
+        // Relayed deltas say nothing about what their authors have seen
+        assert!(agent_c.store.get_peer_progress(doc_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn anti_entropy_fills_gaps_left_by_out_of_order_delivery() {
+        let doc_id = &test_doc_id();
+        let mut agent_a = TestAgent::new(1);
+        let mut agent_b = TestAgent::new(2);
+
+        agent_a.write(doc_id, "Temperature", serde_json::json!(25.5));
+        agent_a.write(doc_id, "Status", serde_json::json!("Running"));
+
+        // B receives A's second delta but misses the first
+        let logged = agent_a
+            .store
+            .get_deltas_beyond(doc_id, &VersionVector::new())
+            .unwrap();
+        assert_eq!(logged.len(), 2);
+        let second = logged
+            .iter()
+            .map(|(_, payload)| {
+                ciborium::from_reader::<Delta<String, serde_json::Value>, _>(payload.as_slice())
+                    .unwrap()
+            })
+            .find(|delta| delta.prev.is_some())
+            .unwrap();
+        document_mut(&mut agent_b.documents, doc_id, agent_b.actor_id)
+            .apply_delta(&second)
+            .unwrap();
+        assert!(agent_b.documents[doc_id].version.has_gap(&agent_a.actor_id));
+
+        // Its summary does not claim the missing delta, so A resends it
+        let response = agent_b.sync_from(&agent_a, doc_id).unwrap();
+        assert_eq!(response.deltas.len(), 1);
+        assert_eq!(agent_b.state(doc_id), agent_a.state(doc_id));
+        assert_eq!(
+            agent_b.documents[doc_id].version,
+            agent_a.documents[doc_id].version
+        );
+        assert!(agent_b.sync_from(&agent_a, doc_id).is_none());
+    }
+
+    #[test]
+    fn anti_entropy_sends_snapshot_after_compaction() {
+        let doc_id = &test_doc_id();
+        let mut agent_a = TestAgent::new(1);
+
+        agent_a.write(doc_id, "Temperature", serde_json::json!(25.5));
+        agent_a.write(doc_id, "Status", serde_json::json!("Running"));
+
+        let mut peer_version = VersionVector::new();
+        peer_version.observe(make_timestamp(u64::from(u32::MAX) * 1000, 0, 1));
+        agent_a
+            .store
+            .update_peer_progress("peer", doc_id, &peer_version.to_cbor().unwrap())
+            .unwrap();
+        let doc = agent_a.documents.get_mut(doc_id).unwrap();
+        compact_document(&agent_a.store, doc, agent_a.actor_id).unwrap();
+
+        // A new agent cannot rely on the (possibly compacted) log alone
+        let mut agent_c = TestAgent::new(3);
+        let response = agent_c.sync_from(&agent_a, doc_id).unwrap();
+        assert!(response.snapshot.is_some());
+        assert_eq!(agent_c.state(doc_id), agent_a.state(doc_id));
+
+        // The snapshot's version vector is adopted, so it is not resent
+        assert!(agent_c.sync_from(&agent_a, doc_id).is_none());
+    }
+
+    #[test]
+    fn expired_peer_converges_on_deletes_after_returning() {
+        let doc_id = &test_doc_id();
+        let mut agent_a = TestAgent::new(1);
+        let mut agent_b = TestAgent::new(2);
+
+        agent_a.write(doc_id, "Temperature", serde_json::json!(25.5));
+        agent_b.sync_from(&agent_a, doc_id).unwrap();
+
+        // B announces what it has seen, then goes offline past the TTL
+        let hello = AgentHello::with_version_vector(
+            agent_b.actor_id,
+            Vec::new(),
+            &agent_b.documents[doc_id].version,
+        )
+        .unwrap();
+        let mut peers = PeerTable::new(Duration::from_secs(60));
+        observe_peer(
+            &hello,
+            doc_id,
+            &mut peers,
+            Some(&agent_a.store),
+            Instant::now(),
+        );
+        assert_eq!(
+            agent_a.store.expire_peers(u64::MAX >> 1).unwrap(),
+            vec![agent_b.actor_id.to_string()]
+        );
+
+        // A deletes while B is away and another peer acknowledges it; B's
+        // progress still bounds compaction
+        agent_a.remove(doc_id, "Temperature");
+        let doc = agent_a.documents.get_mut(doc_id).unwrap();
+        let removed = doc.clock.current();
+        doc.clock
+            .update(Timestamp {
+                physical_ms: removed.physical_ms + 1000,
+                ..removed
+            })
+            .unwrap();
+        agent_a.write(doc_id, "Status", serde_json::json!("Running"));
+        let doc = agent_a.documents.get_mut(doc_id).unwrap();
+        agent_a
+            .store
+            .update_peer_progress("peer-c", doc_id, &doc.version.to_cbor().unwrap())
+            .unwrap();
+        compact_document(&agent_a.store, doc, agent_a.actor_id).unwrap();
+        assert!(doc.state.has_seen(&"Temperature".to_string()));
+
+        agent_b.sync_from(&agent_a, doc_id).unwrap();
+        assert_eq!(agent_b.state(doc_id).get(&"Temperature".to_string()), None);
+        assert_eq!(agent_b.state(doc_id), agent_a.state(doc_id));
+    }
+
+    #[test]
+    fn anti_entropy_rejects_deltas_from_fast_clocks() {
+        let doc_id = test_doc_id();
+        let store = SqliteStore::in_memory().unwrap();
+        let actor_id = Uuid::from_bytes([1; 16]);
+        let mut doc = new_document(actor_id);
+        doc.clock.set_max_offset(Some(Duration::from_secs(60)));
+        let mut documents = HashMap::from([(doc_id.clone(), doc)]);
+
+        // One peer's clock runs a year fast
+        let on_time = Timestamp::now(Uuid::from_bytes([2; 16]));
+        let mut ahead = Timestamp::now(Uuid::from_bytes([3; 16]));
+        ahead.physical_ms += 365 * 24 * 3_600_000;
+        let deltas = [("Status", on_time), ("Temperature", ahead)]
+            .into_iter()
+            .map(|(path, timestamp)| {
+                let mut delta = Delta::new();
+                delta.add_insert(path.to_string(), serde_json::json!(1), timestamp);
+                DocDelta::new(doc_id.clone(), timestamp, encode(&delta))
+            })
+            .collect();
+        let response = AntiEntropyResponse::with_deltas(doc_id.clone(), deltas);
+
+        let mut offenders = ClockOffenders::new();
+        apply_ae_response(&response, &mut documents, &mut offenders, Some(&store));
+
+        let doc = &documents[&doc_id];
+        assert_eq!(doc.get("Status"), Some(&serde_json::json!(1)));
+        assert_eq!(doc.get("Temperature"), None);
+        assert!(doc.clock.current() < ahead);
+
+        // The rejected delta is neither logged nor served to other peers
+        assert_eq!(
+            store
+                .get_deltas_beyond(&doc_id, &VersionVector::new())
+                .unwrap()
+                .len(),
+            1
+        );
+        let report = offenders.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, ahead.actor_id);
+    }
+
+    #[tokio::test]
+    async fn live_deltas_from_fast_clocks_are_not_persisted() {
+        let doc_id = test_doc_id();
+        let store = SqliteStore::in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let actor_id = Uuid::from_bytes([1; 16]);
+        let mut doc = new_document(actor_id);
+        doc.clock.set_max_offset(Some(Duration::from_secs(60)));
+        let mut documents = HashMap::from([(doc_id.clone(), doc)]);
+
+        let mut ahead = Timestamp::now(Uuid::from_bytes([3; 16]));
+        ahead.physical_ms += 365 * 24 * 3_600_000;
+        let mut delta = Delta::new();
+        delta.add_insert("Temperature".to_string(), serde_json::json!(1), ahead);
+        let payload = DocDelta::new(doc_id.clone(), ahead, encode(&delta))
+            .to_cbor()
+            .unwrap();
+
+        let mut offenders = ClockOffenders::new();
+        handle_delta_message(
+            &payload,
+            &topics::doc_hash(&doc_id),
+            actor_id,
+            &mut documents,
+            &HashMap::new(),
+            &HashMap::new(),
+            None,
+            &blobs,
+            &mut EchoFilter::new(Duration::from_secs(30)),
+            &mut offenders,
+            Some(&store),
+        )
+        .await;
+
+        assert_eq!(documents[&doc_id].get("Temperature"), None);
+        assert!(store
+            .get_deltas_beyond(&doc_id, &VersionVector::new())
+            .unwrap()
+            .is_empty());
+        assert_eq!(offenders.report().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn state_of_unsubscribed_documents_is_dropped() {
+        let doc_id = test_doc_id();
+        let store = SqliteStore::in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let mut documents = HashMap::new();
+
+        let timestamp = make_timestamp(1000, 0, 3);
+        let mut delta = Delta::new();
+        delta.add_insert("Temperature".to_string(), serde_json::json!(1), timestamp);
+        let doc_delta = DocDelta::new(doc_id.clone(), timestamp, encode(&delta));
+
+        handle_delta_message(
+            &doc_delta.to_cbor().unwrap(),
+            &topics::doc_hash(&doc_id),
+            Uuid::from_bytes([1; 16]),
+            &mut documents,
+            &HashMap::new(),
+            &HashMap::new(),
+            None,
+            &blobs,
+            &mut EchoFilter::new(Duration::from_secs(30)),
+            &mut ClockOffenders::new(),
+            Some(&store),
+        )
+        .await;
+        let response = AntiEntropyResponse::with_deltas(doc_id.clone(), vec![doc_delta]);
+        apply_ae_response(
+            &response,
+            &mut documents,
+            &mut ClockOffenders::new(),
+            Some(&store),
+        );
+
+        assert!(documents.is_empty());
+        assert!(store
+            .get_deltas_beyond(&doc_id, &VersionVector::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn own_deltas_echoed_by_the_broker_are_dropped() {
+        let doc_id = test_doc_id();
+        let store = SqliteStore::in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let actor_id = Uuid::from_bytes([1; 16]);
+        let mut documents = HashMap::from([(doc_id.clone(), new_document(actor_id))]);
+
+        let own = make_timestamp(1000, 0, 1);
+        let mut echoed = Delta::new();
+        echoed.add_insert("Temperature".to_string(), serde_json::json!(1), own);
+        let theirs = make_timestamp(1000, 0, 3);
+        let mut mixed = Delta::new();
+        mixed.add_insert("Speed".to_string(), serde_json::json!(2), own);
+        mixed.add_insert("Pressure".to_string(), serde_json::json!(3), theirs);
+
+        for (timestamp, delta) in [(own, &echoed), (theirs, &mixed)] {
+            let payload = DocDelta::new(doc_id.clone(), timestamp, encode(delta))
+                .to_cbor()
+                .unwrap();
+            handle_delta_message(
+                &payload,
+                &topics::doc_hash(&doc_id),
+                actor_id,
+                &mut documents,
+                &HashMap::new(),
+                &HashMap::new(),
+                None,
+                &blobs,
+                &mut EchoFilter::new(Duration::from_secs(30)),
+                &mut ClockOffenders::new(),
+                Some(&store),
+            )
+            .await;
+        }
+
+        // Only the delta with a foreign timestamp is applied and persisted
+        let doc = &documents[&doc_id];
+        assert_eq!(doc.get("Temperature"), None);
+        assert_eq!(doc.get("Speed"), Some(&serde_json::json!(2)));
+        assert_eq!(doc.get("Pressure"), Some(&serde_json::json!(3)));
+        assert_eq!(
+            store
+                .get_deltas_beyond(&doc_id, &VersionVector::new())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn anti_entropy_refuses_state_of_other_remove_semantics() {
+        let doc_id = test_doc_id();
+        let actor_id = Uuid::from_bytes([1; 16]);
+        let mut documents = HashMap::from([(doc_id.clone(), new_document(actor_id))]);
+        let mut peer = CrdtDocument::with_semantics(
+            doc_id.clone(),
+            Hlc::new(Uuid::from_bytes([2; 16])),
+            RemoveSemantics::ObservedRemove,
+        );
+        let delta = peer.set("Status", serde_json::json!("Running"));
+
+        // Deltas announced under other semantics are refused
+        let mut doc_delta = DocDelta::new(doc_id.clone(), peer.clock.current(), encode(&delta));
+        doc_delta.remove_semantics = Some(RemoveSemantics::ObservedRemove);
+        let response = AntiEntropyResponse::with_deltas(doc_id.clone(), vec![doc_delta]);
+        let mut offenders = ClockOffenders::new();
+        apply_ae_response(&response, &mut documents, &mut offenders, None);
+        assert!(documents[&doc_id].is_empty());
+
+        // So are snapshots, which carry their own semantics
+        let mut response = AntiEntropyResponse::with_snapshot(doc_id.clone(), encode(&peer.state));
+        response.snapshot_version = Some(peer.version.to_cbor().unwrap());
+        apply_ae_response(&response, &mut documents, &mut offenders, None);
+        assert!(documents[&doc_id].is_empty());
+
+        // Peers that do not announce their semantics are trusted
+        response.snapshot = None;
+        response.deltas = vec![DocDelta::new(
+            doc_id.clone(),
+            peer.clock.current(),
+            encode(&delta),
+        )];
+        apply_ae_response(&response, &mut documents, &mut offenders, None);
+        assert_eq!(
+            documents[&doc_id].get("Status"),
+            Some(&serde_json::json!("Running"))
+        );
+    }
+
+    #[test]
+    fn only_documents_with_new_versions_count_as_changed() {
+        let mut documents = HashMap::from([(test_doc_id(), new_document(Uuid::new_v4()))]);
+        let mut checked = HashMap::new();
+
+        assert_eq!(
+            changed_documents(&documents, &mut checked),
+            vec![test_doc_id()]
+        );
+        assert!(changed_documents(&documents, &mut checked).is_empty());
+
+        let doc = documents.get_mut(&test_doc_id()).unwrap();
+        let _ = doc.set("Status", serde_json::json!("Running"));
+        assert_eq!(
+            changed_documents(&documents, &mut checked),
+            vec![test_doc_id()]
+        );
+        assert!(changed_documents(&documents, &mut checked).is_empty());
+    }
+
+    #[test]
+    fn restore_without_persisted_state_is_empty() {
+        let store = SqliteStore::in_memory().unwrap();
+
+        let doc = restore_document(
+            &store,
+            &test_doc_id(),
+            Uuid::new_v4(),
+            RemoveSemantics::default(),
+        )
+        .unwrap();
+
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn poll_deltas_order_after_remote_writes() {
+        let actor_id = Uuid::new_v4();
+        let mut doc = new_document(actor_id);
+
+        // Remote writes from a peer whose clock runs ahead of ours
+        let ahead_ms = doc.clock.current().physical_ms + 60_000;
+        let mut remote = Delta::new();
+        remote.add_insert(
+            "Temperature".to_string(),
+            serde_json::json!(20),
+            make_timestamp(ahead_ms, 0, 2),
+        );
+        remote.add_insert(
+            "Pressure".to_string(),
+            serde_json::json!(1.2),
+            make_timestamp(ahead_ms, 1, 2),
+        );
+        doc.apply_delta(&remote).unwrap();
+
+        let mut polled = Delta::new();
+        polled.add_insert(
+            "Temperature".to_string(),
+            serde_json::json!(25),
+            make_timestamp(1_000, 0, 1),
+        );
+        polled.add_remove("Pressure".to_string(), make_timestamp(1_000, 1, 1));
+
+        let delta = doc.stable_delta(&polled);
+        doc.apply_delta(&delta).unwrap();
+
+        assert_eq!(delta.inserts.len(), 1);
+        assert_eq!(delta.removes.len(), 1);
+        assert!(delta.timestamps().all(|ts| ts.actor_id == actor_id));
+        assert_eq!(doc.get("Temperature"), Some(&serde_json::json!(25)));
+        assert_eq!(doc.get("Pressure"), None);
+    }
+
+    #[test]
+    fn list_entries_replicate_by_stable_id() {
+        let topic = format!(
+            "sm-repository/repo1/submodels/{}/updated",
+            aas_deltasync_adapter_aas::encode_id_base64url("sm1")
+        );
+        let submodel = serde_json::json!({
+            "modelType": "Submodel",
+            "submodelElements": [
+                {"modelType": "SubmodelElementList", "idShort": "Phases", "value": [
+                    {"modelType": "Property", "value": "230"},
+                    {"modelType": "Property", "value": "231"}
+                ]}
+            ]
+        });
+        let event = BasyxEvent::parse(&topic, submodel.to_string().as_bytes()).unwrap();
+        let mut site_a = new_document(Uuid::new_v4());
+        let mut site_b = new_document(Uuid::new_v4());
+        let delta = submodel_event_to_delta(&event, &ElementTypes::new(), &mut site_a);
+        let delta = site_a.stable_delta(&delta);
+        site_a.apply_delta(&delta).unwrap();
+        site_b.apply_delta(&delta).unwrap();
+        assert_eq!(site_a.get("Phases[0]"), None);
+
+        // A poll sees an entry inserted at the head of the list
+        let mut polled = Delta::new();
+        let clock = &mut site_a.clock;
+        polled.add_insert(
+            "Phases[0]".to_string(),
+            serde_json::json!("229"),
+            clock.tick(),
+        );
+        polled.add_insert(
+            "Phases[1]".to_string(),
+            serde_json::json!("230"),
+            clock.tick(),
+        );
+        polled.add_insert(
+            "Phases[2]".to_string(),
+            serde_json::json!("231"),
+            clock.tick(),
+        );
+        let delta = site_a.stable_delta(&polled);
+        assert_eq!(delta.inserts.len(), 2);
+        site_a.apply_delta(&delta).unwrap();
+
+        // Peers write the reordered list whole
+        site_b.apply_delta(&delta).unwrap();
+        let writes = indexed_writes(&site_b.state, &delta);
+        assert_eq!(writes.lists, BTreeSet::from(["Phases".to_string()]));
+        assert_eq!(
+            unflatten_value("Phases", &site_b.index_view(), &ElementTypes::new()),
+            Some(serde_json::json!(["229", "230", "231"]))
+        );
+    }
+
+    #[test]
+    fn patched_event_becomes_one_multi_entry_delta() {
+        let topic = format!(
+            "sm-repository/repo1/submodels/{}/submodelElements/patched",
+            aas_deltasync_adapter_aas::encode_id_base64url("sm1")
+        );
+        let payload = serde_json::json!([
+            {"modelType": "Property", "idShort": "Temperature", "value": "25.5"},
+            {"modelType": "SubmodelElementCollection", "idShort": "Motor", "value": [
+                {"modelType": "Property", "idShort": "Speed", "value": "1200"}
+            ]}
+        ]);
+        let event = BasyxEvent::parse(&topic, payload.to_string().as_bytes()).unwrap();
+
+        let mut doc = new_document(Uuid::new_v4());
+        let delta = basyx_event_to_delta(&event, &ElementTypes::new(), &mut doc);
+
+        let paths: Vec<_> = delta
+            .inserts
+            .iter()
+            .map(|(path, _, _)| path.as_str())
+            .collect();
+        assert_eq!(paths, ["Temperature", "Motor.Speed"]);
+        assert!(delta.inserts[0].2 < delta.inserts[1].2);
+        assert_eq!(delta.inserts[1].2, doc.clock.current());
+    }
+
+    #[tokio::test]
+    async fn egress_holds_back_removes_of_conflicted_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let client = AasClient::new(AasClientConfig {
+            base_url: "http://127.0.0.1:1".to_string(),
+            ..AasClientConfig::default()
+        })
+        .unwrap();
+        let sub = SubscriptionConfig {
+            aas_id: "aas1".to_string(),
+            submodel_id: "sm1".to_string(),
+            repo_id: None,
+            view: View::Value,
+        };
+        let mut echoes = EchoFilter::new(Duration::from_secs(30));
+
+        let doc = new_document(Uuid::new_v4());
+        let mut delta = Delta::new();
+        delta.add_remove(
+            "Setpoints.Temperature".to_string(),
+            make_timestamp(1000, 0, 1),
+        );
+        delta.add_remove("Speed".to_string(), make_timestamp(1000, 0, 1));
+        let writes = indexed_writes(&doc.state, &delta);
+        let held = BTreeSet::from(["Setpoints.Temperature".to_string()]);
+        apply_delta_egress(
+            &client,
+            &blobs,
+            &sub,
+            &writes,
+            &BTreeMap::new(),
+            &ElementTypes::new(),
+            &held,
+            &mut echoes,
+        )
+        .await;
+
+        // Only the remove that went out to the server echoes back
+        let filtered = echoes.filter("sm1", &delta, Instant::now());
+        let kept: Vec<_> = filtered
+            .removes
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(kept, ["Setpoints.Temperature"]);
+    }
+
+    #[tokio::test]
+    async fn blob_content_replicates_by_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let client = AasClient::new(AasClientConfig::default()).unwrap();
+        let mut types = ElementTypes::new();
+        types.insert("Manual", ElementType::Blob);
+
+        let mut values = BTreeMap::from([(
+            "Manual".to_string(),
+            serde_json::json!({"contentType": "application/pdf", "value": BASE64.encode(b"%PDF-1.7")}),
+        )]);
+        store_contents(&client, &blobs, "sm1", &types, values.iter_mut()).await;
+
+        let blob = BlobRef::from_value(&values["Manual"]).unwrap();
+        assert_eq!(blob.content_type, "application/pdf");
+        assert_eq!(
+            blobs.get(&blob.content_hash).unwrap().as_deref(),
+            Some(&b"%PDF-1.7"[..])
+        );
+
+        // A peer without the content requests it once per retry window
+        let mut doc = new_document(Uuid::new_v4());
+        let mut delta = Delta::new();
+        delta.add_insert(
+            "Manual".to_string(),
+            values["Manual"].clone(),
+            doc.clock.tick(),
+        );
+        doc.apply_delta(&delta).unwrap();
+        let peer_dir = tempfile::tempdir().unwrap();
+        let peer_blobs = BlobStore::new(peer_dir.path());
+        let mut fetches = BlobFetches::new(Duration::from_secs(30));
+        let requests = blob_requests([&doc].into_iter(), &peer_blobs, &mut fetches);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].1.content_hash, blob.content_hash);
+        assert!(blob_requests([&doc].into_iter(), &peer_blobs, &mut fetches).is_empty());
+
+        // Chunks from the holder complete the peer's copy
+        let content = blobs.get(&blob.content_hash).unwrap().unwrap();
+        for chunk in BlobChunk::split(&blob.content_hash, &content) {
+            let hash = handle_blob_chunk(&chunk.to_cbor().unwrap(), &peer_blobs, &mut fetches);
+            assert_eq!(hash.as_deref(), Some(blob.content_hash.as_str()));
+        }
+        assert!(peer_blobs.contains(&blob.content_hash));
+        assert!(blob_requests([&doc].into_iter(), &peer_blobs, &mut fetches).is_empty());
+    }
+
+    #[test]
+    fn per_field_elements_replicate_by_field() {
+        let topic = |path: &str| {
+            format!(
+                "sm-repository/repo1/submodels/{}/submodelElements/{path}/updated",
+                aas_deltasync_adapter_aas::encode_id_base64url("sm1")
+            )
+        };
+        let mut types = ElementTypes::new();
+        types.insert("Limits", ElementType::Range);
+        types.insert("Name", ElementType::MultiLanguageProperty);
+
+        // Concurrent edits to different bounds both survive the merge
+        let range = |min: &str, max: &str| {
+            let element = serde_json::json!({
+                "modelType": "Range", "idShort": "Limits", "min": min, "max": max
+            });
+            BasyxEvent::parse(&topic("Limits"), element.to_string().as_bytes()).unwrap()
+        };
+        let mut site_a = new_document(Uuid::new_v4());
+        let mut site_b = new_document(Uuid::new_v4());
+        let baseline = basyx_event_to_delta(&range("0", "100"), &types, &mut site_a);
+        site_a.apply_delta(&baseline).unwrap();
+        site_b.apply_delta(&baseline).unwrap();
+
+        let from_a = basyx_event_to_delta(&range("5", "100"), &types, &mut site_a);
+        let from_b = basyx_event_to_delta(&range("0", "90"), &types, &mut site_b);
+        assert_eq!(from_a.inserts.len(), 1);
+        assert_eq!(from_b.inserts.len(), 1);
+
+        site_a.apply_delta(&from_a).unwrap();
+        site_a.apply_delta(&from_b).unwrap();
+        assert_eq!(
+            types.join(
+                "Limits",
+                element_fields(&site_a.index_view(), &types, "Limits")
+            ),
+            serde_json::json!({"max": "90", "min": "5"})
+        );
+
+        // Languages dropped from an element are removed
+        let mut doc = new_document(Uuid::new_v4());
+        let _ = doc.set("Name.en", serde_json::json!("Pump"));
+        let _ = doc.set("Name.de", serde_json::json!("Pumpe"));
+        let name = serde_json::json!({
+            "modelType": "MultiLanguageProperty",
+            "idShort": "Name",
+            "value": [{"language": "en", "text": "Pump v2"}]
+        });
+        let event = BasyxEvent::parse(&topic("Name"), name.to_string().as_bytes()).unwrap();
+        let delta = basyx_event_to_delta(&event, &types, &mut doc);
+        doc.apply_delta(&delta).unwrap();
+
+        assert_eq!(doc.get("Name.en"), Some(&serde_json::json!("Pump v2")));
+        assert_eq!(doc.get("Name.de"), None);
+        assert_eq!(
+            types.join("Name", element_fields(&doc.index_view(), &types, "Name")),
+            serde_json::json!([{"en": "Pump v2"}])
+        );
+    }
+
+    #[test]
+    fn submodel_events_reconcile_the_whole_document() {
+        let topic = |event: &str| {
+            format!(
+                "sm-repository/repo1/submodels/{}/{event}",
+                aas_deltasync_adapter_aas::encode_id_base64url("sm1")
+            )
+        };
+        let mut doc = new_document(Uuid::new_v4());
+        let _ = doc.set("Temperature", serde_json::json!("20"));
+        let _ = doc.set("Legacy", serde_json::json!("1"));
+
+        // A replaced submodel drops elements it no longer contains
+        let submodel = serde_json::json!({
+            "modelType": "Submodel",
+            "submodelElements": [
+                {"modelType": "Property", "idShort": "Temperature", "value": "25.5"},
+                {"modelType": "SubmodelElementCollection", "idShort": "Motor", "value": [
+                    {"modelType": "Property", "idShort": "Speed", "value": "1200"}
+                ]}
+            ]
+        });
+        let event = BasyxEvent::parse(&topic("updated"), submodel.to_string().as_bytes()).unwrap();
+        let delta = submodel_event_to_delta(&event, &ElementTypes::new(), &mut doc);
+        doc.apply_delta(&delta).unwrap();
+
+        assert_eq!(doc.get("Temperature"), Some(&serde_json::json!("25.5")));
+        assert_eq!(doc.get("Motor.Speed"), Some(&serde_json::json!("1200")));
+        assert_eq!(doc.get("Legacy"), None);
+
+        // A deleted submodel tombstones everything
+        let event = BasyxEvent::parse(&topic("deleted"), b"").unwrap();
+        let delta = submodel_event_to_delta(&event, &ElementTypes::new(), &mut doc);
+        doc.apply_delta(&delta).unwrap();
+
+        assert_eq!(delta.removes.len(), 2);
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn subscriptions_match_on_repository_and_submodel() {
+        let doc_id = DocId::value_view("aas1", "sm1");
+        let sub = SubscriptionConfig {
+            aas_id: "aas1".to_string(),
+            submodel_id: "sm1".to_string(),
+            repo_id: Some("line-1".to_string()),
+            view: View::Value,
+        };
+        let subscriptions = HashMap::from([(doc_id.clone(), sub)]);
+
+        assert_eq!(
+            find_subscribed_doc(&subscriptions, Some("line-1"), "sm1"),
+            Some(doc_id.clone())
+        );
+        assert_eq!(
+            find_subscribed_doc(&subscriptions, Some("line-2"), "sm1"),
+            None
+        );
+        // Adapters that do not know the repository still match
+        assert_eq!(
+            find_subscribed_doc(&subscriptions, None, "sm1"),
+            Some(doc_id)
+        );
+    }
+
+    #[test]
+    fn hello_records_peer_progress_from_clock_summary() {
+        let store = SqliteStore::in_memory().unwrap();
+        let actor_id = Uuid::new_v4();
+        let doc_id = &test_doc_id();
+        let mut peers = PeerTable::new(Duration::from_secs(60));
+
+        let mut doc = new_document(actor_id);
+        let mut delta = Delta::new();
+        delta.add_insert(
+            "a".to_string(),
+            serde_json::json!(1),
+            make_timestamp(1_000, 0, 1),
+        );
+        delta.add_insert(
+            "b".to_string(),
+            serde_json::json!(2),
+            make_timestamp(2_000, 0, 2),
+        );
+        doc.apply_delta(&delta).unwrap();
+
+        // The peer has caught up on actor 2 but only partly on actor 1
+        let mut peer_version = VersionVector::new();
+        peer_version.observe(make_timestamp(500, 0, 1));
+        peer_version.observe(make_timestamp(2_000, 0, 2));
+        let hello =
+            AgentHello::with_version_vector(Uuid::new_v4(), Vec::new(), &peer_version).unwrap();
+
+        observe_peer(&hello, doc_id, &mut peers, Some(&store), Instant::now());
+
+        assert_eq!(peers.len(), 1);
+        assert_eq!(
+            peer_ack_watermark(&store, &doc, actor_id).unwrap(),
+            Some(500)
+        );
+    }
+
+    #[test]
+    fn hello_ack_is_zero_for_unseen_actors() {
+        let mut own = VersionVector::new();
+        own.observe(make_timestamp(1_000, 0, 1));
+
+        let ack = hello_ack(&own, &VersionVector::new()).unwrap();
+
+        assert_eq!(ack.physical_ms, 0);
+        assert!(hello_ack(&VersionVector::new(), &VersionVector::new()).is_none());
+    }
+
+    #[test]
+    fn views_of_a_submodel_are_separate_documents() {
+        let value = DocId::value_view("aas1", "sm1");
+        let metadata = DocId::new("aas1", "sm1", View::Metadata);
+        let subscriptions: HashMap<DocId, SubscriptionConfig> = [&metadata, &value]
+            .into_iter()
+            .map(|doc_id| {
+                let sub = SubscriptionConfig {
+                    aas_id: doc_id.aas_id.clone(),
+                    submodel_id: doc_id.submodel_id.clone(),
+                    repo_id: None,
+                    view: doc_id.view,
+                };
+                (sub.doc_id(), sub)
+            })
+            .collect();
+
+        // Adapter events only ever map to the value view
+        assert_eq!(
+            find_subscribed_doc(&subscriptions, None, "sm1"),
+            Some(value.clone())
+        );
+        assert_eq!(find_subscribed_doc(&subscriptions, None, "sm2"), None);
+
+        let mut agent_a = TestAgent::new(1);
+        let mut agent_b = TestAgent::new(2);
+        agent_a.write(&value, "Temperature", serde_json::json!(25.5));
+        agent_a.write(
+            &metadata,
+            "Temperature",
+            serde_json::json!({"unit": "degC"}),
+        );
+
+        agent_b.sync_from(&agent_a, &value).unwrap();
+        assert_eq!(agent_b.state(&value), agent_a.state(&value));
+        assert!(!agent_b.documents.contains_key(&metadata));
+
+        agent_b.sync_from(&agent_a, &metadata).unwrap();
+        assert_eq!(agent_b.state(&metadata), agent_a.state(&metadata));
+        assert_ne!(agent_b.state(&metadata), agent_b.state(&value));
+    }
+}
@@ -0,0 +1,108 @@
+//! Tracking of peers whose clocks run too far ahead.
+//!
+//! Deltas stamped further ahead than a document clock's maximum offset are
+//! rejected, see [`ClockError`]. The writes keep coming back through
+//! anti-entropy until the peer's clock is fixed, so rejections are counted
+//! per actor and reported for operators rather than logged one by one.
+
+use aas_deltasync_core::ClockError;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Rejections of one actor's timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Offender {
+    /// Timestamps rejected so far
+    pub rejected: u64,
+    /// Furthest ahead of the local wall clock a timestamp was
+    pub ahead_ms: u64,
+}
+
+/// Actors whose timestamps were rejected as too far ahead.
+#[derive(Debug, Default)]
+pub struct ClockOffenders {
+    offenders: HashMap<Uuid, Offender>,
+    /// Rejections per actor at the last report
+    reported: HashMap<Uuid, u64>,
+}
+
+impl ClockOffenders {
+    /// Create a tracker with no offenders.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a rejection, returning the offender's tally.
+    pub fn record(&mut self, err: &ClockError) -> Offender {
+        let ClockError::TooFarAhead {
+            actor_id, ahead_ms, ..
+        } = *err;
+        let offender = self.offenders.entry(actor_id).or_default();
+        offender.rejected += 1;
+        offender.ahead_ms = offender.ahead_ms.max(ahead_ms);
+        *offender
+    }
+
+    /// Offenders with rejections since the last report.
+    pub fn report(&mut self) -> Vec<(Uuid, Offender)> {
+        let mut due: Vec<(Uuid, Offender)> = self
+            .offenders
+            .iter()
+            .filter(|(actor_id, offender)| {
+                self.reported.get(actor_id).copied().unwrap_or(0) < offender.rejected
+            })
+            .map(|(actor_id, offender)| (*actor_id, *offender))
+            .collect();
+        due.sort_by_key(|(actor_id, _)| *actor_id);
+
+        for (actor_id, offender) in &due {
+            self.reported.insert(*actor_id, offender.rejected);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn too_far_ahead(actor_id: Uuid, ahead_ms: u64) -> ClockError {
+        ClockError::TooFarAhead {
+            actor_id,
+            ahead_ms,
+            max_offset_ms: 60_000,
+        }
+    }
+
+    #[test]
+    fn offenders_are_reported_once_per_new_rejection() {
+        let fast = Uuid::from_bytes([1; 16]);
+        let faster = Uuid::from_bytes([2; 16]);
+        let mut offenders = ClockOffenders::new();
+        assert!(offenders.report().is_empty());
+
+        offenders.record(&too_far_ahead(fast, 90_000));
+        let tally = offenders.record(&too_far_ahead(fast, 70_000));
+        assert_eq!(
+            tally,
+            Offender {
+                rejected: 2,
+                ahead_ms: 90_000
+            }
+        );
+        offenders.record(&too_far_ahead(faster, 31_536_000_000));
+
+        let report = offenders.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0], (fast, tally));
+        assert!(offenders.report().is_empty());
+
+        // Only actors rejected again come up in the next report
+        offenders.record(&too_far_ahead(faster, 31_536_000_000));
+        let report = offenders.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, faster);
+        assert_eq!(report[0].1.rejected, 2);
+    }
+}
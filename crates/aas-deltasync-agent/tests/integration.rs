@@ -1,19 +1,11 @@
-use aas_deltasync_core::{Delta, Hlc};
-use aas_deltasync_proto::{DocDelta, TopicScheme};
+use aas_deltasync_core::{Delta, DocId, Hlc};
+use aas_deltasync_proto::{doc_hash, DocDelta, TopicScheme};
 use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::time::Duration;
 use tokio::sync::oneshot;
 use tokio::time::timeout;
 use uuid::Uuid;
 
-fn hash_doc_id(doc_id: &str) -> String {
-    let mut hasher = DefaultHasher::new();
-    doc_id.hash(&mut hasher);
-    format!("{:016x}", hasher.finish())
-}
-
 fn parse_mqtt_url(url: &str) -> (String, u16) {
     let url = url
         .strip_prefix("tcp://")
@@ -56,9 +48,8 @@ async fn mqtt_delta_roundtrip() {
 
     let tenant = "integration";
     let scheme = TopicScheme::new(tenant);
-    let doc_id = "urn:example:aas:1:urn:example:sm:data";
-    let doc_hash = hash_doc_id(doc_id);
-    let topic = scheme.delta(&doc_hash);
+    let doc_id = DocId::value_view("urn:example:aas:1", "urn:example:sm:data");
+    let topic = scheme.delta(&doc_hash(&doc_id));
 
     let mut sub_opts = MqttOptions::new(format!("sub-{}", Uuid::new_v4()), host.clone(), port);
     sub_opts.set_keep_alive(Duration::from_secs(5));
@@ -117,7 +108,7 @@ async fn mqtt_delta_roundtrip() {
     let mut delta_payload = Vec::new();
     ciborium::into_writer(&delta, &mut delta_payload).unwrap();
 
-    let doc_delta = DocDelta::new(doc_id.to_string(), ts, delta_payload);
+    let doc_delta = DocDelta::new(doc_id.clone(), ts, delta_payload);
     let payload = doc_delta.to_cbor().unwrap();
 
     pub_client
@@ -0,0 +1,81 @@
+//! Content-addressed references for `File` and `Blob` values.
+//!
+//! Attachments are too large to replicate inline with every delta, so the
+//! register of a content-addressed element holds a [`BlobRef`] instead: the
+//! SHA-256 hash of the content and its content type. The content itself
+//! lives in a local blob store and is fetched from peers on demand.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Hash content for content addressing.
+///
+/// Returns the SHA-256 digest of `bytes`, hex-encoded.
+#[must_use]
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// The replicated value of a content-addressed element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobRef {
+    /// MIME type of the content
+    pub content_type: String,
+    /// SHA-256 hash of the content, hex-encoded
+    pub content_hash: String,
+}
+
+impl BlobRef {
+    /// Reference `bytes` by their hash.
+    #[must_use]
+    pub fn new(content_type: impl Into<String>, bytes: &[u8]) -> Self {
+        Self {
+            content_type: content_type.into(),
+            content_hash: content_hash(bytes),
+        }
+    }
+
+    /// Read a reference from a register value.
+    ///
+    /// Returns `None` for inline values, which carry no content hash.
+    #[must_use]
+    pub fn from_value(value: &Value) -> Option<Self> {
+        value.get("contentHash")?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// The register value holding this reference.
+    #[must_use]
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({
+            "contentType": self.content_type,
+            "contentHash": self.content_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn blob_refs_round_trip_through_values() {
+        let blob = BlobRef::new("application/pdf", b"%PDF-1.7");
+        assert_eq!(blob.content_hash.len(), 64);
+        assert_eq!(blob.content_hash, content_hash(b"%PDF-1.7"));
+        assert_ne!(blob.content_hash, content_hash(b"%PDF-1.6"));
+        assert_eq!(BlobRef::from_value(&blob.to_value()), Some(blob));
+
+        let inline = json!({"contentType": "application/pdf", "value": "/manual.pdf"});
+        assert_eq!(BlobRef::from_value(&inline), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! Counters for numeric properties changed at several sites.
+//!
+//! Operating hours, cycle counts and produced-parts totals are incremented
+//! at every site, and a last-writer-wins value would keep only one site's
+//! increments. A counter path instead holds a base value, written when the
+//! counter is first seen, plus one [`CounterSlot`] per actor replicated
+//! alongside, `OperatingHours#count.{actor}`. An actor only ever grows its
+//! own slot, with strictly increasing timestamps, so the latest write of a
+//! slot is also its largest and the slots merge as a [`PnCounter`]. A
+//! counter path's value is its base plus the value of that counter.
+//!
+//! Paths become counters through [`CrdtDocument::counter_paths`], or the
+//! `semanticId`s in [`CrdtDocument::counter_semantic_ids`]; a path that
+//! already has slots stays a counter. A changed value entering through
+//! [`CrdtDocument::reconcile`] is replicated as an increment or decrement
+//! of the local actor's slot, and [`CrdtDocument::index_view`] and
+//! [`indexed_writes`](crate::indexed_writes) materialize the sum for
+//! egress.
+
+use crate::crdt::{CounterSlot, Delta, OrMap, PnCounter};
+use crate::document::CrdtDocument;
+use crate::lists::ListIds;
+use crate::merge::{ElementTypes, PathPattern};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Separator between a counter's path and the actor of one of its slots.
+pub const SLOT_SEPARATOR: &str = "#count.";
+
+/// Check if a document path holds a counter slot.
+#[must_use]
+pub fn is_slot(path: &str) -> bool {
+    slot_entry(path).is_some()
+}
+
+/// Get the path of the counter a slot path belongs to.
+pub(crate) fn slot_counter(path: &str) -> Option<&str> {
+    slot_entry(path).map(|(counter, _)| counter)
+}
+
+/// Split a slot path into its counter path and actor.
+fn slot_entry(path: &str) -> Option<(&str, Uuid)> {
+    let (counter, actor) = path.rsplit_once(SLOT_SEPARATOR)?;
+    Some((counter, Uuid::parse_str(actor).ok()?))
+}
+
+fn slot_path(counter: &str, actor: Uuid) -> String {
+    format!("{counter}{SLOT_SEPARATOR}{actor}")
+}
+
+fn slot_value(slot: CounterSlot) -> Value {
+    serde_json::to_value(slot).unwrap_or_default()
+}
+
+/// Read a count from an integer, or a string holding one as servers
+/// serialize `xs:int` values.
+pub(crate) fn as_count(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The slots of every counter in a document state, keyed by counter path.
+pub(crate) fn counters(state: &OrMap<String, Value>) -> BTreeMap<String, PnCounter> {
+    counters_within(state, |_| true)
+}
+
+/// The slots of the counters whose slot paths `in_scope` selects.
+pub(crate) fn counters_within(
+    state: &OrMap<String, Value>,
+    in_scope: impl Fn(&str) -> bool,
+) -> BTreeMap<String, PnCounter> {
+    let mut counters: BTreeMap<String, PnCounter> = BTreeMap::new();
+    for (path, value) in state.iter() {
+        let Some((counter, actor)) = slot_entry(path).filter(|_| in_scope(path)) else {
+            continue;
+        };
+        if let Ok(slot) = serde_json::from_value(value.clone()) {
+            counters
+                .entry(counter.to_string())
+                .or_default()
+                .merge_slot(actor, slot);
+        }
+    }
+    counters
+}
+
+/// Value of a counter: its base, or zero, plus its slots.
+fn counter_value(base: Option<&Value>, slots: &PnCounter) -> Value {
+    let base = base.and_then(as_count).unwrap_or(0);
+    Value::from(base.saturating_add(slots.value()))
+}
+
+/// The entries of a document state `in_scope` selects, with each
+/// counter's slots folded into its value.
+pub(crate) fn fold_counters_within(
+    state: &OrMap<String, Value>,
+    in_scope: impl Fn(&str) -> bool,
+) -> BTreeMap<String, Value> {
+    let counters = counters_within(state, &in_scope);
+    let mut entries: BTreeMap<String, Value> = state
+        .iter()
+        .filter(|(path, _)| !is_slot(path) && in_scope(path))
+        .map(|(path, value)| {
+            let value = match counters.get(path) {
+                Some(slots) => counter_value(Some(value), slots),
+                None => value.clone(),
+            };
+            (path.clone(), value)
+        })
+        .collect();
+    for (path, slots) in &counters {
+        entries
+            .entry(path.clone())
+            .or_insert_with(|| counter_value(None, slots));
+    }
+    entries
+}
+
+/// The write egress needs after an entry of a counter changed: the
+/// counter's path and its value, or `None` if the counter was removed.
+///
+/// Returns `None` if `path` is neither a slot nor the base of a counter
+/// with slots.
+pub(crate) fn counter_write<'a>(
+    state: &OrMap<String, Value>,
+    counters: &BTreeMap<String, PnCounter>,
+    path: &'a str,
+) -> Option<(&'a str, Option<Value>)> {
+    let counter = slot_counter(path).unwrap_or(path);
+    let slots = counters.get(counter);
+    if slots.is_none() && !is_slot(path) {
+        return None;
+    }
+
+    let base = state.get(&counter.to_string());
+    let value = (base.is_some() || slots.is_some())
+        .then(|| counter_value(base, slots.unwrap_or(&PnCounter::new())));
+    Some((counter, value))
+}
+
+impl CrdtDocument {
+    /// Check if `path` is selected as a counter by
+    /// [`CrdtDocument::counter_paths`].
+    ///
+    /// Patterns match index paths; `ids` translates stable ones.
+    pub(crate) fn selects_counter(&self, ids: &ListIds, path: &str) -> bool {
+        let path = ids.index_path(path).unwrap_or_else(|| path.to_string());
+        self.counter_paths.iter().any(|p| p.matches(&path))
+    }
+
+    /// The entry that brings the counter at `path` to `count`.
+    ///
+    /// A counter never seen before takes `count` as its base; otherwise
+    /// the difference is added to the local actor's slot. Returns `None`
+    /// if the counter already holds `count`.
+    pub(crate) fn count_to(
+        &self,
+        counters: &BTreeMap<String, PnCounter>,
+        path: &str,
+        count: i64,
+    ) -> Option<(String, Value)> {
+        let base = self.state.get(&path.to_string());
+        let mut slots = match counters.get(path) {
+            Some(slots) => slots.clone(),
+            None if base.is_none() => return Some((path.to_string(), Value::from(count))),
+            None => PnCounter::new(),
+        };
+
+        let current = base
+            .and_then(as_count)
+            .unwrap_or(0)
+            .saturating_add(slots.value());
+        let change = count.saturating_sub(current);
+        if change == 0 {
+            return None;
+        }
+        let actor = self.clock.actor_id();
+        let slot = slots.add(actor, change);
+        Some((slot_path(path, actor), slot_value(slot)))
+    }
+
+    /// Add `amount`, which may be negative, to the counter at `path`.
+    ///
+    /// Returns a delta representing this change.
+    #[must_use]
+    pub fn increment(&mut self, path: &str, amount: i64) -> Delta<String, Value> {
+        let actor = self.clock.actor_id();
+        let mut slots = counters(&self.state).remove(path).unwrap_or_default();
+        let slot = slots.add(actor, amount);
+        let timestamp = self.clock.tick();
+
+        let mut delta = Delta::new();
+        delta.add_insert(slot_path(path, actor), slot_value(slot), timestamp);
+        self.observe(&mut delta);
+        // A slot only grows, so its earlier writes are not lost values
+        delta.apply_to(&mut self.state);
+        self.version.observe(timestamp);
+        tracing::debug!(
+            doc_id = %self.id,
+            path,
+            amount,
+            timestamp = ?timestamp,
+            "Created delta (increment)"
+        );
+        delta
+    }
+
+    /// Select as counters the elements of `types` whose `semanticId` is
+    /// one of [`CrdtDocument::counter_semantic_ids`].
+    pub fn select_counters(&mut self, types: &ElementTypes) {
+        for (path, semantic_id) in types.semantic_ids() {
+            let selected = self.counter_semantic_ids.iter().any(|id| id == semantic_id);
+            if selected && !self.counter_paths.iter().any(|p| p.as_str() == path) {
+                self.counter_paths.push(PathPattern::new(path));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocId;
+    use crate::hlc::Hlc;
+    use crate::lists::indexed_writes;
+    use serde_json::json;
+
+    fn site() -> CrdtDocument {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        doc.counter_paths = vec![PathPattern::new("*Hours")];
+        doc
+    }
+
+    #[test]
+    fn counters_add_up_concurrent_server_changes() {
+        let mut site_a = site();
+        let mut site_b = site();
+
+        // Both sites start from the same server value
+        let server = BTreeMap::from([
+            ("OperatingHours".to_string(), json!(100)),
+            ("Status".to_string(), json!("Running")),
+        ]);
+        let seed_a = site_a.reconcile(&server);
+        let seed_b = site_b.reconcile(&server);
+        for delta in [&seed_a, &seed_b] {
+            site_a.apply_delta(delta).unwrap();
+            site_b.apply_delta(delta).unwrap();
+        }
+        assert_eq!(site_a.index_view(), server);
+
+        // Each server counts on while the sites are partitioned
+        let mut server_a = server.clone();
+        server_a.insert("OperatingHours".to_string(), json!("105"));
+        let from_a = site_a.reconcile(&server_a);
+        site_a.apply_delta(&from_a).unwrap();
+        let mut server_b = server.clone();
+        server_b.insert("OperatingHours".to_string(), json!(97));
+        let from_b = site_b.reconcile(&server_b);
+        site_b.apply_delta(&from_b).unwrap();
+        let increment = site_b.increment("OperatingHours", 1);
+
+        for delta in [&from_b, &increment] {
+            site_a.apply_delta(delta).unwrap();
+        }
+        site_b.apply_delta(&from_a).unwrap();
+        assert_eq!(site_a.index_view(), site_b.index_view());
+        assert_eq!(site_a.index_view()["OperatingHours"], json!(103));
+
+        // Egress writes the sum, not the slot
+        let writes = indexed_writes(&site_b.state, &from_a);
+        assert_eq!(writes.delta.inserts.len(), 1);
+        assert_eq!(writes.delta.inserts[0].0, "OperatingHours");
+        assert_eq!(writes.delta.inserts[0].1, json!(103));
+
+        // An unchanged value changes nothing; a removed counter goes whole
+        let mut server = site_a.index_view();
+        assert!(site_a.reconcile(&server).is_empty());
+        server.remove("OperatingHours");
+        let removal = site_a.reconcile(&server);
+        site_a.apply_delta(&removal).unwrap();
+        assert_eq!(site_a.index_view(), server);
+        let writes = indexed_writes(&site_a.state, &removal);
+        assert_eq!(writes.delta.removes.len(), 1);
+        assert_eq!(writes.delta.removes[0].0, "OperatingHours");
+    }
+
+    #[test]
+    fn counters_selected_by_semantic_id() {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        doc.counter_semantic_ids = vec!["urn:example:cycle-count".to_string()];
+
+        let mut types = ElementTypes::new();
+        types.insert_semantic_id("Press.Cycles", "urn:example:cycle-count");
+        types.insert_semantic_id("Press.Force", "urn:example:force");
+        doc.select_counters(&types);
+        doc.select_counters(&types);
+
+        assert_eq!(doc.counter_paths, vec![PathPattern::new("Press.Cycles")]);
+    }
+}
@@ -1,11 +1,13 @@
 //! CRDT primitives for AAS-ΔSync.
 //!
-//! Provides Last-Writer-Wins registers and Observed-Remove Maps
-//! adapted for AAS Submodel semantics.
+//! Provides Last-Writer-Wins registers, Observed-Remove Maps and
+//! positive-negative counters adapted for AAS Submodel semantics.
 
 use crate::hlc::Timestamp;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
 
 /// A Last-Writer-Wins register holding a value with a timestamp.
 ///
@@ -46,6 +48,17 @@ impl<T: Clone> LwwRegister<T> {
         }
     }
 
+    /// Merge with another register, returning the write that lost.
+    ///
+    /// Returns `None` if both hold the same write.
+    pub fn resolve(&mut self, other: Self) -> Option<Self> {
+        match other.timestamp.cmp(&self.timestamp) {
+            Ordering::Greater => Some(std::mem::replace(self, other)),
+            Ordering::Less => Some(other),
+            Ordering::Equal => None,
+        }
+    }
+
     /// Generate a delta representing the current state.
     #[must_use]
     pub fn to_delta(&self) -> RegisterDelta<T>
@@ -68,10 +81,155 @@ pub struct RegisterDelta<T> {
     pub timestamp: Timestamp,
 }
 
+/// One actor's share of a [`PnCounter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CounterSlot {
+    /// Total of the actor's increments
+    pub increments: u64,
+    /// Total of the actor's decrements
+    pub decrements: u64,
+}
+
+/// A positive-negative counter.
+///
+/// Each actor counts its own increments and decrements in a
+/// [`CounterSlot`] whose totals only grow. Merging keeps the larger totals
+/// of each slot, so changes made concurrently at different sites all add
+/// up instead of overwriting each other.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PnCounter {
+    slots: BTreeMap<Uuid, CounterSlot>,
+}
+
+impl PnCounter {
+    /// Create a counter at zero.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `amount`, which may be negative, to the counter as `actor`.
+    ///
+    /// Returns the actor's updated slot, which is the delta to replicate.
+    pub fn add(&mut self, actor: Uuid, amount: i64) -> CounterSlot {
+        let slot = self.slots.entry(actor).or_default();
+        if amount >= 0 {
+            slot.increments = slot.increments.saturating_add(amount.unsigned_abs());
+        } else {
+            slot.decrements = slot.decrements.saturating_add(amount.unsigned_abs());
+        }
+        *slot
+    }
+
+    /// Merge one actor's slot, keeping the larger totals.
+    pub fn merge_slot(&mut self, actor: Uuid, slot: CounterSlot) {
+        let ours = self.slots.entry(actor).or_default();
+        ours.increments = ours.increments.max(slot.increments);
+        ours.decrements = ours.decrements.max(slot.decrements);
+    }
+
+    /// Merge with another counter.
+    pub fn merge(&mut self, other: &Self) {
+        for (actor, slot) in &other.slots {
+            self.merge_slot(*actor, *slot);
+        }
+    }
+
+    /// Get the slot of `actor`.
+    #[must_use]
+    pub fn slot(&self, actor: Uuid) -> CounterSlot {
+        self.slots.get(&actor).copied().unwrap_or_default()
+    }
+
+    /// Get the counter's value, saturating at the bounds of `i64`.
+    #[must_use]
+    pub fn value(&self) -> i64 {
+        let total: i128 = self
+            .slots
+            .values()
+            .map(|slot| i128::from(slot.increments) - i128::from(slot.decrements))
+            .sum();
+        i64::try_from(total).unwrap_or(if total < 0 { i64::MIN } else { i64::MAX })
+    }
+}
+
+/// A write dropped in favour of a newer write to the same key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Overwrite<K, V> {
+    /// Key both writes went to
+    pub key: K,
+    /// The write kept
+    pub winner: LwwRegister<V>,
+    /// The write dropped
+    pub loser: LwwRegister<V>,
+}
+
+/// A map key that can nest under other keys.
+pub trait PathKey: Sized {
+    /// Keys this key nests under, outermost first.
+    fn ancestors(&self) -> Vec<Self>;
+
+    /// Check if this key nests under `ancestor`.
+    fn is_descendant_of(&self, ancestor: &Self) -> bool;
+}
+
+/// Paths nest at every `.`, `[` and `#` separator of an idShortPath, so
+/// `Limits.min`, `Phases[2]` and `Phases[2]#position` all lie under their
+/// element.
+impl PathKey for String {
+    fn ancestors(&self) -> Vec<Self> {
+        self.match_indices(['.', '[', '#'])
+            .filter(|(i, _)| *i > 0)
+            .map(|(i, _)| self[..i].to_string())
+            .collect()
+    }
+
+    fn is_descendant_of(&self, ancestor: &Self) -> bool {
+        !ancestor.is_empty()
+            && self.starts_with(ancestor.as_str())
+            && matches!(
+                self.as_bytes().get(ancestor.len()),
+                Some(b'.' | b'[' | b'#')
+            )
+    }
+}
+
+/// How a removal treats inserts to the same key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoveSemantics {
+    /// A removal hides every insert with a lower timestamp, whether or not
+    /// the remover saw it
+    #[default]
+    LastWriterWins,
+    /// A removal hides only the inserts its replica had observed, so
+    /// concurrent inserts survive whatever their timestamps
+    ObservedRemove,
+}
+
+/// Identity of one insert under [`RemoveSemantics::ObservedRemove`].
+///
+/// A dot is an (actor, counter) pair numbering one actor's writes. An
+/// actor's HLC ticks strictly increase, so an insert's timestamp already
+/// is its dot and inserts need no extra tag on the wire.
+pub type Dot = Timestamp;
+
 /// An Observed-Remove Map keyed by path segments.
 ///
 /// Supports add, update, and remove operations with causal consistency.
 /// Removed entries are tracked by tombstones until compaction.
+///
+/// Removal is hierarchical: a tombstone also hides every descendant key
+/// (see [`PathKey`]) written before it. Descendants written after the
+/// removal, such as a child inserted concurrently with a newer timestamp,
+/// survive on their own.
+///
+/// By default removals compare timestamps ([`RemoveSemantics::LastWriterWins`]).
+/// With [`RemoveSemantics::ObservedRemove`] the map keeps the [`Dot`] of
+/// every live insert instead, and a removal drops only the dots its delta
+/// lists as observed (see [`OrMap::observe`]); each key shows the value of
+/// its latest live dot. Under last-writer-wins semantics, keys selected
+/// with [`OrMap::track_values`] keep their live dots the same way, while
+/// still showing the latest write.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OrMap<K, V>
 where
@@ -81,6 +239,17 @@ where
     entries: HashMap<K, MapEntry<V>>,
     /// Tombstones for removed entries (key -> removal timestamp)
     tombstones: HashMap<K, Timestamp>,
+    /// How removals treat concurrent inserts
+    #[serde(default)]
+    semantics: RemoveSemantics,
+    /// Live dots and their values per key, under observed-remove semantics
+    /// or for tracked keys
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    dots: HashMap<K, BTreeMap<Dot, V>>,
+    /// Dots removed after being observed per key, under observed-remove
+    /// semantics or for tracked keys
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    removed_dots: HashMap<K, BTreeSet<Dot>>,
 }
 
 /// An entry in the OR-Map with per-entry metadata.
@@ -94,7 +263,7 @@ pub struct MapEntry<V> {
 
 impl<K, V> Default for OrMap<K, V>
 where
-    K: Eq + std::hash::Hash + Clone,
+    K: Eq + std::hash::Hash + Clone + PathKey,
     V: Clone,
 {
     fn default() -> Self {
@@ -104,24 +273,58 @@ where
 
 impl<K, V> OrMap<K, V>
 where
-    K: Eq + std::hash::Hash + Clone,
+    K: Eq + std::hash::Hash + Clone + PathKey,
     V: Clone,
 {
     /// Create a new empty OR-Map.
     #[must_use]
     pub fn new() -> Self {
+        Self::with_semantics(RemoveSemantics::default())
+    }
+
+    /// Create a new empty OR-Map with the given removal semantics.
+    #[must_use]
+    pub fn with_semantics(semantics: RemoveSemantics) -> Self {
         Self {
             entries: HashMap::new(),
             tombstones: HashMap::new(),
+            semantics,
+            dots: HashMap::new(),
+            removed_dots: HashMap::new(),
         }
     }
 
+    /// Get the removal semantics of this map.
+    #[must_use]
+    pub fn semantics(&self) -> RemoveSemantics {
+        self.semantics
+    }
+
     /// Get a value by key.
     #[must_use]
     pub fn get(&self, key: &K) -> Option<&V> {
         self.entries.get(key).map(|e| &e.value.value)
     }
 
+    /// Get every live value of a key with its dot, oldest first.
+    ///
+    /// Under observed-remove semantics a key holds one value per write no
+    /// other write has superseded, so several values are concurrent
+    /// writes. Under last-writer-wins semantics only the current value is
+    /// kept, unless the key is tracked (see [`OrMap::track_values`]).
+    #[must_use]
+    pub fn values(&self, key: &K) -> Vec<(Dot, &V)> {
+        match self.dots.get(key).filter(|dots| !dots.is_empty()) {
+            Some(dots) => dots.iter().map(|(dot, value)| (*dot, value)).collect(),
+            None => self
+                .entries
+                .get(key)
+                .map(|entry| (entry.value.timestamp, &entry.value.value))
+                .into_iter()
+                .collect(),
+        }
+    }
+
     /// Get a mutable reference to an entry.
     #[must_use]
     pub fn get_entry(&self, key: &K) -> Option<&MapEntry<V>> {
@@ -134,40 +337,134 @@ where
         self.entries.contains_key(key)
     }
 
+    /// Keep every live value of `key` under last-writer-wins semantics.
+    ///
+    /// From now on the key keeps the dots of its writes as under
+    /// observed-remove semantics, so [`OrMap::values`] returns the
+    /// concurrent ones, while [`OrMap::get`] still returns the latest.
+    /// Every replica must track the same keys, before writing them. Under
+    /// observed-remove semantics every key is tracked already.
+    pub fn track_values(&mut self, key: &K) {
+        if self.semantics == RemoveSemantics::ObservedRemove || self.dots.contains_key(key) {
+            return;
+        }
+
+        let dots = self
+            .entries
+            .get(key)
+            .map(|entry| (entry.value.timestamp, entry.value.value.clone()))
+            .into_iter()
+            .collect();
+        self.dots.insert(key.clone(), dots);
+    }
+
+    /// Check if a key is live or has been removed, itself or with an
+    /// ancestor.
+    #[must_use]
+    pub fn has_seen(&self, key: &K) -> bool {
+        self.entries.contains_key(key) || self.removed_at(key).is_some()
+    }
+
+    /// Latest removal of `key` or any of its ancestors.
+    fn removed_at(&self, key: &K) -> Option<Timestamp> {
+        key.ancestors()
+            .iter()
+            .chain(std::iter::once(key))
+            .filter_map(|key| self.tombstones.get(key).copied())
+            .max()
+    }
+
     /// Insert or update a value.
     ///
     /// Returns `true` if this was an insert (vs update).
     pub fn insert(&mut self, key: K, value: V, timestamp: Timestamp) -> bool {
-        // Check if there's a tombstone that supersedes this insert
-        if let Some(&tombstone_ts) = self.tombstones.get(&key) {
-            if tombstone_ts >= timestamp {
-                // Removal happened after this insert, ignore
-                return false;
-            }
-            // Insert is newer, remove tombstone
-            self.tombstones.remove(&key);
+        self.write(key, value, timestamp, &mut Vec::new())
+    }
+
+    /// Insert or update a value, recording the write it drops.
+    fn write(
+        &mut self,
+        key: K,
+        value: V,
+        timestamp: Timestamp,
+        overwrites: &mut Vec<Overwrite<K, V>>,
+    ) -> bool {
+        if self.semantics == RemoveSemantics::ObservedRemove {
+            return self.insert_dot(&key, value, timestamp);
         }
 
-        let is_new = !self.entries.contains_key(&key);
+        // Check if a removal of the key or an ancestor supersedes this insert
+        if self.removed_at(&key).is_some_and(|ts| ts >= timestamp) {
+            // Removal happened after this insert, ignore
+            return false;
+        }
+        // The tombstone stays: it still hides older descendants arriving
+        // after this insert
 
-        self.entries
-            .entry(key)
-            .and_modify(|e| {
-                e.value.set(value.clone(), timestamp);
-            })
-            .or_insert_with(|| MapEntry {
-                value: LwwRegister::new(value, timestamp),
+        if self.dots.contains_key(&key) && !self.is_removed(&key, &timestamp) {
+            if let Some(dots) = self.dots.get_mut(&key) {
+                dots.insert(timestamp, value.clone());
+            }
+        }
+
+        let write = LwwRegister::new(value, timestamp);
+        let Some(entry) = self.entries.get_mut(&key) else {
+            let entry = MapEntry {
+                value: write,
                 created_at: timestamp,
-            });
+            };
+            self.entries.insert(key, entry);
+            return true;
+        };
 
-        is_new
+        if let Some(loser) = entry.value.resolve(write) {
+            overwrites.push(Overwrite {
+                key,
+                winner: entry.value.clone(),
+                loser,
+            });
+        }
+        false
     }
 
-    /// Remove a key.
+    /// Remove a key and the descendants written before `timestamp`.
+    ///
+    /// Under observed-remove semantics, the key and its descendants are
+    /// removed whatever their timestamps, as every dot held locally has
+    /// been observed.
     ///
     /// Returns the removed value if it existed.
     pub fn remove(&mut self, key: &K, timestamp: Timestamp) -> Option<V> {
-        // Record tombstone
+        self.record_removal(key, timestamp);
+
+        if self.semantics == RemoveSemantics::ObservedRemove {
+            let removed = self.get(key).cloned();
+            let observed = self.observed_dots(key, true);
+            self.discard(&observed);
+            return removed;
+        }
+
+        self.entries.retain(|entry_key, entry| {
+            !(entry_key.is_descendant_of(key) && timestamp > entry.value.timestamp)
+        });
+        for (dot_key, dots) in &mut self.dots {
+            if dot_key == key || dot_key.is_descendant_of(key) {
+                dots.retain(|dot, _| *dot > timestamp);
+            }
+        }
+
+        // Remove entry if tombstone supersedes it
+        if let Some(entry) = self.entries.get(key) {
+            if timestamp > entry.value.timestamp {
+                return self.entries.remove(key).map(|e| e.value.value);
+            }
+        }
+
+        None
+    }
+
+    /// Record a tombstone for `key`, keeping the latest removal.
+    fn record_removal(&mut self, key: &K, timestamp: Timestamp) {
         self.tombstones
             .entry(key.clone())
             .and_modify(|ts| {
@@ -176,60 +473,280 @@ where
                 }
             })
             .or_insert(timestamp);
+    }
 
-        // Remove entry if tombstone supersedes it
-        if let Some(entry) = self.entries.get(key) {
-            if timestamp > entry.value.timestamp {
-                return self.entries.remove(key).map(|e| e.value.value);
+    /// Check whether the dot of `key` was removed.
+    ///
+    /// One delta may stamp several keys with the same timestamp, so a dot
+    /// only identifies an insert together with its key.
+    fn is_removed(&self, key: &K, dot: &Dot) -> bool {
+        self.removed_dots
+            .get(key)
+            .is_some_and(|removed| removed.contains(dot))
+    }
+
+    /// Add the dot of an insert, unless it was already removed.
+    fn insert_dot(&mut self, key: &K, value: V, dot: Dot) -> bool {
+        if self.is_removed(key, &dot) {
+            return false;
+        }
+
+        let is_new = !self.entries.contains_key(key);
+        self.dots.entry(key.clone()).or_default().insert(dot, value);
+        self.materialize(key);
+        is_new
+    }
+
+    /// Drop observed dots, remembering them so late copies stay removed.
+    ///
+    /// Under last-writer-wins semantics only the dots of tracked keys are
+    /// kept, and the latest write stays shown.
+    fn discard(&mut self, observed: &[(K, Dot)]) {
+        for (key, dot) in observed {
+            if self.semantics != RemoveSemantics::ObservedRemove {
+                if let Some(dots) = self.dots.get_mut(key) {
+                    dots.remove(dot);
+                    self.removed_dots
+                        .entry(key.clone())
+                        .or_default()
+                        .insert(*dot);
+                }
+                continue;
+            }
+            self.removed_dots
+                .entry(key.clone())
+                .or_default()
+                .insert(*dot);
+            if let Some(dots) = self.dots.get_mut(key) {
+                dots.remove(dot);
             }
+            self.materialize(key);
+        }
+    }
+
+    /// Live dots of `key`, and of its descendants if `subtree` is set.
+    fn observed_dots(&self, key: &K, subtree: bool) -> Vec<(K, Dot)> {
+        self.dots
+            .iter()
+            .filter(|(dot_key, _)| *dot_key == key || (subtree && dot_key.is_descendant_of(key)))
+            .flat_map(|(dot_key, dots)| dots.keys().map(|dot| (dot_key.clone(), *dot)))
+            .collect()
+    }
+
+    /// Show the value of the latest live dot of `key`.
+    fn materialize(&mut self, key: &K) {
+        let entry = self.dots.get(key).and_then(|dots| {
+            let (&created_at, _) = dots.first_key_value()?;
+            let (&timestamp, value) = dots.last_key_value()?;
+            Some(MapEntry {
+                value: LwwRegister::new(value.clone(), timestamp),
+                created_at,
+            })
+        });
+        if let Some(entry) = entry {
+            self.entries.insert(key.clone(), entry);
+        } else {
+            self.dots.remove(key);
+            self.entries.remove(key);
         }
+    }
 
-        None
+    /// Record in `delta` the dots its writes supersede in this map.
+    ///
+    /// An insert supersedes the live dots of its key, a removal those of
+    /// its key and descendants. Where the delta is applied only these dots
+    /// are dropped, so inserts the writer never saw survive. Under
+    /// last-writer-wins semantics an insert records the timestamp of the
+    /// value it replaces, which only tells overwrites it saw from
+    /// concurrent ones, or the live dots of a tracked key.
+    pub fn observe(&self, delta: &mut Delta<K, V>) {
+        if self.semantics != RemoveSemantics::ObservedRemove {
+            let replaced: Vec<(K, Dot)> = delta
+                .inserts
+                .iter()
+                .flat_map(|(key, _, _)| match self.dots.get(key) {
+                    Some(dots) => dots.keys().map(|dot| (key.clone(), *dot)).collect(),
+                    None => self
+                        .entries
+                        .get(key)
+                        .map(|entry| (key.clone(), entry.value.timestamp))
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                })
+                .filter(|observed| !delta.observed.contains(observed))
+                .collect();
+            delta.observed.extend(replaced);
+            return;
+        }
+
+        let inserts = delta.inserts.iter().map(|(key, _, _)| (key, false));
+        let removes = delta.removes.iter().map(|(key, _)| (key, true));
+        let mut seen: BTreeSet<Dot> = delta.observed.iter().map(|(_, dot)| *dot).collect();
+        let mut observed = Vec::new();
+        for (key, subtree) in inserts.chain(removes) {
+            for (dot_key, dot) in self.observed_dots(key, subtree) {
+                if seen.insert(dot) {
+                    observed.push((dot_key, dot));
+                }
+            }
+        }
+        delta.observed.extend(observed);
     }
 
     /// Merge with another OR-Map.
-    pub fn merge(&mut self, other: &Self) {
+    ///
+    /// Returns the writes dropped in favour of newer writes to the same
+    /// key. Under observed-remove semantics the writes a snapshot's
+    /// replica superseded are dropped without being returned, as the
+    /// snapshot does not say what superseded them.
+    pub fn merge(&mut self, other: &Self) -> Vec<Overwrite<K, V>> {
         // Merge tombstones (keep latest)
         for (key, &other_ts) in &other.tombstones {
-            self.tombstones
-                .entry(key.clone())
-                .and_modify(|ts| {
-                    if other_ts > *ts {
-                        *ts = other_ts;
-                    }
-                })
-                .or_insert(other_ts);
+            self.record_removal(key, other_ts);
+        }
+
+        if self.semantics == RemoveSemantics::ObservedRemove {
+            self.merge_dots(other);
+            return Vec::new();
         }
 
+        self.merge_tracked(other);
+        let mut overwrites = Vec::new();
+
         // Merge entries
         for (key, other_entry) in &other.entries {
-            // Check if our tombstone supersedes this entry
-            if let Some(&tombstone_ts) = self.tombstones.get(key) {
-                if tombstone_ts >= other_entry.value.timestamp {
-                    continue;
-                }
+            // Check if our tombstones supersede this entry
+            if self
+                .removed_at(key)
+                .is_some_and(|ts| ts >= other_entry.value.timestamp)
+            {
+                continue;
             }
 
-            self.entries
-                .entry(key.clone())
-                .and_modify(|e| {
-                    e.value.merge(&other_entry.value);
-                    // Keep earliest created_at
-                    if other_entry.created_at < e.created_at {
-                        e.created_at = other_entry.created_at;
-                    }
-                })
-                .or_insert_with(|| other_entry.clone());
+            let Some(entry) = self.entries.get_mut(key) else {
+                self.entries.insert(key.clone(), other_entry.clone());
+                continue;
+            };
+            if let Some(loser) = entry.value.resolve(other_entry.value.clone()) {
+                overwrites.push(Overwrite {
+                    key: key.clone(),
+                    winner: entry.value.clone(),
+                    loser,
+                });
+            }
+            // Keep earliest created_at
+            if other_entry.created_at < entry.created_at {
+                entry.created_at = other_entry.created_at;
+            }
         }
 
         // Remove entries that are superseded by tombstones
-        self.entries.retain(|key, entry| {
-            if let Some(&tombstone_ts) = self.tombstones.get(key) {
-                entry.value.timestamp > tombstone_ts
-            } else {
-                true
+        let superseded: Vec<K> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                self.removed_at(key)
+                    .is_some_and(|ts| ts >= entry.value.timestamp)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in superseded {
+            self.entries.remove(&key);
+        }
+
+        overwrites
+    }
+
+    /// Merge the live dots of keys either map tracks under
+    /// last-writer-wins semantics, see [`OrMap::merge_dots`].
+    ///
+    /// A map not tracking such a key contributes its latest write as a
+    /// dot. Dots hidden by a tombstone are dropped.
+    fn merge_tracked(&mut self, other: &Self) {
+        for key in other.dots.keys() {
+            self.track_values(key);
+        }
+        let keys: Vec<K> = self.dots.keys().cloned().collect();
+        for key in keys {
+            if let Some(removed) = other.removed_dots.get(&key) {
+                self.removed_dots
+                    .entry(key.clone())
+                    .or_default()
+                    .extend(removed.iter().copied());
             }
-        });
+            let theirs: Vec<(Dot, V)> = match other.dots.get(&key) {
+                Some(dots) => dots
+                    .iter()
+                    .map(|(dot, value)| (*dot, value.clone()))
+                    .collect(),
+                None => other
+                    .entries
+                    .get(&key)
+                    .map(|entry| (entry.value.timestamp, entry.value.value.clone()))
+                    .into_iter()
+                    .collect(),
+            };
+            let hidden = self.removed_at(&key);
+            let removed = self.removed_dots.get(&key);
+            let Some(dots) = self.dots.get_mut(&key) else {
+                continue;
+            };
+            dots.extend(theirs);
+            dots.retain(|dot, _| {
+                !removed.is_some_and(|removed| removed.contains(dot))
+                    && hidden.map_or(true, |hidden| *dot > hidden)
+            });
+        }
+    }
+
+    /// Merge the dots of another map: the union of live dots, less every
+    /// dot either side removed.
+    ///
+    /// A map without dots contributes each entry's latest write as a dot.
+    fn merge_dots(&mut self, other: &Self) {
+        for (key, removed) in &other.removed_dots {
+            self.removed_dots
+                .entry(key.clone())
+                .or_default()
+                .extend(removed.iter().copied());
+        }
+
+        let other_dots: Vec<(&K, Dot, &V)> = if other.semantics == RemoveSemantics::ObservedRemove {
+            other
+                .dots
+                .iter()
+                .flat_map(|(key, dots)| dots.iter().map(move |(dot, value)| (key, *dot, value)))
+                .collect()
+        } else {
+            other
+                .entries
+                .iter()
+                .map(|(key, entry)| (key, entry.value.timestamp, &entry.value.value))
+                .collect()
+        };
+        for (key, dot, value) in other_dots {
+            if !self.is_removed(key, &dot) {
+                self.dots
+                    .entry(key.clone())
+                    .or_default()
+                    .insert(dot, value.clone());
+            }
+        }
+
+        for (key, dots) in &mut self.dots {
+            if let Some(removed) = self.removed_dots.get(key) {
+                dots.retain(|dot, _| !removed.contains(dot));
+            }
+        }
+        let keys: Vec<K> = self
+            .dots
+            .keys()
+            .chain(self.entries.keys())
+            .cloned()
+            .collect();
+        for key in keys {
+            self.materialize(&key);
+        }
     }
 
     /// Get an iterator over all entries.
@@ -254,6 +771,12 @@ where
     /// This is safe only after all peers have synced past the timestamp.
     pub fn compact_tombstones(&mut self, before: Timestamp) {
         self.tombstones.retain(|_, &mut ts| ts >= before);
+        // Every peer already holds an insert older than `before`, so no
+        // late copy of a removed dot can still arrive
+        self.removed_dots.retain(|_, removed| {
+            removed.retain(|&dot| dot >= before);
+            !removed.is_empty()
+        });
     }
 }
 
@@ -267,6 +790,14 @@ where
     pub inserts: Vec<(K, V, Timestamp)>,
     /// Removed keys
     pub removes: Vec<(K, Timestamp)>,
+    /// Dots the writes in this delta supersede; under last-writer-wins
+    /// semantics, the timestamps of the values its inserts replace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub observed: Vec<(K, Dot)>,
+    /// The author's latest timestamp before this delta, so receivers can
+    /// tell whether they missed one (see [`crate::VersionVector`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev: Option<Timestamp>,
 }
 
 impl<K, V> Default for Delta<K, V>
@@ -290,6 +821,8 @@ where
         Self {
             inserts: Vec::new(),
             removes: Vec::new(),
+            observed: Vec::new(),
+            prev: None,
         }
     }
 
@@ -309,14 +842,71 @@ where
         self.inserts.is_empty() && self.removes.is_empty()
     }
 
+    /// Get an iterator over the timestamps of every insert and removal.
+    pub fn timestamps(&self) -> impl Iterator<Item = Timestamp> + '_ {
+        self.inserts
+            .iter()
+            .map(|(_, _, timestamp)| *timestamp)
+            .chain(self.removes.iter().map(|(_, timestamp)| *timestamp))
+    }
+
     /// Apply this delta to an OR-Map.
-    pub fn apply_to(&self, map: &mut OrMap<K, V>) {
+    ///
+    /// Under observed-remove semantics removals drop only the observed
+    /// dots; the removed keys are recorded as tombstones.
+    ///
+    /// Returns the writes dropped in favour of newer writes to the same
+    /// key. Under observed-remove semantics observed dots are dropped
+    /// without being returned; instead each insert returns the live dots
+    /// of its key it did not observe, paired with the insert so the later
+    /// write wins, as only the later one is shown.
+    pub fn apply_to(&self, map: &mut OrMap<K, V>) -> Vec<Overwrite<K, V>>
+    where
+        K: PathKey,
+    {
+        let mut overwrites = Vec::new();
+        if map.semantics == RemoveSemantics::ObservedRemove {
+            map.discard(&self.observed);
+            for (key, value, timestamp) in &self.inserts {
+                map.insert(key.clone(), value.clone(), *timestamp);
+                let Some(dots) = map
+                    .dots
+                    .get(key)
+                    .filter(|dots| dots.contains_key(timestamp))
+                else {
+                    continue;
+                };
+                let write = LwwRegister::new(value.clone(), *timestamp);
+                overwrites.extend(dots.iter().filter(|(dot, _)| *dot != timestamp).map(
+                    |(dot, other)| {
+                        let other = LwwRegister::new(other.clone(), *dot);
+                        let (winner, loser) = if dot < timestamp {
+                            (write.clone(), other)
+                        } else {
+                            (other, write.clone())
+                        };
+                        Overwrite {
+                            key: key.clone(),
+                            winner,
+                            loser,
+                        }
+                    },
+                ));
+            }
+            for (key, timestamp) in &self.removes {
+                map.record_removal(key, *timestamp);
+            }
+            return overwrites;
+        }
+
+        map.discard(&self.observed);
         for (key, value, timestamp) in &self.inserts {
-            map.insert(key.clone(), value.clone(), *timestamp);
+            map.write(key.clone(), value.clone(), *timestamp, &mut overwrites);
         }
         for (key, timestamp) in &self.removes {
             map.remove(key, *timestamp);
         }
+        overwrites
     }
 }
 
@@ -409,6 +999,287 @@ mod tests {
         assert_eq!(merged_b.get(&"x".to_string()), Some(&20));
     }
 
+    #[test]
+    fn ormap_remove_hides_descendants() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 1);
+        let t3 = make_timestamp(3000, 0, 1);
+
+        let mut map: OrMap<String, i32> = OrMap::new();
+        map.insert("Motor.Serial".to_string(), 1, t1);
+        map.insert("Motor.Phases[a]".to_string(), 2, t1);
+        map.insert("Motor.Phases[a]#position".to_string(), 3, t1);
+        map.insert("MotorSpeed".to_string(), 4, t1);
+
+        map.remove(&"Motor".to_string(), t2);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"MotorSpeed".to_string()), Some(&4));
+        assert!(map.has_seen(&"Motor.Serial".to_string()));
+        assert!(!map.has_seen(&"MotorSpeed.Unit".to_string()));
+
+        // Late delivery of an older child stays hidden
+        map.insert("Motor.Weight".to_string(), 5, t1);
+        assert!(map.get(&"Motor.Weight".to_string()).is_none());
+
+        // A replacement component written after the removal is kept
+        map.insert("Motor.Serial".to_string(), 6, t3);
+        assert_eq!(map.get(&"Motor.Serial".to_string()), Some(&6));
+    }
+
+    #[test]
+    fn ormap_reinsert_keeps_descendants_hidden() {
+        // A property turns into a collection and back: its old child, the
+        // removal and the re-insert arrive in any order
+        let child = ("Motor.Weight".to_string(), make_timestamp(1000, 0, 1));
+        let removal = make_timestamp(2000, 0, 2);
+        let reinsert = make_timestamp(3000, 0, 2);
+
+        let mut delivered = Vec::new();
+        for child_first in [true, false] {
+            let mut map: OrMap<String, i32> = OrMap::new();
+            if child_first {
+                map.insert(child.0.clone(), 1, child.1);
+            }
+            map.remove(&"Motor".to_string(), removal);
+            map.insert("Motor".to_string(), 7, reinsert);
+            if !child_first {
+                map.insert(child.0.clone(), 1, child.1);
+            }
+
+            assert_eq!(map.get(&child.0), None);
+            assert_eq!(map.get(&"Motor".to_string()), Some(&7));
+            delivered.push(map);
+        }
+        assert_eq!(delivered[0], delivered[1]);
+    }
+
+    #[test]
+    fn ormap_concurrent_insert_under_removed_parent() {
+        // Site 1 deletes the collection while site 2 adds a child to it
+        let removal = make_timestamp(2000, 0, 1);
+        let earlier_child = make_timestamp(1500, 0, 2);
+        let later_child = make_timestamp(2500, 0, 2);
+
+        let mut base: OrMap<String, i32> = OrMap::new();
+        base.insert("Motor.Serial".to_string(), 1, make_timestamp(1000, 0, 1));
+
+        for (child_ts, survives) in [(earlier_child, false), (later_child, true)] {
+            let mut site_1 = base.clone();
+            let mut site_2 = base.clone();
+            site_1.remove(&"Motor".to_string(), removal);
+            site_2.insert("Motor.Weight".to_string(), 2, child_ts);
+
+            let mut merged_1 = site_1.clone();
+            merged_1.merge(&site_2);
+            let mut merged_2 = site_2.clone();
+            merged_2.merge(&site_1);
+            assert_eq!(merged_1, merged_2);
+
+            // The child's fate follows timestamp order, not delivery order
+            let mut delta: Delta<String, i32> = Delta::new();
+            delta.add_insert("Motor.Weight".to_string(), 2, child_ts);
+            let mut applied = site_1.clone();
+            delta.apply_to(&mut applied);
+
+            for map in [&merged_1, &applied] {
+                assert_eq!(map.get(&"Motor.Weight".to_string()).is_some(), survives);
+                assert!(map.get(&"Motor.Serial".to_string()).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn ormap_observed_remove_drops_only_observed_dots() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 2);
+        let t3 = make_timestamp(3000, 0, 1);
+
+        let mut base: OrMap<String, i32> = OrMap::with_semantics(RemoveSemantics::ObservedRemove);
+        base.insert("x".to_string(), 1, t1);
+
+        // Site 1 removes x having seen t1, site 2 concurrently updates it
+        let mut removal: Delta<String, i32> = Delta::new();
+        removal.add_remove("x".to_string(), t3);
+        base.observe(&mut removal);
+        assert_eq!(removal.observed, vec![("x".to_string(), t1)]);
+        let mut update: Delta<String, i32> = Delta::new();
+        update.add_insert("x".to_string(), 2, t2);
+        base.observe(&mut update);
+
+        let mut site_1 = base.clone();
+        removal.apply_to(&mut site_1);
+        assert!(site_1.get(&"x".to_string()).is_none());
+        let mut site_2 = base.clone();
+        update.apply_to(&mut site_2);
+
+        // The unseen update survives the later removal, in any order
+        let mut merged = site_1.clone();
+        merged.merge(&site_2);
+        update.apply_to(&mut site_1);
+        removal.apply_to(&mut site_2);
+        for map in [&merged, &site_1, &site_2] {
+            assert_eq!(map.get(&"x".to_string()), Some(&2));
+            assert_eq!(map, &merged);
+        }
+
+        // A late copy of the removed insert stays removed
+        let mut late: Delta<String, i32> = Delta::new();
+        late.add_insert("x".to_string(), 1, t1);
+        late.apply_to(&mut merged);
+        assert_eq!(merged.get(&"x".to_string()), Some(&2));
+    }
+
+    #[test]
+    fn ormap_observed_remove_keys_removed_dots_by_path() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 2);
+
+        // One delta stamps both keys with the same timestamp
+        let mut inserts: Delta<String, i32> = Delta::new();
+        inserts.add_insert("a".to_string(), 1, t1);
+        inserts.add_insert("b".to_string(), 2, t1);
+        let mut source: OrMap<String, i32> = OrMap::with_semantics(RemoveSemantics::ObservedRemove);
+        inserts.apply_to(&mut source);
+
+        let mut removal: Delta<String, i32> = Delta::new();
+        removal.add_remove("a".to_string(), t2);
+        source.observe(&mut removal);
+        removal.apply_to(&mut source);
+
+        // The removal of a reaches a replica before the inserts
+        let mut replica: OrMap<String, i32> =
+            OrMap::with_semantics(RemoveSemantics::ObservedRemove);
+        removal.apply_to(&mut replica);
+        let mut merged = replica.clone();
+        inserts.apply_to(&mut replica);
+        merged.merge(&source);
+
+        for map in [&source, &replica, &merged] {
+            assert!(map.get(&"a".to_string()).is_none());
+            assert_eq!(map.get(&"b".to_string()), Some(&2));
+        }
+    }
+
+    #[test]
+    fn ormap_tracked_keys_keep_concurrent_values_under_lww() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 2);
+        let t3 = make_timestamp(3000, 0, 1);
+        let t4 = make_timestamp(4000, 0, 2);
+        let key = "x".to_string();
+
+        let mut map: OrMap<String, i32> = OrMap::new();
+        map.track_values(&key);
+        map.insert(key.clone(), 1, t1);
+        map.insert(key.clone(), 2, t2);
+        assert_eq!(map.get(&key), Some(&2));
+        assert_eq!(map.values(&key), vec![(t1, &1), (t2, &2)]);
+
+        // A removal drops every older value, not the ones after it
+        map.insert(key.clone(), 4, t4);
+        map.remove(&key, t3);
+        assert_eq!(map.values(&key), vec![(t4, &4)]);
+
+        // Untracked keys keep only the latest write
+        map.insert("y".to_string(), 1, t1);
+        map.insert("y".to_string(), 2, t2);
+        assert_eq!(map.values(&"y".to_string()), vec![(t2, &2)]);
+    }
+
+    #[test]
+    fn ormap_observed_remove_merges_lww_snapshots() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 1);
+
+        let mut lww: OrMap<String, i32> = OrMap::new();
+        lww.insert("Motor.Serial".to_string(), 1, t1);
+
+        let mut map: OrMap<String, i32> = OrMap::with_semantics(RemoveSemantics::ObservedRemove);
+        map.merge(&lww);
+        assert_eq!(map.get(&"Motor.Serial".to_string()), Some(&1));
+
+        assert_eq!(map.remove(&"Motor".to_string(), t2), None);
+        assert!(map.is_empty());
+        map.merge(&lww);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn ormap_reports_overwritten_writes() {
+        let t1 = make_timestamp(1000, 0, 1);
+        let t2 = make_timestamp(2000, 0, 2);
+        let t3 = make_timestamp(3000, 0, 1);
+        let key = "x".to_string();
+        let overwrite = |winner: (i32, Timestamp), loser: (i32, Timestamp)| Overwrite {
+            key: key.clone(),
+            winner: LwwRegister::new(winner.0, winner.1),
+            loser: LwwRegister::new(loser.0, loser.1),
+        };
+
+        let mut map: OrMap<String, i32> = OrMap::new();
+        map.insert(key.clone(), 1, t1);
+        let mut update: Delta<String, i32> = Delta::new();
+        update.add_insert(key.clone(), 2, t2);
+        assert_eq!(update.apply_to(&mut map), vec![overwrite((2, t2), (1, t1))]);
+        // A duplicate drops nothing, a stale write drops itself
+        assert!(update.apply_to(&mut map).is_empty());
+        let mut stale: Delta<String, i32> = Delta::new();
+        stale.add_insert(key.clone(), 0, t1);
+        assert_eq!(stale.apply_to(&mut map), vec![overwrite((2, t2), (0, t1))]);
+
+        let mut other: OrMap<String, i32> = OrMap::new();
+        other.insert(key.clone(), 3, t3);
+        assert_eq!(map.merge(&other), vec![overwrite((3, t3), (2, t2))]);
+
+        // Under observed-remove semantics, an insert drops the dots it
+        // observed, and hides or is hidden by those it did not
+        let mut map: OrMap<String, i32> = OrMap::with_semantics(RemoveSemantics::ObservedRemove);
+        map.insert(key.clone(), 1, t2);
+        let mut update: Delta<String, i32> = Delta::new();
+        update.add_insert(key.clone(), 2, t1);
+        map.observe(&mut update);
+        assert!(update.apply_to(&mut map).is_empty());
+        let mut concurrent: Delta<String, i32> = Delta::new();
+        concurrent.add_insert(key.clone(), 3, t3);
+        assert_eq!(
+            concurrent.apply_to(&mut map),
+            vec![overwrite((3, t3), (2, t1))]
+        );
+        assert_eq!(map.get(&key), Some(&3));
+    }
+
+    #[test]
+    fn pn_counter_adds_concurrent_changes() {
+        let site_a = Uuid::from_bytes([1; 16]);
+        let site_b = Uuid::from_bytes([2; 16]);
+
+        let mut counter_a = PnCounter::new();
+        counter_a.add(site_a, 5);
+        let mut counter_b = counter_a.clone();
+
+        // Both sites change the counter concurrently
+        counter_a.add(site_a, 3);
+        let slot_b = counter_b.add(site_b, -2);
+        assert_eq!(
+            slot_b,
+            CounterSlot {
+                increments: 0,
+                decrements: 2
+            }
+        );
+
+        let mut merged = counter_a.clone();
+        merged.merge(&counter_b);
+        counter_b.merge(&counter_a);
+        assert_eq!(merged, counter_b);
+        assert_eq!(merged.value(), 6);
+
+        // A stale copy of a slot changes nothing
+        merged.merge_slot(site_a, CounterSlot::default());
+        assert_eq!(merged.value(), 6);
+        assert_eq!(merged.slot(site_a).increments, 8);
+    }
+
     #[test]
     fn delta_apply() {
         let t1 = make_timestamp(1000, 0, 1);
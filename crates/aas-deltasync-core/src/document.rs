@@ -4,9 +4,14 @@
 //! - Keys are canonical idShortPath strings
 //! - Values are LWW registers holding JSON values
 
-use crate::crdt::{Delta, OrMap};
-use crate::hlc::Hlc;
+use crate::counters::{as_count, counters_within, is_slot, slot_counter};
+use crate::crdt::{Delta, LwwRegister, OrMap, Overwrite, RemoveSemantics};
+use crate::hlc::{ClockError, Hlc};
+use crate::lists::{is_position, ListIds};
+use crate::merge::PathPattern;
+use crate::version_vector::VersionVector;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// The type of view being replicated.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
@@ -59,17 +64,6 @@ impl DocId {
     pub fn value_view(aas_id: impl Into<String>, submodel_id: impl Into<String>) -> Self {
         Self::new(aas_id, submodel_id, View::Value)
     }
-
-    /// Generate a hash for MQTT topic sharding.
-    #[must_use]
-    pub fn topic_hash(&self) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
-        format!("{:016x}", hasher.finish())
-    }
 }
 
 impl std::fmt::Display for DocId {
@@ -78,11 +72,21 @@ impl std::fmt::Display for DocId {
     }
 }
 
+/// A multi-value path holding concurrent writes no later write resolved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Index path of the element, as adapters address it
+    pub path: String,
+    /// Each concurrent write, oldest first
+    pub values: Vec<LwwRegister<serde_json::Value>>,
+}
+
 /// A CRDT-backed AAS document.
 ///
 /// The document state is an OR-Map where:
 /// - Keys are canonical idShortPath strings (e.g., "TechnicalProperties.MaxTemperature")
 /// - Values are JSON values serialized as strings
+#[derive(Debug)]
 pub struct CrdtDocument {
     /// Document identifier
     pub id: DocId,
@@ -90,16 +94,41 @@ pub struct CrdtDocument {
     pub state: OrMap<String, serde_json::Value>,
     /// Hybrid logical clock for this document
     pub clock: Hlc,
+    /// Highest timestamp applied per actor
+    pub version: VersionVector,
+    /// Paths whose concurrent writes are kept as conflicts, see
+    /// [`CrdtDocument::conflicts`]
+    pub multi_value_paths: Vec<PathPattern>,
+    /// Paths whose concurrent changes add up, see [`crate::counters`]
+    pub counter_paths: Vec<PathPattern>,
+    /// `semanticId`s of elements whose concurrent changes add up, see
+    /// [`CrdtDocument::select_counters`]
+    pub counter_semantic_ids: Vec<String>,
+    /// Writes dropped since the last [`CrdtDocument::take_overwrites`]
+    overwrites: Vec<Overwrite<String, serde_json::Value>>,
 }
 
 impl CrdtDocument {
     /// Create a new empty document.
     #[must_use]
     pub fn new(id: DocId, clock: Hlc) -> Self {
+        Self::with_semantics(id, clock, RemoveSemantics::default())
+    }
+
+    /// Create a new empty document with the given removal semantics.
+    ///
+    /// Every replica of a document must use the same semantics.
+    #[must_use]
+    pub fn with_semantics(id: DocId, clock: Hlc, semantics: RemoveSemantics) -> Self {
         Self {
             id,
-            state: OrMap::new(),
+            state: OrMap::with_semantics(semantics),
             clock,
+            version: VersionVector::new(),
+            multi_value_paths: Vec::new(),
+            counter_paths: Vec::new(),
+            counter_semantic_ids: Vec::new(),
+            overwrites: Vec::new(),
         }
     }
 
@@ -113,11 +142,14 @@ impl CrdtDocument {
         value: serde_json::Value,
     ) -> Delta<String, serde_json::Value> {
         let timestamp = self.clock.tick();
-        self.state
-            .insert(path.to_string(), value.clone(), timestamp);
-
+        self.track_multi_values([&path.to_string()]);
         let mut delta = Delta::new();
         delta.add_insert(path.to_string(), value, timestamp);
+        self.observe(&mut delta);
+        let overwrites = delta.apply_to(&mut self.state);
+        let seen = self.version.clone();
+        self.record_delta_overwrites(&delta, overwrites, &seen);
+        self.version.observe(timestamp);
         tracing::debug!(
             doc_id = %self.id,
             path,
@@ -139,10 +171,13 @@ impl CrdtDocument {
     #[must_use]
     pub fn remove(&mut self, path: &str) -> Delta<String, serde_json::Value> {
         let timestamp = self.clock.tick();
-        self.state.remove(&path.to_string(), timestamp);
-
         let mut delta = Delta::new();
         delta.add_remove(path.to_string(), timestamp);
+        self.observe(&mut delta);
+        let overwrites = delta.apply_to(&mut self.state);
+        let seen = self.version.clone();
+        self.record_delta_overwrites(&delta, overwrites, &seen);
+        self.version.observe(timestamp);
         tracing::debug!(
             doc_id = %self.id,
             path,
@@ -152,18 +187,42 @@ impl CrdtDocument {
         delta
     }
 
+    /// Record in a locally built delta the dots its writes supersede, and
+    /// the latest timestamp of ours it follows.
+    ///
+    /// Must be called before the delta is applied or published; see
+    /// [`OrMap::observe`].
+    pub fn observe(&self, delta: &mut Delta<String, serde_json::Value>) {
+        self.state.observe(delta);
+        delta.prev = self.version.get(&self.clock.actor_id());
+    }
+
     /// Apply a delta from another replica.
-    pub fn apply_delta(&mut self, delta: &Delta<String, serde_json::Value>) {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClockError::TooFarAhead`] and applies nothing if any of
+    /// the delta's timestamps is further ahead than the clock's maximum
+    /// offset.
+    pub fn apply_delta(
+        &mut self,
+        delta: &Delta<String, serde_json::Value>,
+    ) -> Result<(), ClockError> {
+        delta
+            .timestamps()
+            .try_for_each(|timestamp| self.clock.check(timestamp))?;
+
         let before_len = self.state.len();
-        // Update clock based on delta timestamps
-        for (_, _, timestamp) in &delta.inserts {
-            self.clock.update(*timestamp);
-        }
-        for (_, timestamp) in &delta.removes {
-            self.clock.update(*timestamp);
+        self.track_multi_values(delta.inserts.iter().map(|(key, _, _)| key));
+        let seen = self.version.clone();
+        // Update clock and version vector based on delta timestamps
+        for timestamp in delta.timestamps() {
+            self.clock.update(timestamp)?;
+            self.version.observe_after(timestamp, delta.prev);
         }
 
-        delta.apply_to(&mut self.state);
+        let overwrites = delta.apply_to(&mut self.state);
+        self.record_delta_overwrites(delta, overwrites, &seen);
         let after_len = self.state.len();
         tracing::debug!(
             doc_id = %self.id,
@@ -173,12 +232,292 @@ impl CrdtDocument {
             after_len,
             "Applied delta"
         );
+        Ok(())
     }
 
     /// Merge with another document's state.
-    pub fn merge(&mut self, other: &Self) {
-        self.clock.update(other.clock.current());
-        self.state.merge(&other.state);
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClockError::TooFarAhead`] and merges nothing if the other
+    /// document's clock is further ahead than the maximum offset.
+    pub fn merge(&mut self, other: &Self) -> Result<(), ClockError> {
+        self.clock.update(other.clock.current())?;
+        let overwrites = self.state.merge(&other.state);
+        self.record_merge_overwrites(overwrites, &other.version);
+        self.version.merge(&other.version);
+        Ok(())
+    }
+
+    /// Merge a state snapshot covering `version` into this document.
+    ///
+    /// The clock is advanced past every timestamp in `version`, so later
+    /// local writes order after everything the snapshot contains.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClockError::TooFarAhead`] and merges nothing if any
+    /// timestamp in `version` is further ahead than the clock's maximum
+    /// offset.
+    pub fn merge_snapshot(
+        &mut self,
+        state: &OrMap<String, serde_json::Value>,
+        version: &VersionVector,
+    ) -> Result<(), ClockError> {
+        version
+            .iter()
+            .try_for_each(|timestamp| self.clock.check(*timestamp))?;
+        for timestamp in version.iter() {
+            self.clock.update(*timestamp)?;
+        }
+        let overwrites = self.state.merge(state);
+        self.record_merge_overwrites(overwrites, version);
+        self.version.merge(version);
+        Ok(())
+    }
+
+    /// Build the delta that turns this document into `target`.
+    ///
+    /// Paths that are new or hold a different value become inserts, paths
+    /// missing from `target` become removes, each stamped with a fresh
+    /// tick. A counter whose value changed instead gets the difference
+    /// added to the local actor's slot, see [`crate::counters`]. Values
+    /// are compared with [`same_value`]. The document itself is left
+    /// unchanged until the delta is applied.
+    #[must_use]
+    pub fn reconcile(
+        &mut self,
+        target: &BTreeMap<String, serde_json::Value>,
+    ) -> Delta<String, serde_json::Value> {
+        let ids = ListIds::from_state(&self.state);
+        self.reconcile_within(&ids, target, |_| true)
+    }
+
+    /// Build the delta that turns the paths `in_scope` selects into
+    /// `target`, see [`CrdtDocument::reconcile`].
+    ///
+    /// Paths outside the scope are neither compared nor removed, so a
+    /// change to a few elements does not diff the whole document. A
+    /// counter's slots are in scope with the counter.
+    pub(crate) fn reconcile_within(
+        &mut self,
+        ids: &ListIds,
+        target: &BTreeMap<String, serde_json::Value>,
+        in_scope: impl Fn(&str) -> bool,
+    ) -> Delta<String, serde_json::Value> {
+        let mut delta = Delta::new();
+        let counters = counters_within(&self.state, &in_scope);
+
+        for (path, value) in target {
+            if counters.contains_key(path) || self.selects_counter(ids, path) {
+                let Some(count) = as_count(value) else {
+                    tracing::warn!(doc_id = %self.id, path, "Ignoring non-integer counter value");
+                    continue;
+                };
+                if let Some((entry, value)) = self.count_to(&counters, path, count) {
+                    delta.add_insert(entry, value, self.clock.tick());
+                }
+                continue;
+            }
+            if !self
+                .state
+                .get(path)
+                .is_some_and(|current| same_value(current, value))
+            {
+                delta.add_insert(path.clone(), value.clone(), self.clock.tick());
+            }
+        }
+
+        let mut stale: Vec<String> = self
+            .paths()
+            .filter(|path| in_scope(path) && !target.contains_key(*path))
+            .filter(|path| slot_counter(path).map_or(true, |counter| !target.contains_key(counter)))
+            .cloned()
+            .collect();
+        stale.sort();
+        for path in stale {
+            delta.add_remove(path, self.clock.tick());
+        }
+
+        delta
+    }
+
+    /// Build the delta that adds the entries of `seed` this document has
+    /// never seen.
+    ///
+    /// Paths that are live or were removed are left alone, so state already
+    /// replicated from peers wins over the seed. Each insert is stamped with
+    /// a fresh tick; the document is left unchanged until the delta is
+    /// applied.
+    #[must_use]
+    pub fn seed(
+        &mut self,
+        seed: &BTreeMap<String, serde_json::Value>,
+    ) -> Delta<String, serde_json::Value> {
+        let mut delta = Delta::new();
+        for (path, value) in seed {
+            if !self.state.has_seen(path) {
+                delta.add_insert(path.clone(), value.clone(), self.clock.tick());
+            }
+        }
+        delta
+    }
+
+    /// Multi-value paths holding more than one concurrent value.
+    ///
+    /// Concurrent writes are told apart by their dots, which
+    /// last-writer-wins documents keep for multi-value paths only (see
+    /// [`OrMap::track_values`]). A conflict lasts until a write to the
+    /// path that observes and so supersedes every value. Until then
+    /// [`get`](Self::get) returns the latest value.
+    #[must_use]
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        if self.multi_value_paths.is_empty() {
+            return Vec::new();
+        }
+
+        let ids = ListIds::from_state(&self.state);
+        let mut conflicts: Vec<Conflict> = self
+            .paths()
+            .filter(|path| !is_position(path) && !is_slot(path))
+            .filter_map(|path| {
+                let values = self.state.values(path);
+                if values.len() < 2 {
+                    return None;
+                }
+                let path = ids.index_path(path)?;
+                if !self.multi_value_paths.iter().any(|p| p.matches(&path)) {
+                    return None;
+                }
+                let values = values
+                    .into_iter()
+                    .map(|(dot, value)| LwwRegister::new(value.clone(), dot))
+                    .collect();
+                Some(Conflict { path, values })
+            })
+            .collect();
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+        conflicts
+    }
+
+    /// Take the concurrent writes dropped in favour of newer writes since
+    /// the last call, in the order they were dropped.
+    ///
+    /// Every write, merge or applied delta that settles a path by
+    /// timestamp records the value it dropped, unless the winning write was
+    /// made after seeing it: a replica's later writes to a path, and writes
+    /// recording the value they replace (see [`CrdtDocument::observe`]),
+    /// are plain updates. Under observed-remove semantics a value is
+    /// dropped from view by a concurrent write; multi-value paths keep both
+    /// and report them as [`conflicts`](Self::conflicts) instead. Paths are
+    /// index paths, as adapters address them. Callers that audit writes
+    /// should take them regularly, as they are kept until taken.
+    pub fn take_overwrites(&mut self) -> Vec<Overwrite<String, serde_json::Value>> {
+        std::mem::take(&mut self.overwrites)
+    }
+
+    /// Keep the writes `delta` dropped without having seen them.
+    ///
+    /// A write is seen if the delta records it as replaced, or if it came
+    /// with the delta but was covered by `seen`, the version before the
+    /// delta, and so was settled when first applied.
+    fn record_delta_overwrites(
+        &mut self,
+        delta: &Delta<String, serde_json::Value>,
+        overwrites: Vec<Overwrite<String, serde_json::Value>>,
+        seen: &VersionVector,
+    ) {
+        let concurrent = overwrites
+            .into_iter()
+            .filter(|overwrite| {
+                let loser = overwrite.loser.timestamp;
+                let replaced = delta
+                    .observed
+                    .iter()
+                    .any(|(key, dot)| *key == overwrite.key && *dot == loser);
+                let redelivered =
+                    seen.contains(&loser) && delta.timestamps().any(|timestamp| timestamp == loser);
+                !replaced && !redelivered
+            })
+            .collect();
+        self.record_overwrites(concurrent);
+    }
+
+    /// Keep the writes a merge with state covering `version` dropped
+    /// without having seen them.
+    ///
+    /// Each side holds the writes it has seen, so a write both this
+    /// document and the merged state had seen was settled before.
+    fn record_merge_overwrites(
+        &mut self,
+        overwrites: Vec<Overwrite<String, serde_json::Value>>,
+        version: &VersionVector,
+    ) {
+        let concurrent = overwrites
+            .into_iter()
+            .filter(|overwrite| {
+                let loser = overwrite.loser.timestamp;
+                !(self.version.contains(&loser) && version.contains(&loser))
+            })
+            .collect();
+        self.record_overwrites(concurrent);
+    }
+
+    /// Keep the values of the multi-value paths among `keys`, before they
+    /// are written.
+    ///
+    /// Observed-remove documents keep every value already. A list entry
+    /// is matched once its list is known.
+    fn track_multi_values<'a>(&mut self, keys: impl IntoIterator<Item = &'a String>) {
+        if self.multi_value_paths.is_empty()
+            || self.state.semantics() == RemoveSemantics::ObservedRemove
+        {
+            return;
+        }
+
+        let ids = ListIds::from_state(&self.state);
+        for key in keys {
+            if is_position(key) || is_slot(key) {
+                continue;
+            }
+            let multi_value = ids
+                .index_path(key)
+                .is_some_and(|path| self.multi_value_paths.iter().any(|p| p.matches(&path)));
+            if multi_value {
+                self.state.track_values(key);
+            }
+        }
+    }
+
+    /// Keep dropped writes for [`CrdtDocument::take_overwrites`].
+    ///
+    /// A replica's writes to a path follow each other, so one dropping
+    /// another of the same actor is never concurrent.
+    fn record_overwrites(&mut self, overwrites: Vec<Overwrite<String, serde_json::Value>>) {
+        if overwrites.is_empty() {
+            return;
+        }
+
+        let ids = ListIds::from_state(&self.state);
+        for mut overwrite in overwrites {
+            if is_position(&overwrite.key) || is_slot(&overwrite.key) {
+                continue;
+            }
+            if overwrite.winner.timestamp.actor_id == overwrite.loser.timestamp.actor_id {
+                continue;
+            }
+            if let Some(path) = ids.index_path(&overwrite.key) {
+                overwrite.key = path;
+            }
+            if self
+                .multi_value_paths
+                .iter()
+                .any(|p| p.matches(&overwrite.key))
+            {
+                continue;
+            }
+            self.overwrites.push(overwrite);
+        }
     }
 
     /// Get all paths in the document.
@@ -199,6 +538,25 @@ impl CrdtDocument {
     }
 }
 
+/// Compare a document value with one read from a server.
+///
+/// `$value` carries typed scalars while the normal serialization, which
+/// `BaSyx` events use, carries property values as strings, so a string
+/// matches a scalar with the same text.
+#[must_use]
+pub fn same_value(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    a == b || matches!((scalar_text(a), scalar_text(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// Text of a scalar as it appears in the normal serialization.
+fn scalar_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,10 +597,211 @@ mod tests {
         let delta_b = doc_b.set("X", serde_json::json!(20));
 
         // Cross-apply deltas
-        doc_a.apply_delta(&delta_b);
-        doc_b.apply_delta(&delta_a);
+        doc_a.apply_delta(&delta_b).unwrap();
+        doc_b.apply_delta(&delta_a).unwrap();
 
         // Should converge (deterministic based on timestamp + actor)
         assert_eq!(doc_a.get("X"), doc_b.get("X"));
+
+        // Both replicas have seen both actors
+        assert_eq!(doc_a.version, doc_b.version);
+        assert_eq!(doc_a.version.len(), 2);
+    }
+
+    #[test]
+    fn crdt_document_records_only_concurrent_overwrites() {
+        let id = DocId::value_view("aas1", "sm1");
+        let mut doc_a = CrdtDocument::new(id.clone(), Hlc::new(Uuid::new_v4()));
+        let mut doc_b = CrdtDocument::new(id, Hlc::new(Uuid::new_v4()));
+
+        // Updates made after seeing the value they replace are not recorded
+        let first = doc_a.set("X", serde_json::json!(1));
+        doc_b.apply_delta(&first).unwrap();
+        let second = doc_b.set("X", serde_json::json!(2));
+        doc_a.apply_delta(&second).unwrap();
+        let third = doc_a.set("X", serde_json::json!(3));
+        doc_b.apply_delta(&third).unwrap();
+        doc_b.apply_delta(&first).unwrap();
+        assert!(doc_a.take_overwrites().is_empty());
+        assert!(doc_b.take_overwrites().is_empty());
+
+        // Concurrent updates are, alike on both replicas
+        let from_a = doc_a.set("X", serde_json::json!(4));
+        let from_b = doc_b.set("X", serde_json::json!(5));
+        doc_a.apply_delta(&from_b).unwrap();
+        doc_b.apply_delta(&from_a).unwrap();
+        let overwrites = doc_a.take_overwrites();
+        assert_eq!(overwrites.len(), 1);
+        assert_eq!(overwrites, doc_b.take_overwrites());
+
+        // Merging state both replicas hold records nothing again
+        doc_a.merge_snapshot(&doc_b.state, &doc_b.version).unwrap();
+        assert!(doc_a.take_overwrites().is_empty());
+    }
+
+    #[test]
+    fn crdt_document_merge_snapshot() {
+        let id = DocId::value_view("aas1", "sm1");
+
+        let mut doc_a = CrdtDocument::new(id.clone(), Hlc::new(Uuid::new_v4()));
+        let mut doc_b = CrdtDocument::new(id, Hlc::new(Uuid::new_v4()));
+
+        let _ = doc_a.set("X", serde_json::json!(10));
+        let _ = doc_b.set("Y", serde_json::json!(20));
+
+        doc_b.merge_snapshot(&doc_a.state, &doc_a.version).unwrap();
+
+        assert_eq!(doc_b.get("X"), Some(&serde_json::json!(10)));
+        assert_eq!(doc_b.get("Y"), Some(&serde_json::json!(20)));
+        assert!(doc_b.version.dominates(&doc_a.version));
+        assert!(doc_b.clock.current() > doc_a.clock.current());
+    }
+
+    #[test]
+    fn crdt_document_reconcile() {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        let _ = doc.set("Kept", serde_json::json!(1));
+        let _ = doc.set("Changed", serde_json::json!(2));
+        let _ = doc.set("Dropped", serde_json::json!(3));
+
+        let target = BTreeMap::from([
+            ("Kept".to_string(), serde_json::json!(1)),
+            ("Changed".to_string(), serde_json::json!(20)),
+            ("Added".to_string(), serde_json::json!(4)),
+        ]);
+
+        let delta = doc.reconcile(&target);
+        assert_eq!(delta.inserts.len(), 2);
+        assert_eq!(delta.removes.len(), 1);
+
+        doc.apply_delta(&delta).unwrap();
+        let state: BTreeMap<String, serde_json::Value> = doc
+            .state
+            .iter()
+            .map(|(path, value)| (path.clone(), value.clone()))
+            .collect();
+        assert_eq!(state, target);
+        assert!(doc.reconcile(&target).is_empty());
+
+        // The normal serialization's string values match typed scalars
+        let normal = BTreeMap::from([
+            ("Kept".to_string(), serde_json::json!("1")),
+            ("Changed".to_string(), serde_json::json!("20")),
+            ("Added".to_string(), serde_json::json!("4")),
+        ]);
+        assert!(doc.reconcile(&normal).is_empty());
+    }
+
+    #[test]
+    fn crdt_document_seed_keeps_existing_state() {
+        let mut doc = CrdtDocument::new(DocId::value_view("aas1", "sm1"), Hlc::new(Uuid::new_v4()));
+        let _ = doc.set("Replicated", serde_json::json!(1));
+        let _ = doc.set("Removed", serde_json::json!(2));
+        let _ = doc.remove("Removed");
+
+        let seed = BTreeMap::from([
+            ("Replicated".to_string(), serde_json::json!(10)),
+            ("Removed".to_string(), serde_json::json!(20)),
+            ("Fresh".to_string(), serde_json::json!(30)),
+        ]);
+
+        let delta = doc.seed(&seed);
+        assert_eq!(delta.inserts.len(), 1);
+        assert_eq!(delta.inserts[0].0, "Fresh");
+
+        doc.apply_delta(&delta).unwrap();
+        assert_eq!(doc.get("Replicated"), Some(&serde_json::json!(1)));
+        assert_eq!(doc.get("Removed"), None);
+        assert!(doc.seed(&seed).is_empty());
+    }
+
+    #[test]
+    fn crdt_document_multi_value_conflicts_until_resolved() {
+        for semantics in [
+            RemoveSemantics::LastWriterWins,
+            RemoveSemantics::ObservedRemove,
+        ] {
+            let id = DocId::value_view("aas1", "sm1");
+            let new_site = || {
+                let mut doc =
+                    CrdtDocument::with_semantics(id.clone(), Hlc::new(Uuid::new_v4()), semantics);
+                doc.multi_value_paths = vec![PathPattern::new("Setpoints.*")];
+                doc
+            };
+            let (mut site_a, mut site_b) = (new_site(), new_site());
+
+            // Both sites change the setpoint and the speed concurrently
+            let from_a = site_a.set("Setpoints.Temperature", serde_json::json!(80));
+            let speed_a = site_a.set("Speed", serde_json::json!(1200));
+            let from_b = site_b.set("Setpoints.Temperature", serde_json::json!(85));
+            let speed_b = site_b.set("Speed", serde_json::json!(1500));
+            for delta in [&from_b, &speed_b] {
+                site_a.apply_delta(delta).unwrap();
+            }
+            for delta in [&from_a, &speed_a] {
+                site_b.apply_delta(delta).unwrap();
+            }
+
+            let conflicts = site_a.conflicts();
+            assert_eq!(conflicts, site_b.conflicts());
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].path, "Setpoints.Temperature");
+            let mut values: Vec<_> = conflicts[0]
+                .values
+                .iter()
+                .map(|v| v.value.clone())
+                .collect();
+            values.sort_by_key(serde_json::Value::as_i64);
+            assert_eq!(values, vec![serde_json::json!(80), serde_json::json!(85)]);
+
+            // A replica joining from a snapshot sees the conflict too
+            let mut site_c = new_site();
+            site_c
+                .merge_snapshot(&site_a.state, &site_a.version)
+                .unwrap();
+            assert_eq!(site_c.conflicts(), conflicts);
+
+            // An operator's write on one site resolves it everywhere
+            let resolution = site_b.set("Setpoints.Temperature", serde_json::json!(82));
+            site_a.apply_delta(&resolution).unwrap();
+            site_c.apply_delta(&resolution).unwrap();
+            for doc in [&site_a, &site_b, &site_c] {
+                assert!(doc.conflicts().is_empty());
+                assert_eq!(
+                    doc.get("Setpoints.Temperature"),
+                    Some(&serde_json::json!(82))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn crdt_document_observed_remove_survives_clock_skew() {
+        for (semantics, weight) in [
+            (RemoveSemantics::LastWriterWins, None),
+            (RemoveSemantics::ObservedRemove, Some(serde_json::json!(12))),
+        ] {
+            let id = DocId::value_view("aas1", "sm1");
+            let mut gateway =
+                CrdtDocument::with_semantics(id.clone(), Hlc::new(Uuid::new_v4()), semantics);
+            let mut site = CrdtDocument::with_semantics(id, Hlc::new(Uuid::new_v4()), semantics);
+            site.apply_delta(&gateway.set("Motor.Serial", serde_json::json!("A1")))
+                .unwrap();
+
+            // The gateway's clock runs an hour ahead when it deletes the motor,
+            // while the site concurrently records its weight
+            let mut skewed = gateway.clock.current();
+            skewed.physical_ms += 3_600_000;
+            gateway.clock.update(skewed).unwrap();
+            let removal = gateway.remove("Motor");
+            let addition = site.set("Motor.Weight", serde_json::json!(12));
+
+            gateway.apply_delta(&addition).unwrap();
+            site.apply_delta(&removal).unwrap();
+            for doc in [&gateway, &site] {
+                assert_eq!(doc.get("Motor.Serial"), None);
+                assert_eq!(doc.get("Motor.Weight"), weight.as_ref());
+            }
+        }
     }
 }
@@ -5,6 +5,11 @@
 //! - Logical counter for events at the same physical time
 //! - Actor ID for deterministic tiebreaking
 //!
+//! A clock may bound how far ahead of its wall clock a received timestamp
+//! can be, see [`Hlc::set_max_offset`]. Without the bound one peer whose
+//! clock runs a year fast drags every clock it reaches a year forward, and
+//! its writes win every last-writer-wins conflict in the meantime.
+//!
 //! # References
 //!
 //! Kulkarni, Demirbas, et al. "Logical Physical Clocks and Consistent Snapshots
@@ -12,7 +17,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 /// A globally unique timestamp combining physical time, logical counter, and actor ID.
@@ -115,17 +120,35 @@ impl PartialOrd for Timestamp {
 pub struct Hlc {
     /// Current timestamp state
     last: Timestamp,
+    /// How far ahead of the wall clock received timestamps may be
+    max_offset: Option<Duration>,
 }
 
 impl Hlc {
     /// Create a new HLC with the given actor ID.
+    ///
+    /// The clock accepts received timestamps however far ahead they are
+    /// until a maximum offset is set.
     #[must_use]
     pub fn new(actor_id: Uuid) -> Self {
         Self {
             last: Timestamp::now(actor_id),
+            max_offset: None,
         }
     }
 
+    /// Get how far ahead of the wall clock received timestamps may be.
+    #[must_use]
+    pub fn max_offset(&self) -> Option<Duration> {
+        self.max_offset
+    }
+
+    /// Set how far ahead of the wall clock received timestamps may be, or
+    /// `None` to accept any.
+    pub fn set_max_offset(&mut self, max_offset: Option<Duration>) {
+        self.max_offset = max_offset;
+    }
+
     /// Get the actor ID for this clock.
     #[must_use]
     pub fn actor_id(&self) -> Uuid {
@@ -145,7 +168,7 @@ impl Hlc {
             self.last.logical = 0;
         } else {
             // Wall clock hasn't advanced, increment logical counter
-            self.last.logical = self.last.logical.saturating_add(1);
+            self.advance(self.last.physical_ms, self.last.logical);
         }
 
         self.last
@@ -154,8 +177,14 @@ impl Hlc {
     /// Update the clock upon receiving a remote timestamp.
     ///
     /// Ensures the local clock advances past the received timestamp.
-    pub fn update(&mut self, received: Timestamp) {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClockError::TooFarAhead`] and leaves the clock unchanged if
+    /// the timestamp is further ahead than the maximum offset.
+    pub fn update(&mut self, received: Timestamp) -> Result<(), ClockError> {
         let now_ms = current_time_ms();
+        self.check_at(received, now_ms)?;
 
         if now_ms > self.last.physical_ms && now_ms > received.physical_ms {
             // Wall clock is ahead of both, use it
@@ -163,14 +192,57 @@ impl Hlc {
             self.last.logical = 0;
         } else if self.last.physical_ms == received.physical_ms {
             // Same physical time, take max logical and increment
-            self.last.logical = self.last.logical.max(received.logical).saturating_add(1);
+            self.advance(
+                self.last.physical_ms,
+                self.last.logical.max(received.logical),
+            );
         } else if received.physical_ms > self.last.physical_ms {
             // Received is ahead, sync to it
-            self.last.physical_ms = received.physical_ms;
-            self.last.logical = received.logical.saturating_add(1);
+            self.advance(received.physical_ms, received.logical);
         } else {
             // Local is ahead, just increment
-            self.last.logical = self.last.logical.saturating_add(1);
+            self.advance(self.last.physical_ms, self.last.logical);
+        }
+        Ok(())
+    }
+
+    /// Check that a received timestamp is within the maximum offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClockError::TooFarAhead`] if the timestamp is further ahead
+    /// of the wall clock than the maximum offset.
+    pub fn check(&self, received: Timestamp) -> Result<(), ClockError> {
+        self.check_at(received, current_time_ms())
+    }
+
+    fn check_at(&self, received: Timestamp, now_ms: u64) -> Result<(), ClockError> {
+        let Some(max_offset) = self.max_offset else {
+            return Ok(());
+        };
+        let max_offset_ms = u64::try_from(max_offset.as_millis()).unwrap_or(u64::MAX);
+        let ahead_ms = received.physical_ms.saturating_sub(now_ms);
+        if ahead_ms > max_offset_ms {
+            return Err(ClockError::TooFarAhead {
+                actor_id: received.actor_id,
+                ahead_ms,
+                max_offset_ms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Move to the timestamp following `(physical_ms, logical)`.
+    ///
+    /// An exhausted logical counter carries into the physical time rather
+    /// than stalling, which would hand out the same timestamp again.
+    fn advance(&mut self, physical_ms: u64, logical: u32) {
+        if let Some(logical) = logical.checked_add(1) {
+            self.last.physical_ms = physical_ms;
+            self.last.logical = logical;
+        } else {
+            self.last.physical_ms = physical_ms.saturating_add(1);
+            self.last.logical = 0;
         }
     }
 
@@ -194,6 +266,21 @@ pub enum TimestampError {
     },
 }
 
+/// Errors that can occur when receiving a timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ClockError {
+    /// The timestamp is further ahead of the wall clock than allowed
+    #[error("timestamp from {actor_id} is {ahead_ms} ms ahead, more than the {max_offset_ms} ms allowed")]
+    TooFarAhead {
+        /// Actor whose clock produced the timestamp
+        actor_id: Uuid,
+        /// How far ahead of the wall clock the timestamp is
+        ahead_ms: u64,
+        /// Maximum offset allowed
+        max_offset_ms: u64,
+    },
+}
+
 /// Get current wall clock time in milliseconds since UNIX epoch.
 fn current_time_ms() -> u64 {
     let millis = SystemTime::now()
@@ -232,13 +319,57 @@ mod tests {
         let t_a = hlc_a.tick();
 
         // B receives it and updates
-        hlc_b.update(t_a);
+        hlc_b.update(t_a).unwrap();
 
         // B's next timestamp should be ahead of A's
         let t_b = hlc_b.tick();
         assert!(t_b > t_a);
     }
 
+    #[test]
+    fn hlc_rejects_timestamps_beyond_max_offset() {
+        let mut hlc = Hlc::new(Uuid::new_v4());
+        hlc.set_max_offset(Some(Duration::from_secs(60)));
+        let before = hlc.current();
+
+        // A peer whose clock runs a year fast
+        let mut ahead = Timestamp::now(Uuid::new_v4());
+        ahead.physical_ms += 365 * 24 * 3_600_000;
+        let err = hlc.update(ahead).unwrap_err();
+        assert!(matches!(
+            err,
+            ClockError::TooFarAhead { actor_id, max_offset_ms: 60_000, .. }
+                if actor_id == ahead.actor_id
+        ));
+        assert_eq!(hlc.current(), before);
+
+        // Skew within the offset is absorbed as before
+        ahead.physical_ms = current_time_ms() + 30_000;
+        hlc.update(ahead).unwrap();
+        assert!(hlc.tick() > ahead);
+
+        hlc.set_max_offset(None);
+        ahead.physical_ms = current_time_ms() + 365 * 24 * 3_600_000;
+        assert!(hlc.update(ahead).is_ok());
+    }
+
+    #[test]
+    fn hlc_logical_overflow_carries_into_physical_time() {
+        let mut hlc = Hlc::new(Uuid::new_v4());
+        let received = Timestamp {
+            physical_ms: current_time_ms() + 3_600_000,
+            logical: u32::MAX,
+            actor_id: Uuid::new_v4(),
+        };
+
+        hlc.update(received).unwrap();
+        let t1 = hlc.current();
+        let t2 = hlc.tick();
+        assert!(received < t1);
+        assert!(t1 < t2);
+        assert_eq!(t1.physical_ms, received.physical_ms + 1);
+    }
+
     #[test]
     fn timestamp_serialization_roundtrip() {
         let ts = Timestamp {
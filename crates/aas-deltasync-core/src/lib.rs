@@ -4,18 +4,32 @@
 //!
 //! This crate provides:
 //! - Hybrid Logical Clock (HLC) for globally ordered timestamps
+//! - Version vectors summarizing causal history for anti-entropy
 //! - CRDT primitives (LWW registers, OR-Map) adapted for AAS semantics
 //! - Document model mapping AAS Submodels to CRDT structures
+//! - Content-addressed references for attachments
+//! - Stable IDs for list entries, translated to and from indices
+//! - Counters adding up numeric properties changed at several sites
 //! - Merge algorithms with deterministic conflict resolution
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
+pub mod blob;
+pub mod counters;
 pub mod crdt;
 pub mod document;
 pub mod hlc;
+pub mod lists;
 pub mod merge;
+pub mod version_vector;
 
-pub use crdt::{Delta, LwwRegister, OrMap};
-pub use document::{CrdtDocument, DocId, View};
-pub use hlc::{Hlc, Timestamp};
+pub use blob::{content_hash, BlobRef};
+pub use crdt::{
+    CounterSlot, Delta, Dot, LwwRegister, OrMap, Overwrite, PathKey, PnCounter, RemoveSemantics,
+};
+pub use document::{same_value, Conflict, CrdtDocument, DocId, View};
+pub use hlc::{ClockError, Hlc, Timestamp};
+pub use lists::{indexed_writes, IndexedWrites, ListIds};
+pub use merge::{ElementType, ElementTypes, MergeStrategy, PathPattern};
+pub use version_vector::VersionVector;
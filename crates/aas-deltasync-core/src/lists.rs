@@ -0,0 +1,736 @@
+//! Stable identities for `SubmodelElementList` entries.
+//!
+//! Adapters address list entries by index, `Phases[2]`, and an index shifts
+//! whenever an entry is inserted or removed ahead of it. Documents key list
+//! entries by a stable ID instead, `Phases[9c1e…]`, and order them by a
+//! position entry replicated alongside, `Phases[9c1e…]#position`. Positions
+//! are fractional keys, so an entry fits between any two others, and entries
+//! inserted concurrently at the same spot are ordered by ID.
+//!
+//! [`ListIds`] is the index ↔ ID table derived from the position entries.
+//! Index-addressed changes enter a document through
+//! [`CrdtDocument::reconcile_indexed`] or [`CrdtDocument::stable_delta`],
+//! which match list entries to existing IDs by content, and leave it
+//! through [`indexed_writes`].
+
+use crate::counters::{counter_write, counters, fold_counters_within, is_slot};
+use crate::crdt::{Delta, OrMap};
+use crate::document::CrdtDocument;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+/// Suffix of the entry holding a list entry's position.
+pub const POSITION_SUFFIX: &str = "#position";
+
+/// Check if a document path holds a list entry's position.
+#[must_use]
+pub fn is_position(path: &str) -> bool {
+    path.ends_with(POSITION_SUFFIX)
+}
+
+/// Split a position path into its list path and entry ID.
+fn position_entry(path: &str) -> Option<(&str, &str)> {
+    let entry = path.strip_suffix(POSITION_SUFFIX)?.strip_suffix(']')?;
+    let open = entry.rfind('[')?;
+    Some((&entry[..open], &entry[open + 1..]))
+}
+
+fn position_path(list: &str, id: &str) -> String {
+    format!("{list}[{id}]{POSITION_SUFFIX}")
+}
+
+/// The element a change to `path` is scoped to: its outermost list, as
+/// aligning a list looks at all its entries, or else the path itself
+/// without a position or counter slot suffix.
+///
+/// Stable and index paths of an entry share their scope.
+fn scope_of(path: &str) -> &str {
+    path.find(['[', '#']).map_or(path, |end| &path[..end])
+}
+
+/// Index ↔ stable-ID table of a document's lists.
+#[derive(Debug, Clone, Default)]
+pub struct ListIds {
+    /// Entry IDs in list order, keyed by the stable path of the list
+    lists: BTreeMap<String, Vec<String>>,
+}
+
+impl ListIds {
+    /// Build the table from the position entries of a document state.
+    #[must_use]
+    pub fn from_state(state: &OrMap<String, Value>) -> Self {
+        let mut positioned: BTreeMap<String, Vec<(&str, &str)>> = BTreeMap::new();
+        for (path, position) in state.iter() {
+            if let Some((list, id)) = position_entry(path) {
+                positioned
+                    .entry(list.to_string())
+                    .or_default()
+                    .push((position.as_str().unwrap_or_default(), id));
+            }
+        }
+
+        let lists = positioned
+            .into_iter()
+            .map(|(list, mut entries)| {
+                entries.sort_unstable();
+                let ids = entries.into_iter().map(|(_, id)| id.to_string()).collect();
+                (list, ids)
+            })
+            .collect();
+        Self { lists }
+    }
+
+    /// Entry IDs of a list, in order.
+    #[must_use]
+    pub fn entries(&self, list: &str) -> &[String] {
+        self.lists.get(list).map_or(&[], Vec::as_slice)
+    }
+
+    /// Translate a stable path to the index path adapters use.
+    ///
+    /// Returns `None` if the path names an entry no list holds.
+    #[must_use]
+    pub fn index_path(&self, path: &str) -> Option<String> {
+        self.translate(path, true, |ids, id| {
+            ids.iter()
+                .position(|entry| entry == id)
+                .map(|i| i.to_string())
+        })
+    }
+
+    /// Translate an index path to the stable path a document uses.
+    ///
+    /// Returns `None` if an index is past the end of its list.
+    #[must_use]
+    pub fn stable_path(&self, path: &str) -> Option<String> {
+        self.translate(path, false, |ids, index| {
+            ids.get(index.parse::<usize>().ok()?).cloned()
+        })
+    }
+
+    /// Rewrite each bracketed segment of `path`, looking its list up by
+    /// stable path.
+    fn translate(
+        &self,
+        path: &str,
+        stable_input: bool,
+        segment: impl Fn(&[String], &str) -> Option<String>,
+    ) -> Option<String> {
+        let mut out = String::with_capacity(path.len());
+        let mut consumed = 0;
+        while let Some(open) = path[consumed..].find('[').map(|i| consumed + i) {
+            let close = open + path[open..].find(']')?;
+            out.push_str(&path[consumed..open]);
+            let list = if stable_input {
+                &path[..open]
+            } else {
+                out.as_str()
+            };
+            let translated = segment(self.entries(list), &path[open + 1..close])?;
+            out.push('[');
+            out.push_str(&translated);
+            out.push(']');
+            consumed = close + 1;
+        }
+        out.push_str(&path[consumed..]);
+        Some(out)
+    }
+}
+
+/// Writes an index-addressed server needs after a delta was applied.
+#[derive(Debug, Default)]
+pub struct IndexedWrites {
+    /// Changed entries, addressed by index
+    pub delta: Delta<String, Value>,
+    /// Index paths of lists whose entries were inserted, removed or
+    /// reordered; these are written whole
+    pub lists: BTreeSet<String>,
+}
+
+/// Translate a delta applied to `state` into index-addressed writes.
+///
+/// Entries of lists whose structure changed are left out of the delta, as
+/// rewriting the list covers them. A changed counter slot or base writes
+/// the counter's value instead.
+#[must_use]
+pub fn indexed_writes(state: &OrMap<String, Value>, delta: &Delta<String, Value>) -> IndexedWrites {
+    let ids = ListIds::from_state(state);
+    let paths = delta
+        .inserts
+        .iter()
+        .map(|(path, _, _)| path)
+        .chain(delta.removes.iter().map(|(path, _)| path));
+
+    let changed: BTreeSet<&str> = paths
+        .filter_map(|path| position_entry(path).map(|(list, _)| list))
+        .collect();
+    let under = |list: &str, path: &str| {
+        path.strip_prefix(list)
+            .is_some_and(|rest| rest.starts_with('['))
+    };
+    let outermost: Vec<&str> = changed
+        .iter()
+        .copied()
+        .filter(|list| !changed.iter().any(|outer| under(outer, list)))
+        .collect();
+    let rewritten = |path: &str| outermost.iter().any(|list| under(list, path));
+
+    let mut writes = IndexedWrites {
+        delta: Delta::new(),
+        lists: outermost
+            .iter()
+            .filter_map(|list| ids.index_path(list))
+            .collect(),
+    };
+
+    // A changed slot or base writes its counter's value, once per counter
+    let counters = counters(state);
+    let mut written = BTreeSet::new();
+    let edited = delta
+        .inserts
+        .iter()
+        .map(|(path, _, timestamp)| (path, *timestamp))
+        .chain(
+            delta
+                .removes
+                .iter()
+                .map(|(path, timestamp)| (path, *timestamp)),
+        );
+    for (path, timestamp) in edited {
+        let Some((counter, value)) = counter_write(state, &counters, path) else {
+            continue;
+        };
+        if rewritten(path) || !written.insert(counter) {
+            continue;
+        }
+        let Some(counter) = ids.index_path(counter) else {
+            continue;
+        };
+        match value {
+            Some(value) => writes.delta.add_insert(counter, value, timestamp),
+            None => writes.delta.add_remove(counter, timestamp),
+        }
+    }
+    let skipped = |path: &str| {
+        is_position(path) || is_slot(path) || written.contains(path) || rewritten(path)
+    };
+
+    for (path, value, timestamp) in &delta.inserts {
+        if skipped(path) {
+            continue;
+        }
+        if let Some(path) = ids.index_path(path) {
+            writes.delta.add_insert(path, value.clone(), *timestamp);
+        }
+    }
+    for (path, timestamp) in &delta.removes {
+        if skipped(path) {
+            continue;
+        }
+        if let Some(path) = ids.index_path(path) {
+            writes.delta.add_remove(path, *timestamp);
+        }
+    }
+    writes
+}
+
+impl CrdtDocument {
+    /// The document's entries as adapters address them: list entries by
+    /// index and without position entries, and counters by their value
+    /// instead of their slots.
+    #[must_use]
+    pub fn index_view(&self) -> BTreeMap<String, Value> {
+        let ids = ListIds::from_state(&self.state);
+        self.index_view_within(&ids, |_| true)
+    }
+
+    /// The [`CrdtDocument::index_view`] of the paths `in_scope` selects.
+    fn index_view_within(
+        &self,
+        ids: &ListIds,
+        in_scope: impl Fn(&str) -> bool,
+    ) -> BTreeMap<String, Value> {
+        fold_counters_within(&self.state, in_scope)
+            .into_iter()
+            .filter(|(path, _)| !is_position(path))
+            .filter_map(|(path, value)| Some((ids.index_path(&path)?, value)))
+            .collect()
+    }
+
+    /// Build the delta that turns this document into an index-addressed
+    /// `target`.
+    ///
+    /// Each list's entries are aligned with the entries it already holds by
+    /// content, so an entry inserted or removed ahead of others does not
+    /// move their IDs. Unmatched entries between the same neighbours are
+    /// taken as edited in place; the rest get fresh IDs. See
+    /// [`CrdtDocument::reconcile`].
+    #[must_use]
+    pub fn reconcile_indexed(&mut self, target: &BTreeMap<String, Value>) -> Delta<String, Value> {
+        let ids = ListIds::from_state(&self.state);
+        let view = self.index_view_within(&ids, |_| true);
+        self.reconcile_aligned(&ids, &view, target, |_| true)
+    }
+
+    /// Build the delta that turns the paths `in_scope` selects into an
+    /// index-addressed `target`, given the document's `ids` and the
+    /// [`CrdtDocument::index_view`] of at least those paths.
+    fn reconcile_aligned(
+        &mut self,
+        ids: &ListIds,
+        view: &BTreeMap<String, Value>,
+        target: &BTreeMap<String, Value>,
+        in_scope: impl Fn(&str) -> bool,
+    ) -> Delta<String, Value> {
+        let mut aligned: BTreeMap<String, BTreeMap<usize, String>> = BTreeMap::new();
+        let mut stable = BTreeMap::new();
+
+        'paths: for (path, value) in target {
+            let mut out = String::with_capacity(path.len());
+            let mut consumed = 0;
+            while let Some(open) = path[consumed..].find('[').map(|i| consumed + i) {
+                let Some(close) = path[open..].find(']').map(|i| open + i) else {
+                    continue 'paths;
+                };
+                let Ok(index) = path[open + 1..close].parse::<usize>() else {
+                    continue 'paths;
+                };
+                out.push_str(&path[consumed..open]);
+                if !aligned.contains_key(&out) {
+                    let list = ListAlignment {
+                        state: &self.state,
+                        ids,
+                        view,
+                        target,
+                    };
+                    let entries = list.align(&out, &path[..open], &mut stable);
+                    aligned.insert(out.clone(), entries);
+                }
+                let Some(id) = aligned[&out].get(&index) else {
+                    continue 'paths;
+                };
+                out.push('[');
+                out.push_str(id);
+                out.push(']');
+                consumed = close + 1;
+            }
+            out.push_str(&path[consumed..]);
+            stable.insert(out, value.clone());
+        }
+
+        self.reconcile_within(ids, &stable, in_scope)
+    }
+
+    /// Build the delta applying an index-addressed delta to this document.
+    ///
+    /// The delta is applied to the [`CrdtDocument::index_view`] and the
+    /// result reconciled with [`CrdtDocument::reconcile_indexed`], so its
+    /// timestamps are replaced with fresh ticks. Only the paths the delta
+    /// touches are compared, and of lists, the lists it touches.
+    #[must_use]
+    pub fn stable_delta(&mut self, indexed: &Delta<String, Value>) -> Delta<String, Value> {
+        let scopes: BTreeSet<&str> = indexed
+            .inserts
+            .iter()
+            .map(|(path, _, _)| scope_of(path))
+            .chain(indexed.removes.iter().map(|(path, _)| scope_of(path)))
+            .collect();
+        if scopes.is_empty() {
+            return Delta::new();
+        }
+        let in_scope = |path: &str| scopes.contains(scope_of(path));
+
+        let ids = ListIds::from_state(&self.state);
+        let view = self.index_view_within(&ids, in_scope);
+        let mut target = view.clone();
+        for (path, _) in &indexed.removes {
+            target.remove(path);
+        }
+        for (path, value, _) in &indexed.inserts {
+            target.insert(path.clone(), value.clone());
+        }
+        self.reconcile_aligned(&ids, &view, &target, in_scope)
+    }
+}
+
+/// Inputs for matching a list's target entries to its existing IDs.
+struct ListAlignment<'a> {
+    state: &'a OrMap<String, Value>,
+    ids: &'a ListIds,
+    view: &'a BTreeMap<String, Value>,
+    target: &'a BTreeMap<String, Value>,
+}
+
+impl ListAlignment<'_> {
+    /// Assign an ID to each target entry of a list and add the position
+    /// entries of the list to `stable`.
+    fn align(
+        &self,
+        list: &str,
+        indexed_list: &str,
+        stable: &mut BTreeMap<String, Value>,
+    ) -> BTreeMap<usize, String> {
+        let old_list = self.ids.index_path(list);
+        let old_ids = if old_list.is_some() {
+            self.ids.entries(list)
+        } else {
+            &[]
+        };
+        let old: Vec<_> = (0..old_ids.len())
+            .map(|k| {
+                entry_content(
+                    self.view,
+                    &format!("{}[{k}]", old_list.as_deref().unwrap_or_default()),
+                )
+            })
+            .collect();
+        let indices = list_indices(self.target, indexed_list);
+        let new: Vec<_> = indices
+            .iter()
+            .map(|index| entry_content(self.target, &format!("{indexed_list}[{index}]")))
+            .collect();
+
+        // Entries left unmatched between the same anchors are edits in place
+        let mut matched: Vec<Option<usize>> = vec![None; new.len()];
+        let (mut next_old, mut next_new) = (0, 0);
+        let anchors = common_entries(&old, &new);
+        for (o, n) in anchors.into_iter().chain([(old.len(), new.len())]) {
+            for (edited_old, edited_new) in (next_old..o).zip(next_new..n) {
+                matched[edited_new] = Some(edited_old);
+            }
+            if n < new.len() {
+                matched[n] = Some(o);
+            }
+            next_old = o + 1;
+            next_new = n + 1;
+        }
+
+        let old_positions: Vec<&str> = old_ids
+            .iter()
+            .map(|id| {
+                self.state
+                    .get(&position_path(list, id))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let mut assigned: Vec<(String, String)> = Vec::with_capacity(new.len());
+        let mut n = 0;
+        while n < new.len() {
+            if let Some(o) = matched[n] {
+                assigned.push((old_ids[o].clone(), old_positions[o].to_string()));
+                n += 1;
+                continue;
+            }
+            let run_end = (n..new.len())
+                .find(|&k| matched[k].is_some())
+                .unwrap_or(new.len());
+            let lo = assigned
+                .last()
+                .map(|(_, position)| position.clone())
+                .unwrap_or_default();
+            // Neighbours sharing a position were inserted concurrently
+            let hi = matched
+                .get(run_end)
+                .copied()
+                .flatten()
+                .map(|o| old_positions[o])
+                .filter(|hi| *hi > lo.as_str());
+            for position in positions_between(&lo, hi, run_end - n) {
+                assigned.push((Uuid::new_v4().simple().to_string(), position));
+            }
+            n = run_end;
+        }
+
+        indices
+            .into_iter()
+            .zip(assigned)
+            .map(|(index, (id, position))| {
+                stable.insert(position_path(list, &id), Value::String(position));
+                (index, id)
+            })
+            .collect()
+    }
+}
+
+/// Leaves of a list entry, keyed relative to the entry.
+fn entry_content<'a>(
+    leaves: &'a BTreeMap<String, Value>,
+    entry: &str,
+) -> BTreeMap<&'a str, &'a Value> {
+    leaves
+        .range(entry.to_string()..)
+        .take_while(|(path, _)| path.starts_with(entry))
+        .map(|(path, value)| (&path[entry.len()..], value))
+        .collect()
+}
+
+/// Distinct entry indices of a list, in order.
+fn list_indices(leaves: &BTreeMap<String, Value>, list: &str) -> Vec<usize> {
+    let prefix = format!("{list}[");
+    let indices: BTreeSet<usize> = leaves
+        .range(prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&prefix))
+        .filter_map(|(path, _)| {
+            let rest = &path[prefix.len()..];
+            rest[..rest.find(']')?].parse().ok()
+        })
+        .collect();
+    indices.into_iter().collect()
+}
+
+/// Index pairs of a longest common subsequence of `old` and `new`.
+fn common_entries<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Digits of a fractional position, `a` being zero.
+const POSITION_BASE: u8 = 26;
+
+/// A position strictly between `lo` and `hi`, or after `lo` if `hi` is
+/// `None`.
+///
+/// Positions are base-26 fractions written with `a`–`z`, compared as
+/// strings. Generated positions never end in `a`, so one always fits
+/// between any two.
+fn position_between(lo: &str, hi: Option<&str>) -> String {
+    let lo = lo.as_bytes();
+    let mut hi = hi.map(str::as_bytes);
+    let mut out = Vec::new();
+    for i in 0.. {
+        let l = lo.get(i).map_or(0, |c| c - b'a');
+        let h = hi.map_or(POSITION_BASE, |hi| hi.get(i).map_or(0, |c| c - b'a'));
+        if h > l + 1 {
+            out.push(b'a' + (l + h) / 2);
+            break;
+        }
+        out.push(b'a' + l);
+        if h == l + 1 || (i >= lo.len() && hi.is_some_and(|hi| i >= hi.len())) {
+            hi = None;
+        }
+    }
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// `count` ascending positions between `lo` and `hi`, spread by bisection
+/// so their length grows with the logarithm of `count`.
+fn positions_between(lo: &str, hi: Option<&str>, count: usize) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let mid = position_between(lo, hi);
+    let before = (count - 1) / 2;
+    let mut positions = positions_between(lo, Some(&mid), before);
+    positions.extend(positions_between(&mid, hi, count - 1 - before));
+    positions.insert(before, mid);
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocId;
+    use crate::hlc::Hlc;
+    use serde_json::json;
+
+    fn leaves(entries: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        entries
+            .iter()
+            .map(|(path, value)| ((*path).to_string(), value.clone()))
+            .collect()
+    }
+
+    fn replicas() -> (CrdtDocument, CrdtDocument) {
+        let id = DocId::value_view("aas1", "sm1");
+        let mut a = CrdtDocument::new(id.clone(), Hlc::new(Uuid::new_v4()));
+        let mut b = CrdtDocument::new(id, Hlc::new(Uuid::new_v4()));
+        let delta = a.reconcile_indexed(&leaves(&[
+            ("Phases[0]", json!(230)),
+            ("Phases[1]", json!(231)),
+            ("Phases[2]", json!(232)),
+        ]));
+        a.apply_delta(&delta).unwrap();
+        b.apply_delta(&delta).unwrap();
+        (a, b)
+    }
+
+    #[test]
+    fn stable_delta_compares_only_touched_elements() {
+        let (mut a, _) = replicas();
+        let mut extra = Delta::new();
+        extra.add_insert("Status".to_string(), json!("Running"), a.clock.tick());
+        // An entry of a list without a position has no index path
+        extra.add_insert("Modes[orphan]".to_string(), json!(1), a.clock.tick());
+        a.apply_delta(&extra).unwrap();
+
+        let mut event = Delta::new();
+        event.add_insert("Status".to_string(), json!("Stopped"), a.clock.tick());
+        let delta = a.stable_delta(&event);
+        assert_eq!(delta.inserts.len(), 1);
+        assert_eq!(delta.inserts[0].0, "Status");
+        assert!(delta.removes.is_empty());
+        a.apply_delta(&delta).unwrap();
+
+        // Touching a list realigns that list only
+        let mut event = Delta::new();
+        event.add_remove("Phases[0]".to_string(), a.clock.tick());
+        let delta = a.stable_delta(&event);
+        assert!(delta.inserts.is_empty());
+        assert_eq!(delta.removes.len(), 2);
+        assert!(delta
+            .removes
+            .iter()
+            .all(|(path, _)| path.starts_with("Phases[")));
+        a.apply_delta(&delta).unwrap();
+        assert_eq!(
+            a.index_view(),
+            leaves(&[
+                ("Phases[0]", json!(231)),
+                ("Phases[1]", json!(232)),
+                ("Status", json!("Stopped")),
+            ])
+        );
+    }
+
+    #[test]
+    fn positions_order_between_neighbours() {
+        let first = position_between("", None);
+        let after = position_between(&first, None);
+        let before = position_between("", Some(&first));
+        let middle = position_between(&first, Some(&after));
+        assert!(before < first && first < middle && middle < after);
+
+        let spread = positions_between("", None, 100);
+        assert!(spread.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(spread.iter().all(|position| position.len() <= 3));
+    }
+
+    #[test]
+    fn list_entries_get_stable_ids() {
+        let (a, _) = replicas();
+        let ids = ListIds::from_state(&a.state);
+        let entries = ids.entries("Phases");
+        assert_eq!(entries.len(), 3);
+
+        let stable = format!("Phases[{}]", entries[1]);
+        assert_eq!(a.get(&stable), Some(&json!(231)));
+        assert_eq!(ids.index_path(&stable).as_deref(), Some("Phases[1]"));
+        assert_eq!(ids.stable_path("Phases[1]"), Some(stable));
+        assert_eq!(ids.stable_path("Phases[3]"), None);
+        assert_eq!(
+            a.index_view(),
+            leaves(&[
+                ("Phases[0]", json!(230)),
+                ("Phases[1]", json!(231)),
+                ("Phases[2]", json!(232)),
+            ])
+        );
+    }
+
+    #[test]
+    fn concurrent_inserts_keep_entry_identity() {
+        let (mut a, mut b) = replicas();
+
+        // A inserts at the head while B edits the last entry by index
+        let insert = a.reconcile_indexed(&leaves(&[
+            ("Phases[0]", json!(229)),
+            ("Phases[1]", json!(230)),
+            ("Phases[2]", json!(231)),
+            ("Phases[3]", json!(232)),
+        ]));
+        let mut edit = Delta::new();
+        edit.add_insert("Phases[2]".to_string(), json!(240), b.clock.tick());
+        let edit = b.stable_delta(&edit);
+        b.apply_delta(&edit).unwrap();
+        // B also inserts at the head concurrently
+        let mut head = BTreeMap::new();
+        for (i, value) in [228, 230, 231, 240].into_iter().enumerate() {
+            head.insert(format!("Phases[{i}]"), json!(value));
+        }
+        let head = b.reconcile_indexed(&head);
+
+        a.apply_delta(&insert).unwrap();
+        b.apply_delta(&head).unwrap();
+        a.apply_delta(&edit).unwrap();
+        a.apply_delta(&head).unwrap();
+        b.apply_delta(&insert).unwrap();
+
+        let view = a.index_view();
+        assert_eq!(view, b.index_view());
+        let values: Vec<_> = view.values().cloned().collect();
+        assert_eq!(values.len(), 5);
+        assert_eq!(&values[2..], [json!(230), json!(231), json!(240)]);
+    }
+
+    #[test]
+    fn concurrent_removals_and_edits_converge() {
+        let (mut a, mut b) = replicas();
+
+        let mut remove = Delta::new();
+        remove.add_remove("Phases[0]".to_string(), a.clock.tick());
+        let remove = a.stable_delta(&remove);
+
+        let edit = b.reconcile_indexed(&leaves(&[
+            ("Phases[0]", json!(230)),
+            ("Phases[1]", json!(241)),
+        ]));
+
+        a.apply_delta(&remove).unwrap();
+        b.apply_delta(&edit).unwrap();
+        a.apply_delta(&edit).unwrap();
+        b.apply_delta(&remove).unwrap();
+
+        assert_eq!(a.index_view(), b.index_view());
+        assert_eq!(a.index_view(), leaves(&[("Phases[0]", json!(241))]));
+    }
+
+    #[test]
+    fn structural_changes_rewrite_the_list() {
+        let (mut a, _) = replicas();
+
+        let mut edit = Delta::new();
+        edit.add_insert("Phases[1]".to_string(), json!(235), a.clock.tick());
+        let edit = a.stable_delta(&edit);
+        a.apply_delta(&edit).unwrap();
+        let writes = indexed_writes(&a.state, &edit);
+        assert!(writes.lists.is_empty());
+        assert_eq!(writes.delta.inserts.len(), 1);
+        assert_eq!(writes.delta.inserts[0].0, "Phases[1]");
+
+        let insert = a.reconcile_indexed(&leaves(&[
+            ("Phases[0]", json!(229)),
+            ("Phases[1]", json!(230)),
+            ("Phases[2]", json!(235)),
+            ("Phases[3]", json!(232)),
+        ]));
+        a.apply_delta(&insert).unwrap();
+        let writes = indexed_writes(&a.state, &insert);
+        assert_eq!(writes.lists, BTreeSet::from(["Phases".to_string()]));
+        assert!(writes.delta.is_empty());
+    }
+}
@@ -8,12 +8,26 @@
 //! |--------------|----------|
 //! | Property | LWW register (HLC + actor tiebreaker) |
 //! | Range | LWW per bound (min/max) |
-//! | MultiLanguageProperty | LWW per language code |
-//! | SubmodelElementCollection | OR-Map of children by idShort |
-//! | SubmodelElementList | Stable element IDs (not list indices) |
+//! | `MultiLanguageProperty` | LWW per language code |
+//! | `SubmodelElementCollection` | OR-Map of children by idShort |
+//! | `SubmodelElementList` | Stable element IDs (not list indices) |
 //! | File/Blob | LWW pointer to content-addressed blob |
+//!
+//! Per-field elements are replicated as one entry per field, keyed
+//! `{idShortPath}.{field}`, so concurrent edits to different bounds or
+//! languages both survive. [`ElementTypes`] records which paths hold such
+//! elements so their values can be split on ingestion and reassembled on
+//! egress.
+//!
+//! Some strategies are chosen per path rather than per type, through
+//! [`PathPattern`]s: multi-value paths keep every concurrent value until a
+//! resolving write, see [`crate::CrdtDocument::conflicts`], and counter
+//! paths add up concurrent changes, see [`crate::counters`]. Counters can
+//! also be chosen by the `semanticId` of their element.
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 
 /// The type of an AAS submodel element.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -56,11 +70,33 @@ pub enum MergeStrategy {
     PerFieldLww,
     /// OR-Map: children merged as a set with add-wins
     OrMap,
-    /// Content-addressed: pointer to immutable content
+    /// Content-addressed: pointer to immutable content, see [`crate::BlobRef`]
     ContentAddressed,
 }
 
 impl ElementType {
+    /// Parse the `modelType` of a submodel element.
+    #[must_use]
+    pub fn from_model_type(model_type: &str) -> Option<Self> {
+        let element_type = match model_type {
+            "Property" => ElementType::Property,
+            "Range" => ElementType::Range,
+            "MultiLanguageProperty" => ElementType::MultiLanguageProperty,
+            "ReferenceElement" => ElementType::ReferenceElement,
+            "Blob" => ElementType::Blob,
+            "File" => ElementType::File,
+            "SubmodelElementCollection" => ElementType::SubmodelElementCollection,
+            "SubmodelElementList" => ElementType::SubmodelElementList,
+            "AnnotatedRelationshipElement" => ElementType::AnnotatedRelationshipElement,
+            "BasicEventElement" => ElementType::BasicEventElement,
+            "Entity" => ElementType::Entity,
+            "Operation" => ElementType::Operation,
+            "Capability" => ElementType::Capability,
+            _ => return None,
+        };
+        Some(element_type)
+    }
+
     /// Get the merge strategy for this element type.
     #[must_use]
     pub fn merge_strategy(&self) -> MergeStrategy {
@@ -87,6 +123,214 @@ impl ElementType {
     }
 }
 
+/// A glob over idShortPaths selecting paths for a merge strategy.
+///
+/// `*` matches any run of characters, including `.` and list brackets, so
+/// `Setpoints.*` covers every element below `Setpoints`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PathPattern(String);
+
+impl PathPattern {
+    /// Create a pattern from its glob.
+    #[must_use]
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self(pattern.into())
+    }
+
+    /// Get the glob of this pattern.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check if `path` matches this pattern.
+    #[must_use]
+    pub fn matches(&self, path: &str) -> bool {
+        let (pattern, path) = (self.0.as_bytes(), path.as_bytes());
+        let (mut p, mut s) = (0, 0);
+        // Last `*` seen and the path position it currently stands for
+        let mut star: Option<(usize, usize)> = None;
+
+        while s < path.len() {
+            match pattern.get(p) {
+                Some(b'*') => {
+                    star = Some((p, s));
+                    p += 1;
+                }
+                Some(&c) if c == path[s] => {
+                    p += 1;
+                    s += 1;
+                }
+                _ => match star {
+                    // Let the last `*` absorb one more character
+                    Some((star_p, star_s)) => {
+                        star = Some((star_p, star_s + 1));
+                        p = star_p + 1;
+                        s = star_s + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+        pattern[p..].iter().all(|&c| c == b'*')
+    }
+}
+
+impl std::fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Element types of a submodel, keyed by idShortPath.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElementTypes {
+    types: BTreeMap<String, ElementType>,
+    semantic_ids: BTreeMap<String, String>,
+}
+
+impl ElementTypes {
+    /// Create an empty type map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the type of the element at `path`.
+    pub fn insert(&mut self, path: impl Into<String>, element_type: ElementType) {
+        self.types.insert(path.into(), element_type);
+    }
+
+    /// Get the type of the element at `path`, if known.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<ElementType> {
+        self.types.get(path).copied()
+    }
+
+    /// Record the `semanticId` of the element at `path`.
+    pub fn insert_semantic_id(&mut self, path: impl Into<String>, semantic_id: impl Into<String>) {
+        self.semantic_ids.insert(path.into(), semantic_id.into());
+    }
+
+    /// Get the `semanticId` of the element at `path`, if known.
+    #[must_use]
+    pub fn semantic_id(&self, path: &str) -> Option<&str> {
+        self.semantic_ids.get(path).map(String::as_str)
+    }
+
+    /// Get an iterator over the paths of elements with a known
+    /// `semanticId`, and their IDs.
+    pub fn semantic_ids(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.semantic_ids
+            .iter()
+            .map(|(path, id)| (path.as_str(), id.as_str()))
+    }
+
+    /// Record every type and `semanticId` known to `other`.
+    pub fn extend(&mut self, other: &ElementTypes) {
+        self.types
+            .extend(other.types.iter().map(|(path, ty)| (path.clone(), *ty)));
+        self.semantic_ids.extend(
+            other
+                .semantic_ids
+                .iter()
+                .map(|(path, id)| (path.clone(), id.clone())),
+        );
+    }
+
+    /// Get the number of known element types.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Check if no element types are known.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Split a replicated path into its per-field element and field name.
+    ///
+    /// Returns `None` unless the parent of `path` is a per-field element.
+    #[must_use]
+    pub fn field_owner<'a>(&self, path: &'a str) -> Option<(&'a str, &'a str)> {
+        let (owner, field) = path.rsplit_once('.')?;
+        let per_field = self
+            .get(owner)
+            .is_some_and(|ty| ty.merge_strategy() == MergeStrategy::PerFieldLww);
+        per_field.then_some((owner, field))
+    }
+
+    /// Split the value of the element at `path` into replicated entries.
+    ///
+    /// Per-field elements yield one entry per field; every other element
+    /// is replicated whole.
+    #[must_use]
+    pub fn split(&self, path: &str, value: &Value) -> Vec<(String, Value)> {
+        let fields: Vec<(String, Value)> = match (self.get(path), value) {
+            (Some(ElementType::Range), Value::Object(bounds)) => bounds
+                .iter()
+                .filter(|(bound, _)| matches!(bound.as_str(), "min" | "max"))
+                .map(|(bound, value)| (bound.clone(), value.clone()))
+                .collect(),
+            (Some(ElementType::MultiLanguageProperty), Value::Array(texts)) => {
+                texts.iter().flat_map(language_texts).collect()
+            }
+            _ => return vec![(path.to_string(), value.clone())],
+        };
+
+        fields
+            .into_iter()
+            .map(|(field, value)| (format!("{path}.{field}"), value))
+            .collect()
+    }
+
+    /// Reassemble the `$value` of a per-field element from its fields.
+    #[must_use]
+    pub fn join<'a>(
+        &self,
+        owner: &str,
+        fields: impl IntoIterator<Item = (&'a str, &'a Value)>,
+    ) -> Value {
+        match self.get(owner) {
+            Some(ElementType::MultiLanguageProperty) => Value::Array(
+                fields
+                    .into_iter()
+                    .map(|(language, text)| {
+                        Value::Object(Map::from_iter([(language.to_string(), text.clone())]))
+                    })
+                    .collect(),
+            ),
+            _ => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field.to_string(), value.clone()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Language and text pairs of a `MultiLanguageProperty` entry.
+///
+/// Accepts both the `$value` form `{"en": "text"}` and the normal
+/// serialization `{"language": "en", "text": "text"}`.
+fn language_texts(entry: &Value) -> Vec<(String, Value)> {
+    let Value::Object(fields) = entry else {
+        return Vec::new();
+    };
+
+    match (fields.get("language"), fields.get("text")) {
+        (Some(Value::String(language)), Some(text)) => vec![(language.clone(), text.clone())],
+        _ => fields
+            .iter()
+            .map(|(language, text)| (language.clone(), text.clone()))
+            .collect(),
+    }
+}
+
 /// Path segment for addressing within an AAS structure.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PathSegment {
@@ -197,6 +441,24 @@ impl std::fmt::Display for CanonicalPath {
 mod tests {
     use super::*;
 
+    #[test]
+    fn path_patterns_match_globs() {
+        let setpoints = PathPattern::new("Setpoints.*");
+        assert!(setpoints.matches("Setpoints.Temperature"));
+        assert!(setpoints.matches("Setpoints.Zones[2].Temperature"));
+        assert!(!setpoints.matches("Setpoints"));
+        assert!(!setpoints.matches("Measurements.Temperature"));
+
+        let limits = PathPattern::new("*.Limit*");
+        assert!(limits.matches("Motor.LimitHigh"));
+        assert!(limits.matches("Line.Motor.Limit"));
+        assert!(!limits.matches("Motor.Speed"));
+
+        assert!(PathPattern::new("Speed").matches("Speed"));
+        assert!(!PathPattern::new("Speed").matches("SpeedMax"));
+        assert!(PathPattern::new("*").matches(""));
+    }
+
     #[test]
     fn element_type_strategies() {
         assert_eq!(ElementType::Property.merge_strategy(), MergeStrategy::Lww);
@@ -214,6 +476,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn per_field_elements_split_and_join() {
+        let mut types = ElementTypes::new();
+        types.insert("Limits", ElementType::Range);
+        types.insert("Name", ElementType::MultiLanguageProperty);
+        types.insert("Temperature", ElementType::Property);
+
+        let limits = types.split("Limits", &serde_json::json!({"min": 0, "max": 100}));
+        assert_eq!(
+            limits,
+            [
+                ("Limits.max".to_string(), serde_json::json!(100)),
+                ("Limits.min".to_string(), serde_json::json!(0)),
+            ]
+        );
+
+        // Normal serialization and `$value` split the same way
+        let normal = serde_json::json!([{"language": "en", "text": "Pump"}]);
+        let value = serde_json::json!([{"en": "Pump"}]);
+        assert_eq!(types.split("Name", &normal), types.split("Name", &value));
+
+        assert_eq!(
+            types.split("Temperature", &serde_json::json!(25.5)),
+            [("Temperature".to_string(), serde_json::json!(25.5))]
+        );
+
+        assert_eq!(types.field_owner("Name.de"), Some(("Name", "de")));
+        assert_eq!(types.field_owner("Temperature.unit"), None);
+
+        let en = serde_json::json!("Pump");
+        let de = serde_json::json!("Pumpe");
+        assert_eq!(
+            types.join("Name", [("de", &de), ("en", &en)]),
+            serde_json::json!([{"de": "Pumpe"}, {"en": "Pump"}])
+        );
+    }
+
     #[test]
     fn canonical_path_roundtrip() {
         let path = CanonicalPath::from_segments(vec![
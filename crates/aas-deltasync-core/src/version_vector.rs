@@ -0,0 +1,329 @@
+//! Version vectors for causal summaries.
+//!
+//! A version vector maps each actor to the highest timestamp observed from
+//! it. Replicas exchange version vectors during anti-entropy to find exactly
+//! which deltas the other side is missing. Unlike a single timestamp
+//! threshold, this stays correct when one actor's clock lags behind another's.
+//!
+//! Deltas can arrive out of order, for example relayed by anti-entropy or
+//! redelivered by the MQTT broker. Each delta therefore names its
+//! author's previous timestamp ([`crate::Delta::prev`]), and the vector only
+//! advances an actor's entry over contiguous deltas. Deltas seen past a gap
+//! are kept as separate ranges until the gap is filled, so a summary never
+//! claims the missing deltas.
+
+use crate::hlc::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Highest contiguously observed timestamp per actor.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionVector {
+    /// Actor ID -> highest timestamp seen from that actor with no gap before
+    entries: BTreeMap<Uuid, Timestamp>,
+    /// Actor ID -> ranges seen past a gap, from the timestamp a delta
+    /// followed (exclusive) to the delta's latest timestamp (inclusive)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pending: BTreeMap<Uuid, BTreeMap<Timestamp, Timestamp>>,
+}
+
+impl VersionVector {
+    /// Create an empty version vector.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a timestamp from its actor, with nothing missing before it.
+    ///
+    /// Returns `true` if the entry for the actor advanced.
+    pub fn observe(&mut self, timestamp: Timestamp) -> bool {
+        let advanced = match self.entries.get_mut(&timestamp.actor_id) {
+            Some(current) if *current >= timestamp => false,
+            Some(current) => {
+                *current = timestamp;
+                true
+            }
+            None => {
+                self.entries.insert(timestamp.actor_id, timestamp);
+                true
+            }
+        };
+        if advanced {
+            self.close_gaps(timestamp.actor_id);
+        }
+        advanced
+    }
+
+    /// Record a timestamp of a delta whose author's previous timestamp was
+    /// `prev`.
+    ///
+    /// Unless `prev` is covered, the timestamp is kept past a gap and the
+    /// actor's entry does not advance. A `prev` of another actor is
+    /// ignored.
+    pub fn observe_after(&mut self, timestamp: Timestamp, prev: Option<Timestamp>) {
+        match prev {
+            Some(prev)
+                if prev.actor_id == timestamp.actor_id
+                    && prev < timestamp
+                    && self.get(&prev.actor_id).map_or(true, |seen| seen < prev) =>
+            {
+                let latest = self
+                    .pending
+                    .entry(timestamp.actor_id)
+                    .or_default()
+                    .entry(prev)
+                    .or_insert(timestamp);
+                if timestamp > *latest {
+                    *latest = timestamp;
+                }
+            }
+            _ => {
+                self.observe(timestamp);
+            }
+        }
+    }
+
+    /// Advance the entry of `actor_id` over the ranges it now reaches.
+    fn close_gaps(&mut self, actor_id: Uuid) {
+        let Some(ranges) = self.pending.get_mut(&actor_id) else {
+            return;
+        };
+        let Some(mut seen) = self.entries.get(&actor_id).copied() else {
+            return;
+        };
+        while let Some(entry) = ranges.first_entry() {
+            if *entry.key() > seen {
+                break;
+            }
+            seen = seen.max(entry.remove());
+        }
+        if ranges.is_empty() {
+            self.pending.remove(&actor_id);
+        }
+        self.entries.insert(actor_id, seen);
+    }
+
+    /// Get the highest timestamp seen from an actor with no gap before it.
+    #[must_use]
+    pub fn get(&self, actor_id: &Uuid) -> Option<Timestamp> {
+        self.entries.get(actor_id).copied()
+    }
+
+    /// Check whether a timestamp is covered by this vector.
+    #[must_use]
+    pub fn contains(&self, timestamp: &Timestamp) -> bool {
+        self.entries
+            .get(&timestamp.actor_id)
+            .is_some_and(|seen| seen >= timestamp)
+            || self.pending.get(&timestamp.actor_id).is_some_and(|ranges| {
+                ranges
+                    .range(..timestamp)
+                    .next_back()
+                    .is_some_and(|(_, latest)| latest >= timestamp)
+            })
+    }
+
+    /// Check whether an actor has timestamps past a gap.
+    #[must_use]
+    pub fn has_gap(&self, actor_id: &Uuid) -> bool {
+        self.pending.contains_key(actor_id)
+    }
+
+    /// Merge with another version vector, keeping the maximum per actor
+    /// and the ranges either side holds past a gap.
+    pub fn merge(&mut self, other: &Self) {
+        for (actor_id, ranges) in &other.pending {
+            for (prev, latest) in ranges {
+                self.observe_after(*latest, Some(*prev));
+            }
+            self.close_gaps(*actor_id);
+        }
+        for timestamp in other.entries.values() {
+            self.observe(*timestamp);
+        }
+    }
+
+    /// Check whether this vector has seen everything the other has.
+    #[must_use]
+    pub fn dominates(&self, other: &Self) -> bool {
+        // An entry covers every earlier timestamp, a range only its own
+        other
+            .entries
+            .values()
+            .all(|timestamp| self.get(&timestamp.actor_id) >= Some(*timestamp))
+            && other
+                .pending
+                .values()
+                .flat_map(BTreeMap::values)
+                .all(|timestamp| self.contains(timestamp))
+    }
+
+    /// Get an iterator over the highest contiguous timestamp of every actor.
+    pub fn iter(&self) -> impl Iterator<Item = &Timestamp> {
+        self.entries.values()
+    }
+
+    /// Get the number of actors tracked.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pending
+            .keys()
+            .filter(|actor_id| !self.entries.contains_key(actor_id))
+            .count()
+            + self.entries.len()
+    }
+
+    /// Check if no actor has been observed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.pending.is_empty()
+    }
+
+    /// Serialize to CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if serialization fails.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, VersionVectorError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| VersionVectorError::Serialize(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Deserialize from CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if deserialization fails.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, VersionVectorError> {
+        ciborium::from_reader(bytes).map_err(|e| VersionVectorError::Deserialize(e.to_string()))
+    }
+}
+
+/// Errors that can occur encoding version vectors.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum VersionVectorError {
+    /// Serialization failed
+    #[error("serialization failed: {0}")]
+    Serialize(String),
+    /// Deserialization failed
+    #[error("deserialization failed: {0}")]
+    Deserialize(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_timestamp(physical: u64, logical: u32, actor_num: u8) -> Timestamp {
+        Timestamp {
+            physical_ms: physical,
+            logical,
+            actor_id: Uuid::from_bytes([actor_num; 16]),
+        }
+    }
+
+    #[test]
+    fn observe_keeps_highest_per_actor() {
+        let mut vv = VersionVector::new();
+
+        assert!(vv.observe(make_timestamp(2000, 0, 1)));
+        assert!(!vv.observe(make_timestamp(1000, 0, 1)));
+        assert!(vv.observe(make_timestamp(500, 0, 2)));
+
+        assert_eq!(vv.len(), 2);
+        assert_eq!(
+            vv.get(&Uuid::from_bytes([1; 16])),
+            Some(make_timestamp(2000, 0, 1))
+        );
+        assert!(vv.contains(&make_timestamp(1500, 7, 1)));
+        assert!(!vv.contains(&make_timestamp(600, 0, 2)));
+        assert!(!vv.contains(&make_timestamp(1, 0, 3)));
+    }
+
+    #[test]
+    fn lagging_actor_is_not_hidden_by_faster_clock() {
+        // Actor 2's clock lags far behind actor 1's; a single timestamp
+        // threshold would consider its writes already seen.
+        let mut vv = VersionVector::new();
+        vv.observe(make_timestamp(10_000, 0, 1));
+
+        assert!(!vv.contains(&make_timestamp(1000, 0, 2)));
+    }
+
+    #[test]
+    fn merge_and_dominance() {
+        let mut vv_a = VersionVector::new();
+        vv_a.observe(make_timestamp(2000, 0, 1));
+        vv_a.observe(make_timestamp(1000, 0, 2));
+
+        let mut vv_b = VersionVector::new();
+        vv_b.observe(make_timestamp(1000, 0, 1));
+        vv_b.observe(make_timestamp(3000, 0, 2));
+
+        // Concurrent: neither dominates
+        assert!(!vv_a.dominates(&vv_b));
+        assert!(!vv_b.dominates(&vv_a));
+
+        let mut merged = vv_a.clone();
+        merged.merge(&vv_b);
+
+        assert!(merged.dominates(&vv_a));
+        assert!(merged.dominates(&vv_b));
+        assert_eq!(
+            merged.get(&Uuid::from_bytes([2; 16])),
+            Some(make_timestamp(3000, 0, 2))
+        );
+        assert!(merged.dominates(&VersionVector::new()));
+    }
+
+    #[test]
+    fn out_of_order_deltas_leave_a_gap() {
+        let actor_id = Uuid::from_bytes([1; 16]);
+        let first = make_timestamp(1000, 0, 1);
+        let second = make_timestamp(2000, 0, 1);
+        let third = make_timestamp(3000, 0, 1);
+
+        let mut vv = VersionVector::new();
+        vv.observe_after(first, None);
+
+        // The third delta overtakes the second
+        vv.observe_after(third, Some(second));
+        assert_eq!(vv.get(&actor_id), Some(first));
+        assert!(vv.has_gap(&actor_id));
+        assert!(vv.contains(&third));
+        assert!(!vv.contains(&second));
+
+        // A peer learns of the gap through the wire format too
+        let decoded = VersionVector::from_cbor(&vv.to_cbor().unwrap()).unwrap();
+        assert!(!decoded.contains(&second));
+        let mut merged = VersionVector::new();
+        merged.merge(&decoded);
+        assert_eq!(merged, vv);
+        let mut caught_up = VersionVector::new();
+        caught_up.observe(third);
+        assert!(!vv.dominates(&caught_up));
+        assert!(caught_up.dominates(&vv));
+
+        // Once the second arrives, the entry advances past both
+        vv.observe_after(second, Some(first));
+        assert_eq!(vv.get(&actor_id), Some(third));
+        assert!(!vv.has_gap(&actor_id));
+    }
+
+    #[test]
+    fn cbor_roundtrip() {
+        let mut vv = VersionVector::new();
+        vv.observe(make_timestamp(2000, 1, 1));
+        vv.observe(make_timestamp(1000, 4, 2));
+
+        let bytes = vv.to_cbor().unwrap();
+        let decoded = VersionVector::from_cbor(&bytes).unwrap();
+
+        assert_eq!(vv, decoded);
+        assert!(VersionVector::from_cbor(b"\xff").is_err());
+    }
+}
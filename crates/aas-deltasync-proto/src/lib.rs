@@ -7,10 +7,13 @@
 //! - `AgentHello`: Peer discovery and capability advertisement
 //! - `DocDelta`: Compact delta for incremental replication
 //! - `AntiEntropyRequest/Response`: State synchronization
+//! - `BlobRequest`/`BlobChunk`: Chunked transfer of attachment content
+//! - `ConflictReport`: Unresolved multi-value conflicts, for operators
 //!
 //! ## MQTT Topics
 //!
-//! Topic scheme: `aas-deltasync/v1/{tenant}/{doc_hash}/{message_type}`
+//! Topic scheme: `aas-deltasync/v2/{tenant}/{doc_hash}/{message_type}`,
+//! where `doc_hash` is [`topics::doc_hash`] of the document identifier.
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
@@ -18,5 +21,8 @@
 pub mod messages;
 pub mod topics;
 
-pub use messages::{AgentHello, AntiEntropyRequest, AntiEntropyResponse, DocDelta};
-pub use topics::TopicScheme;
+pub use messages::{
+    AgentHello, AntiEntropyRequest, AntiEntropyResponse, BlobChunk, BlobRequest, ConflictReport,
+    DocDelta, BLOB_CHUNK_SIZE,
+};
+pub use topics::{doc_hash, TopicScheme};
@@ -1,6 +1,6 @@
 //! Protocol messages for delta replication.
 
-use aas_deltasync_core::Timestamp;
+use aas_deltasync_core::{Conflict, DocId, RemoveSemantics, Timestamp, VersionVector};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -11,10 +11,13 @@ pub struct AgentHello {
     pub agent_id: Uuid,
     /// Supported capabilities (AAS service profile identifiers)
     pub capabilities: Vec<String>,
-    /// Clock summary for anti-entropy (serialized vector clock or digest)
+    /// Clock summary for anti-entropy (CBOR-encoded `VersionVector`)
     pub clock_summary: Vec<u8>,
     /// Agent version
     pub version: String,
+    /// Removal semantics of the sender's document, if announced
+    #[serde(default)]
+    pub remove_semantics: Option<RemoveSemantics>,
 }
 
 impl AgentHello {
@@ -26,9 +29,37 @@ impl AgentHello {
             capabilities,
             clock_summary: Vec::new(),
             version: env!("CARGO_PKG_VERSION").to_string(),
+            remove_semantics: None,
         }
     }
 
+    /// Create a hello announcing local state of a document.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the version vector cannot be serialized.
+    pub fn with_version_vector(
+        agent_id: Uuid,
+        capabilities: Vec<String>,
+        version: &VersionVector,
+    ) -> Result<Self, MessageError> {
+        let mut hello = Self::new(agent_id, capabilities);
+        hello.clock_summary = version
+            .to_cbor()
+            .map_err(|e| MessageError::Serialize(e.to_string()))?;
+        Ok(hello)
+    }
+
+    /// Decode the sender's version vector from `clock_summary`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the summary is not a CBOR-encoded version vector.
+    pub fn version_vector(&self) -> Result<VersionVector, MessageError> {
+        VersionVector::from_cbor(&self.clock_summary)
+            .map_err(|e| MessageError::Deserialize(e.to_string()))
+    }
+
     /// Serialize to CBOR bytes.
     ///
     /// # Errors
@@ -54,25 +85,30 @@ impl AgentHello {
 /// A document delta for incremental replication.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocDelta {
-    /// Document identifier (serialized `DocId`)
-    pub doc_id: String,
+    /// Document identifier
+    pub doc_id: DocId,
     /// Delta identifier (HLC timestamp bytes)
     pub delta_id: Vec<u8>,
     /// CBOR-encoded delta payload
     pub delta_payload: Vec<u8>,
     /// Optional Ed25519 signature
     pub signature: Option<Vec<u8>>,
+    /// Removal semantics of the document the delta was made for, if
+    /// announced
+    #[serde(default)]
+    pub remove_semantics: Option<RemoveSemantics>,
 }
 
 impl DocDelta {
     /// Create a new delta message.
     #[must_use]
-    pub fn new(doc_id: String, timestamp: Timestamp, payload: Vec<u8>) -> Self {
+    pub fn new(doc_id: DocId, timestamp: Timestamp, payload: Vec<u8>) -> Self {
         Self {
             doc_id,
             delta_id: timestamp.to_bytes(),
             delta_payload: payload,
             signature: None,
+            remove_semantics: None,
         }
     }
 
@@ -111,8 +147,8 @@ impl DocDelta {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AntiEntropyRequest {
     /// Document identifier
-    pub doc_id: String,
-    /// Digest of local state (for comparison)
+    pub doc_id: DocId,
+    /// Summary of local state (CBOR-encoded `VersionVector`)
     pub have_summary: Vec<u8>,
     /// Range of deltas being requested (optional)
     pub want_range: Option<DeltaRange>,
@@ -130,7 +166,7 @@ pub struct DeltaRange {
 impl AntiEntropyRequest {
     /// Create a new anti-entropy request.
     #[must_use]
-    pub fn new(doc_id: String, have_summary: Vec<u8>) -> Self {
+    pub fn new(doc_id: DocId, have_summary: Vec<u8>) -> Self {
         Self {
             doc_id,
             have_summary,
@@ -138,6 +174,31 @@ impl AntiEntropyRequest {
         }
     }
 
+    /// Create a request summarizing local state with a version vector.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the version vector cannot be serialized.
+    pub fn with_version_vector(
+        doc_id: DocId,
+        version: &VersionVector,
+    ) -> Result<Self, MessageError> {
+        let have_summary = version
+            .to_cbor()
+            .map_err(|e| MessageError::Serialize(e.to_string()))?;
+        Ok(Self::new(doc_id, have_summary))
+    }
+
+    /// Decode the requester's version vector from `have_summary`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the summary is not a CBOR-encoded version vector.
+    pub fn version_vector(&self) -> Result<VersionVector, MessageError> {
+        VersionVector::from_cbor(&self.have_summary)
+            .map_err(|e| MessageError::Deserialize(e.to_string()))
+    }
+
     /// Serialize to CBOR bytes.
     ///
     /// # Errors
@@ -164,31 +225,189 @@ impl AntiEntropyRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AntiEntropyResponse {
     /// Document identifier
-    pub doc_id: String,
+    pub doc_id: DocId,
     /// Deltas that the requester is missing
     pub deltas: Vec<DocDelta>,
     /// Full state snapshot (if delta set would be too large)
     pub snapshot: Option<Vec<u8>>,
+    /// Version vector covered by the snapshot (CBOR-encoded `VersionVector`)
+    #[serde(default)]
+    pub snapshot_version: Option<Vec<u8>>,
 }
 
 impl AntiEntropyResponse {
     /// Create a new response with deltas.
     #[must_use]
-    pub fn with_deltas(doc_id: String, deltas: Vec<DocDelta>) -> Self {
+    pub fn with_deltas(doc_id: DocId, deltas: Vec<DocDelta>) -> Self {
         Self {
             doc_id,
             deltas,
             snapshot: None,
+            snapshot_version: None,
         }
     }
 
     /// Create a new response with a full snapshot.
     #[must_use]
-    pub fn with_snapshot(doc_id: String, snapshot: Vec<u8>) -> Self {
+    pub fn with_snapshot(doc_id: DocId, snapshot: Vec<u8>) -> Self {
         Self {
             doc_id,
             deltas: Vec::new(),
             snapshot: Some(snapshot),
+            snapshot_version: None,
+        }
+    }
+
+    /// Serialize to CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if serialization fails.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, MessageError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| MessageError::Serialize(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Deserialize from CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if deserialization fails.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MessageError> {
+        ciborium::from_reader(bytes).map_err(|e| MessageError::Deserialize(e.to_string()))
+    }
+}
+
+/// Largest chunk of blob content sent in one message.
+///
+/// Chunks stay below the 10 KiB default packet limit of MQTT clients such
+/// as `rumqttc`, leaving room for the message envelope.
+pub const BLOB_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Request for blob content a document references but the sender lacks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobRequest {
+    /// Document referencing the blob
+    pub doc_id: DocId,
+    /// SHA-256 hash of the content, hex-encoded
+    pub content_hash: String,
+}
+
+impl BlobRequest {
+    /// Create a new blob request.
+    #[must_use]
+    pub fn new(doc_id: DocId, content_hash: impl Into<String>) -> Self {
+        Self {
+            doc_id,
+            content_hash: content_hash.into(),
+        }
+    }
+
+    /// Serialize to CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if serialization fails.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, MessageError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| MessageError::Serialize(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Deserialize from CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if deserialization fails.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MessageError> {
+        ciborium::from_reader(bytes).map_err(|e| MessageError::Deserialize(e.to_string()))
+    }
+}
+
+/// One chunk of blob content, answering a [`BlobRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobChunk {
+    /// SHA-256 hash of the whole content, hex-encoded
+    pub content_hash: String,
+    /// Position of this chunk, starting at zero
+    pub index: u32,
+    /// Number of chunks the content was split into
+    pub total: u32,
+    /// Chunk bytes
+    pub data: Vec<u8>,
+}
+
+impl BlobChunk {
+    /// Split content into chunks of at most [`BLOB_CHUNK_SIZE`] bytes.
+    ///
+    /// Empty content still yields one, empty, chunk.
+    #[must_use]
+    pub fn split(content_hash: &str, content: &[u8]) -> Vec<Self> {
+        let chunks: Vec<&[u8]> = if content.is_empty() {
+            vec![content]
+        } else {
+            content.chunks(BLOB_CHUNK_SIZE).collect()
+        };
+        let total = u32::try_from(chunks.len()).unwrap_or(u32::MAX);
+        chunks
+            .into_iter()
+            .zip(0..)
+            .map(|(data, index)| Self {
+                content_hash: content_hash.to_string(),
+                index,
+                total,
+                data: data.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Serialize to CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if serialization fails.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, MessageError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| MessageError::Serialize(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Deserialize from CBOR bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if deserialization fails.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MessageError> {
+        ciborium::from_reader(bytes).map_err(|e| MessageError::Deserialize(e.to_string()))
+    }
+}
+
+/// Unresolved multi-value conflicts of a document, as one agent sees them.
+///
+/// Published retained whenever the set changes, so operator tools see the
+/// current conflicts on subscribing; an empty report means all resolved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictReport {
+    /// Document holding the conflicts
+    pub doc_id: DocId,
+    /// Reporting agent
+    pub agent_id: Uuid,
+    /// Conflicting paths, sorted by path
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ConflictReport {
+    /// Create a new conflict report.
+    #[must_use]
+    pub fn new(doc_id: DocId, agent_id: Uuid, conflicts: Vec<Conflict>) -> Self {
+        Self {
+            doc_id,
+            agent_id,
+            conflicts,
         }
     }
 
@@ -228,6 +447,7 @@ pub enum MessageError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aas_deltasync_core::{LwwRegister, View};
 
     #[test]
     fn agent_hello_cbor_roundtrip() {
@@ -243,6 +463,21 @@ mod tests {
         assert_eq!(hello.capabilities, decoded.capabilities);
     }
 
+    #[test]
+    fn agent_hello_version_vector_roundtrip() {
+        let mut version = VersionVector::new();
+        version.observe(Timestamp {
+            physical_ms: 1_704_067_200_000,
+            logical: 1,
+            actor_id: Uuid::new_v4(),
+        });
+
+        let hello = AgentHello::with_version_vector(Uuid::new_v4(), Vec::new(), &version).unwrap();
+        let decoded = AgentHello::from_cbor(&hello.to_cbor().unwrap()).unwrap();
+
+        assert_eq!(decoded.version_vector().unwrap(), version);
+    }
+
     #[test]
     fn doc_delta_cbor_roundtrip() {
         let ts = Timestamp {
@@ -251,7 +486,8 @@ mod tests {
             actor_id: Uuid::new_v4(),
         };
 
-        let delta = DocDelta::new("doc1".to_string(), ts, vec![1, 2, 3]);
+        let doc_id = DocId::new("urn:example:aas:1", "urn:example:sm:data", View::Metadata);
+        let delta = DocDelta::new(doc_id, ts, vec![1, 2, 3]);
 
         let bytes = delta.to_cbor().unwrap();
         let decoded = DocDelta::from_cbor(&bytes).unwrap();
@@ -259,4 +495,59 @@ mod tests {
         assert_eq!(delta.doc_id, decoded.doc_id);
         assert_eq!(delta.delta_payload, decoded.delta_payload);
     }
+
+    #[test]
+    fn ae_request_version_vector_roundtrip() {
+        let mut version = VersionVector::new();
+        version.observe(Timestamp {
+            physical_ms: 1_704_067_200_000,
+            logical: 3,
+            actor_id: Uuid::new_v4(),
+        });
+
+        let request =
+            AntiEntropyRequest::with_version_vector(DocId::value_view("aas1", "sm1"), &version)
+                .unwrap();
+        let decoded = AntiEntropyRequest::from_cbor(&request.to_cbor().unwrap()).unwrap();
+
+        assert_eq!(decoded.version_vector().unwrap(), version);
+    }
+
+    #[test]
+    fn conflict_report_cbor_roundtrip() {
+        let timestamp = Timestamp {
+            physical_ms: 1_704_067_200_000,
+            logical: 0,
+            actor_id: Uuid::new_v4(),
+        };
+        let conflict = Conflict {
+            path: "Setpoints.Temperature".to_string(),
+            values: vec![
+                LwwRegister::new(serde_json::json!(80), timestamp),
+                LwwRegister::new(serde_json::json!("85"), timestamp),
+            ],
+        };
+        let doc_id = DocId::new("aas1", "sm1", View::Value);
+        let report = ConflictReport::new(doc_id, Uuid::new_v4(), vec![conflict.clone()]);
+
+        let decoded = ConflictReport::from_cbor(&report.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded.agent_id, report.agent_id);
+        assert_eq!(decoded.conflicts, vec![conflict]);
+    }
+
+    #[test]
+    fn blob_chunks_cover_the_content() {
+        let content = vec![7u8; BLOB_CHUNK_SIZE * 2 + 1];
+        let chunks = BlobChunk::split("abc", &content);
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.total == 3));
+        assert_eq!(chunks[2].index, 2);
+        assert_eq!(chunks[2].data.len(), 1);
+
+        let decoded = BlobChunk::from_cbor(&chunks[0].to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded.content_hash, "abc");
+        assert_eq!(decoded.data.len(), BLOB_CHUNK_SIZE);
+        assert_eq!(BlobChunk::split("empty", &[]).len(), 1);
+    }
 }
@@ -1,16 +1,46 @@
 //! MQTT topic scheme for delta replication.
 //!
-//! Topic structure: `aas-deltasync/v1/{tenant}/{doc_hash}/{message_type}`
+//! Topic structure: `aas-deltasync/v2/{tenant}/{doc_hash}/{message_type}`
 //!
 //! This allows:
 //! - Tenant isolation
 //! - Topic sharding by document hash
 //! - Message-type filtering
+//!
+//! ## Versions
+//!
+//! The protocol version also fixes how `doc_hash` is computed, so agents
+//! that hash differently never share topics by accident:
+//! - `v1`: `DefaultHasher` (unstable across Rust releases, no longer produced)
+//! - `v2`: [`doc_hash`], truncated SHA-256
 
+use aas_deltasync_core::DocId;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
 
 /// Protocol version for topic scheme.
-pub const PROTOCOL_VERSION: &str = "v1";
+pub const PROTOCOL_VERSION: &str = "v2";
+
+/// Number of SHA-256 bytes kept in a document hash.
+const DOC_HASH_BYTES: usize = 16;
+
+/// Hash a document identifier for topic sharding.
+///
+/// Returns the first 128 bits of the SHA-256 digest of the identifier's
+/// display form, hex-encoded. The result is part of the wire protocol and
+/// must not change within a protocol version.
+#[must_use]
+pub fn doc_hash(doc_id: &DocId) -> String {
+    let digest = Sha256::digest(doc_id.to_string().as_bytes());
+    digest[..DOC_HASH_BYTES].iter().fold(
+        String::with_capacity(DOC_HASH_BYTES * 2),
+        |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        },
+    )
+}
 
 /// Topic scheme configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +102,24 @@ impl TopicScheme {
         format!("{}/ae/response", self.base(doc_hash))
     }
 
+    /// Topic for blob content requests.
+    #[must_use]
+    pub fn blob_request(&self, doc_hash: &str) -> String {
+        format!("{}/blob/request", self.base(doc_hash))
+    }
+
+    /// Topic for blob content chunks.
+    #[must_use]
+    pub fn blob_chunk(&self, doc_hash: &str) -> String {
+        format!("{}/blob/chunk", self.base(doc_hash))
+    }
+
+    /// Topic for conflict reports, published retained.
+    #[must_use]
+    pub fn conflicts(&self, doc_hash: &str) -> String {
+        format!("{}/conflicts", self.base(doc_hash))
+    }
+
     /// Wildcard subscription for all messages of a document.
     #[must_use]
     pub fn doc_wildcard(&self, doc_hash: &str) -> String {
@@ -107,6 +155,9 @@ impl TopicScheme {
             "delta" => MessageType::Delta,
             "ae/request" => MessageType::AntiEntropyRequest,
             "ae/response" => MessageType::AntiEntropyResponse,
+            "blob/request" => MessageType::BlobRequest,
+            "blob/chunk" => MessageType::BlobChunk,
+            "conflicts" => MessageType::Conflicts,
             _ => return None,
         };
 
@@ -125,6 +176,12 @@ pub enum MessageType {
     AntiEntropyRequest,
     /// Anti-entropy response
     AntiEntropyResponse,
+    /// Blob content request
+    BlobRequest,
+    /// Blob content chunk
+    BlobChunk,
+    /// Conflict report
+    Conflicts,
 }
 
 #[cfg(test)]
@@ -138,15 +195,15 @@ mod tests {
 
         assert_eq!(
             scheme.hello(doc_hash),
-            "aas-deltasync/v1/factory-a/abc123def456/hello"
+            "aas-deltasync/v2/factory-a/abc123def456/hello"
         );
         assert_eq!(
             scheme.delta(doc_hash),
-            "aas-deltasync/v1/factory-a/abc123def456/delta"
+            "aas-deltasync/v2/factory-a/abc123def456/delta"
         );
         assert_eq!(
             scheme.ae_request(doc_hash),
-            "aas-deltasync/v1/factory-a/abc123def456/ae/request"
+            "aas-deltasync/v2/factory-a/abc123def456/ae/request"
         );
     }
 
@@ -154,7 +211,7 @@ mod tests {
     fn topic_parsing() {
         let scheme = TopicScheme::new("factory-a");
 
-        let topic = "aas-deltasync/v1/factory-a/abc123/delta";
+        let topic = "aas-deltasync/v2/factory-a/abc123/delta";
         let (doc_hash, msg_type) = scheme.parse(topic).unwrap();
 
         assert_eq!(doc_hash, "abc123");
@@ -165,21 +222,68 @@ mod tests {
     fn topic_parsing_ae() {
         let scheme = TopicScheme::new("site-b");
 
-        let topic = "aas-deltasync/v1/site-b/xyz789/ae/request";
+        let topic = "aas-deltasync/v2/site-b/xyz789/ae/request";
         let (doc_hash, msg_type) = scheme.parse(topic).unwrap();
 
         assert_eq!(doc_hash, "xyz789");
         assert_eq!(msg_type, MessageType::AntiEntropyRequest);
     }
 
+    #[test]
+    fn topic_parsing_blob() {
+        let scheme = TopicScheme::new("site-b");
+
+        let topic = scheme.blob_chunk("xyz789");
+        assert_eq!(topic, "aas-deltasync/v2/site-b/xyz789/blob/chunk");
+        assert_eq!(
+            scheme.parse(&topic),
+            Some(("xyz789".to_string(), MessageType::BlobChunk))
+        );
+        let topic = scheme.blob_request("xyz789");
+        assert_eq!(
+            scheme.parse(&topic).map(|(_, msg_type)| msg_type),
+            Some(MessageType::BlobRequest)
+        );
+        let topic = scheme.conflicts("xyz789");
+        assert_eq!(
+            scheme.parse(&topic).map(|(_, msg_type)| msg_type),
+            Some(MessageType::Conflicts)
+        );
+    }
+
     #[test]
     fn wildcard_topics() {
         let scheme = TopicScheme::new("tenant1");
 
         assert_eq!(
             scheme.doc_wildcard("doc1"),
-            "aas-deltasync/v1/tenant1/doc1/#"
+            "aas-deltasync/v2/tenant1/doc1/#"
+        );
+        assert_eq!(scheme.tenant_wildcard(), "aas-deltasync/v2/tenant1/#");
+    }
+
+    #[test]
+    fn doc_hash_golden_vectors() {
+        use aas_deltasync_core::View;
+
+        assert_eq!(
+            doc_hash(&DocId::value_view("aas1", "sm1")),
+            "ba33150e8ad67727f59690b604e749ad"
+        );
+        assert_eq!(
+            doc_hash(&DocId::value_view(
+                "urn:example:aas:1",
+                "urn:example:sm:data"
+            )),
+            "4f0fc13d281e810510592765bfc15f7a"
+        );
+        assert_eq!(
+            doc_hash(&DocId::new(
+                "urn:example:aas:1",
+                "urn:example:sm:data",
+                View::Metadata
+            )),
+            "d2eedb0b14c4ba20fd319554e0b6de95"
         );
-        assert_eq!(scheme.tenant_wildcard(), "aas-deltasync/v1/tenant1/#");
     }
 }